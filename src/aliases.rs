@@ -0,0 +1,294 @@
+use anyhow::{anyhow, Result};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::nostr::config_dir;
+
+// Cap on the commands one alias can turn into, aliases calling aliases multiply
+const MAX_COMMANDS: usize = 100;
+
+// Cap on the aliases expanded for one command line, including ones that add no
+// commands, which the cap above doesn't see
+const MAX_EXPANSIONS: usize = 1000;
+
+/// User-defined commands from /alias, kept in ~/.config/bitchatx/aliases.toml
+///
+/// An expansion is one or more commands separated by `;` (`\;` for a literal one).
+/// `$0`, `$1`... are the alias's arguments, `$1-` is the rest of the line from the
+/// second argument on, `$*` all of it, `$nick` our nickname and `$chan` the current
+/// channel. An expansion that uses no arguments gets them appended, so
+/// `/alias j /join` makes `/j dr5reg` work. Lines that don't start with `/` are
+/// sent to the current channel.
+#[derive(Debug, Default)]
+pub struct Aliases {
+    aliases: BTreeMap<String, String>,
+}
+
+impl Aliases {
+    fn path() -> PathBuf {
+        config_dir().join("aliases.toml")
+    }
+
+    /// Saved aliases, or none if there's no file yet (or it can't be read)
+    pub fn load() -> Self {
+        let aliases = std::fs::read_to_string(Self::path())
+            .ok()
+            .and_then(|contents| toml::from_str(&contents).ok())
+            .unwrap_or_default();
+        Self { aliases }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, toml::to_string(&self.aliases)?)?;
+        Ok(())
+    }
+
+    pub fn path_display() -> String {
+        Self::path().display().to_string()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.aliases.get(&name.to_lowercase()).map(String::as_str)
+    }
+
+    pub fn list(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.aliases.iter()
+    }
+
+    pub fn set(&mut self, name: &str, expansion: &str) -> Result<()> {
+        let name = name.trim_start_matches('/').to_lowercase();
+        if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_') {
+            return Err(anyhow!("Alias names are letters, digits, - and _"));
+        }
+        // Otherwise there'd be no way to change or remove aliases from the client
+        if name == "alias" || name == "unalias" {
+            return Err(anyhow!("/{} can't be redefined", name));
+        }
+        self.aliases.insert(name, expansion.trim().to_string());
+        Ok(())
+    }
+
+    /// False if there was no such alias
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.aliases.remove(&name.trim_start_matches('/').to_lowercase()).is_some()
+    }
+
+    /// Alias names starting with `prefix`, as `/name`
+    pub fn matching(&self, prefix: &str) -> Vec<String> {
+        let prefix = prefix.trim_start_matches('/').to_lowercase();
+        self.aliases
+            .keys()
+            .filter(|name| name.starts_with(&prefix))
+            .map(|name| format!("/{}", name))
+            .collect()
+    }
+
+    /// The lines a command line stands for, or None if it isn't an alias
+    ///
+    /// Aliases used by an expansion are expanded too, except one that is already
+    /// being expanded: inside its own expansion `/join` means the built-in, which
+    /// lets an alias wrap the command it's named after.
+    pub fn expand(&self, input: &str, nick: &str, chan: &str) -> Result<Option<Vec<String>>> {
+        let mut lines = Vec::new();
+        let mut expanding = Vec::new();
+        let mut expansions = 0;
+        if !self.expand_into(input, nick, chan, &mut expanding, &mut expansions, &mut lines)? {
+            return Ok(None);
+        }
+        Ok(Some(lines))
+    }
+
+    fn expand_into(
+        &self,
+        input: &str,
+        nick: &str,
+        chan: &str,
+        expanding: &mut Vec<String>,
+        expansions: &mut usize,
+        lines: &mut Vec<String>,
+    ) -> Result<bool> {
+        let Some(command) = input.strip_prefix('/') else {
+            return Ok(false);
+        };
+        let (name, args) = command.split_once(char::is_whitespace).unwrap_or((command, ""));
+        let name = name.to_lowercase();
+        if expanding.contains(&name) {
+            return Ok(false);
+        }
+        let Some(expansion) = self.aliases.get(&name) else {
+            return Ok(false);
+        };
+        if *expansions == MAX_EXPANSIONS {
+            return Err(anyhow!("Alias expands through more than {} aliases", MAX_EXPANSIONS));
+        }
+        *expansions += 1;
+
+        let args = args.trim();
+        let mut commands: Vec<String> = split_commands(expansion)
+            .iter()
+            .map(|command| substitute(command, args, nick, chan))
+            .collect();
+        if !uses_arguments(expansion) && !args.is_empty() {
+            if let Some(last) = commands.last_mut() {
+                last.push(' ');
+                last.push_str(args);
+            }
+        }
+
+        expanding.push(name);
+        for command in commands {
+            let command = command.trim();
+            if command.is_empty() {
+                continue;
+            }
+            if !self.expand_into(command, nick, chan, expanding, expansions, lines)? {
+                if lines.len() == MAX_COMMANDS {
+                    return Err(anyhow!("Alias expands to more than {} commands", MAX_COMMANDS));
+                }
+                lines.push(command.to_string());
+            }
+        }
+        expanding.pop();
+        Ok(true)
+    }
+}
+
+// Split at unescaped `;`, turning `\;` into a literal one
+fn split_commands(expansion: &str) -> Vec<String> {
+    let mut commands = vec![String::new()];
+    let mut chars = expansion.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&';') => {
+                chars.next();
+                commands.last_mut().unwrap().push(';');
+            }
+            ';' => commands.push(String::new()),
+            _ => commands.last_mut().unwrap().push(c),
+        }
+    }
+    commands
+}
+
+fn uses_arguments(expansion: &str) -> bool {
+    expansion
+        .split('$')
+        .skip(1)
+        .any(|rest| rest.starts_with('*') || rest.starts_with(|c: char| c.is_ascii_digit()))
+}
+
+// Fill in $0, $1-, $*, $nick and $chan; `$$` is a literal `$`
+fn substitute(command: &str, args: &str, nick: &str, chan: &str) -> String {
+    // Byte offset where each argument starts, for the `$N-` forms
+    let mut starts = Vec::new();
+    let mut in_word = false;
+    for (i, c) in args.char_indices() {
+        if !c.is_whitespace() && !in_word {
+            starts.push(i);
+        }
+        in_word = !c.is_whitespace();
+    }
+    let word = |n: usize| -> &str {
+        starts
+            .get(n)
+            .map(|&start| args[start..].split(char::is_whitespace).next().unwrap_or(""))
+            .unwrap_or("")
+    };
+
+    let mut output = String::new();
+    let mut rest = command;
+    while let Some(dollar) = rest.find('$') {
+        output.push_str(&rest[..dollar]);
+        rest = &rest[dollar + 1..];
+
+        if let Some(after) = rest.strip_prefix('$') {
+            output.push('$');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix('*') {
+            output.push_str(args);
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("nick") {
+            output.push_str(nick);
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("chan") {
+            output.push_str(chan);
+            rest = after;
+        } else {
+            let digits = rest.chars().take_while(|c| c.is_ascii_digit()).count();
+            if digits == 0 {
+                output.push('$');
+                continue;
+            }
+            let n: usize = rest[..digits].parse().unwrap_or(usize::MAX);
+            rest = &rest[digits..];
+            if let Some(after) = rest.strip_prefix('-') {
+                if let Some(&start) = starts.get(n) {
+                    output.push_str(&args[start..]);
+                }
+                rest = after;
+            } else {
+                output.push_str(word(n));
+            }
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn aliases(definitions: &[(&str, &str)]) -> Aliases {
+        let mut aliases = Aliases::default();
+        for (name, expansion) in definitions {
+            aliases.set(name, expansion).unwrap();
+        }
+        aliases
+    }
+
+    #[test]
+    fn splits_at_unescaped_semicolons() {
+        assert_eq!(split_commands("/join $0; hi all"), ["/join $0", " hi all"]);
+        assert_eq!(split_commands(r"wait\; what;/me"), ["wait; what", "/me"]);
+        assert_eq!(split_commands(r"a\b"), [r"a\b"]);
+        assert_eq!(split_commands(""), [""]);
+    }
+
+    #[test]
+    fn substitutes_arguments_and_variables() {
+        let args = "alice  bob carol";
+        assert_eq!(substitute("/msg $0 hey $0!", args, "me", "dr5reg"), "/msg alice hey alice!");
+        assert_eq!(substitute("$1-", args, "me", "dr5reg"), "bob carol");
+        assert_eq!(substitute("[$*]", args, "me", "dr5reg"), "[alice  bob carol]");
+        assert_eq!(substitute("$nick in #$chan", args, "me", "dr5reg"), "me in #dr5reg");
+        assert_eq!(substitute("$$5 $x $", args, "me", "dr5reg"), "$5 $x $");
+        // Missing arguments are empty
+        assert_eq!(substitute("<$7> <$3->", args, "me", "dr5reg"), "<> <>");
+    }
+
+    #[test]
+    fn expands_nested_aliases() {
+        let aliases = aliases(&[("j", "/join"), ("join", "/join $0; hi, $nick is here")]);
+        let lines = aliases.expand("/j dr5reg", "me", "system").unwrap().unwrap();
+        assert_eq!(lines, ["/join dr5reg", "hi, me is here"]);
+        assert!(aliases.expand("/leave", "me", "system").unwrap().is_none());
+    }
+
+    #[test]
+    fn stops_fan_out_that_adds_no_commands() {
+        // Each level calls the next ten times and the last adds nothing
+        let mut definitions = Vec::new();
+        for level in 0..8 {
+            definitions.push((format!("a{}", level), vec![format!("/a{}", level + 1); 10].join(";")));
+        }
+        definitions.push(("a8".to_string(), ";".to_string()));
+        let definitions: Vec<(&str, &str)> = definitions.iter().map(|(n, e)| (n.as_str(), e.as_str())).collect();
+
+        assert!(aliases(&definitions).expand("/a0", "me", "system").is_err());
+    }
+}
@@ -1,14 +1,32 @@
 use anyhow::Result;
 use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers, MouseEvent, MouseEventKind};
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot};
 use rand::Rng;
 use std::collections::{HashSet, HashMap};
+use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 use arboard::Clipboard;
 
-use crate::channels::{ChannelManager, Message, Channel};
-use crate::nostr::{NostrClient, Identity};
-use nostr::{PublicKey, ToBech32};
+use crate::channels::{ChannelManager, ChatLog, Message, MessageStore, Channel, SearchHit, SearchQuery};
+use crate::config::{self, Config, SpamConfig};
+use crate::control::ControlServer;
+use crate::aliases::Aliases;
+use crate::scripts::{HookStage, ScriptAction, ScriptContext, ScriptHost, ScriptParticipant};
+use crate::nostr::{NostrClient, Identity, Nip05Resolver, Nip05Status, Nip05Verifier, PowJob};
+use nostr::{EventId, PublicKey, ToBech32};
+
+// Messages paged in from history each time the view reaches the top
+const HISTORY_PAGE_SIZE: usize = 100;
+
+// Most recent hits shown by /search
+const MAX_SEARCH_HITS: usize = 200;
+
+// Mentions kept for /mentions, oldest are dropped first
+const MAX_MENTIONS: usize = 200;
+
+// Beyond this mining takes hours on a laptop
+const MAX_POW_DIFFICULTY: u8 = 32;
 
 #[derive(Debug)]
 pub struct SpamFilter {
@@ -23,6 +41,7 @@ pub struct SpamFilter {
     duplicate_message_threshold: u32,
     max_future_time_seconds: u64, // Maximum time into the future allowed
     max_past_time_hours: u64, // Maximum time into the past allowed (hours)
+    min_pow_difficulty: u8, // Required NIP-13 leading zero bits for channel messages (0 = off)
     
     // Track recent messages for duplicate detection (content_hash -> (count, pubkey))
     recent_message_hashes: HashMap<u64, (u32, String)>,
@@ -32,28 +51,31 @@ pub struct SpamFilter {
 }
 
 impl SpamFilter {
-    pub fn new() -> Self {
-        Self {
+    pub fn new(config: &SpamConfig) -> Self {
+        let mut filter = Self {
             user_message_frequency: HashMap::new(),
             auto_muted_users: HashMap::new(),
-            max_messages_per_minute: 15, // Allow up to 15 messages per minute
-            duplicate_message_threshold: 3, // Mute after 3 identical messages
-            max_future_time_seconds: 300, // Allow up to 5 minutes into the future
-            max_past_time_hours: 24, // Allow up to 24 hours into the past
+            max_messages_per_minute: 0,
+            duplicate_message_threshold: 0,
+            max_future_time_seconds: 0,
+            max_past_time_hours: 0,
+            min_pow_difficulty: 0,
             recent_message_hashes: HashMap::new(),
-            spam_keywords: vec![
-                "🚀🚀🚀".to_string(),
-                "CLICK HERE".to_string(),
-                "FREE MONEY".to_string(),
-                "telegram.me".to_string(),
-                "bit.ly".to_string(),
-                "JOIN NOW".to_string(),
-                "LIMITED TIME".to_string(),
-                "EARN $$$".to_string(),
-                "CRYPTO PUMP".to_string(),
-                "🎰🎰🎰".to_string(),
-            ],
-        }
+            spam_keywords: Vec::new(),
+        };
+        filter.configure(config);
+        filter.min_pow_difficulty = config.min_pow_difficulty;
+        filter
+    }
+    
+    /// Apply rate, duplicate, timestamp and keyword limits from config.toml or /set,
+    /// keeping mutes and history (the PoW minimum has its own setter)
+    pub fn configure(&mut self, config: &SpamConfig) {
+        self.max_messages_per_minute = config.max_messages_per_minute;
+        self.duplicate_message_threshold = config.duplicate_message_threshold;
+        self.max_future_time_seconds = config.max_future_time_seconds;
+        self.max_past_time_hours = config.max_past_time_hours;
+        self.spam_keywords = config.keywords.clone();
     }
     
     pub fn is_spam(&mut self, message: &Message) -> bool {
@@ -81,6 +103,12 @@ impl SpamFilter {
             return true;
         }
         
+        // Channels can require proof of work (like bitchat mobile); not enough work
+        // just means the message is dropped, nobody gets muted for it
+        if !message.is_private && message.pow_difficulty < self.min_pow_difficulty {
+            return true;
+        }
+        
         // Check if user is currently auto-muted
         if let Some(mute_time) = self.auto_muted_users.get(pubkey) {
             if now.duration_since(*mute_time) < Duration::from_secs(600) {
@@ -196,6 +224,14 @@ impl SpamFilter {
         true // Newly muted
     }
     
+    pub fn set_min_pow_difficulty(&mut self, difficulty: u8) {
+        self.min_pow_difficulty = difficulty;
+    }
+    
+    pub fn min_pow_difficulty(&self) -> u8 {
+        self.min_pow_difficulty
+    }
+    
     pub fn is_user_auto_muted(&self, pubkey: &str) -> bool {
         if let Some(mute_time) = self.auto_muted_users.get(pubkey) {
             Instant::now().duration_since(*mute_time) < Duration::from_secs(600)
@@ -250,11 +286,13 @@ impl SpamFilter {
     }
 }
 
+/// Rendered message row: (timestamp, nickname, content, is_own, pubkey, is_mention, is_pending)
+pub type VisibleMessage = (String, String, String, bool, Option<String>, bool, bool);
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum AppState {
     Connecting,
     Connected,
-    #[allow(dead_code)]
     Disconnected,
     Error(String),
 }
@@ -275,10 +313,30 @@ pub struct App {
     pub input_horizontal_scroll: usize,
     pub should_autoscroll: bool,
     
+    // Settings from config.toml, changed with /set and written back with /save
+    pub config: Config,
+    config_path: PathBuf,
+    
     // Nostr client
     pub nostr_client: NostrClient,
     pub identity: Identity,
     
+    // NIP-05 verification of participants
+    pub nip05: Nip05Verifier,
+    
+    // Identities used this session, for /identity list and /identity use
+    session_identities: Vec<Identity>,
+    
+    // Next input is an nsec for /identity load and must not be echoed
+    pub awaiting_nsec: bool,
+    
+    // Messages still mining NIP-13 proof of work, for the input bar indicator
+    pub pow_jobs: Vec<PowJob>,
+    
+    // Channel messages shown as pending until their send finishes
+    pending_sends: Vec<PendingSend>,
+    next_send_id: u64,
+    
     // Channel management
     pub channel_manager: ChannelManager,
     pub current_channel: Option<String>,
@@ -311,6 +369,28 @@ pub struct App {
     
     // Flag to prevent UI from overriding autoscroll after new messages
     pub just_processed_messages: bool,
+    
+    // /search results view, shown instead of the chat while open
+    pub search_results: Option<SearchResults>,
+    
+    // Messages that mentioned us, newest last, for /mentions
+    mentions: Vec<SearchHit>,
+    
+    // JSON-RPC control socket for other programs, served from on_tick
+    control: Option<ControlServer>,
+    
+    // Rhai scripts with their commands and message hooks
+    scripts: ScriptHost,
+    
+    // /alias definitions, resolved before the built-in commands
+    aliases: Aliases,
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchResults {
+    pub title: String,
+    pub hits: Vec<SearchHit>,
+    pub selected: usize,
 }
 
 #[derive(Debug, Clone)]
@@ -333,22 +413,40 @@ pub struct TabCompletionState {
     pub current_match_index: usize,
 }
 
+// A local echo waiting for NostrClient::send_message to finish
+struct PendingSend {
+    id: u64,
+    channel: String,
+    content: String,
+    sent: oneshot::Receiver<Result<EventId>>,
+}
+
 impl App {
-    pub async fn new(nsec: Option<&str>, auto_channel: Option<&str>) -> Result<Self> {
-        let identity = if let Some(nsec_str) = nsec {
-            match Identity::from_nsec(nsec_str).await {
-            Ok(identity) => identity,
-            Err(e) => return Err(e),
-        }
-        } else {
-            Identity::ephemeral()
-        };
-        
+    pub async fn new(
+        mut identity: Identity,
+        config: Config,
+        config_path: PathBuf,
+        auto_channel: Option<&str>,
+        nip05_resolver: Arc<dyn Nip05Resolver>,
+    ) -> Result<Self> {
         let (message_tx, message_rx) = mpsc::unbounded_channel();
         let (status_tx, status_rx) = mpsc::unbounded_channel();
         
-        let nostr_client = NostrClient::new(&identity, message_tx.clone(), status_tx.clone()).await?;
-        let channel_manager = ChannelManager::new(message_tx);
+        if let (true, Some(nickname)) = (identity.is_ephemeral, &config.nickname) {
+            identity.nickname = nickname.clone();
+        }
+        
+        let mut nostr_client = NostrClient::new(&identity, &config.relays, &config.removed_relays, message_tx.clone(), status_tx.clone()).await?;
+        nostr_client.set_bitchat_first_contact(config.dm.bitchat_format);
+        let nip05 = nostr_client.nip05_verifier(nip05_resolver);
+        let (history, history_error) = match config.history.enabled {
+            true => match MessageStore::open_default(config.history.max_age_days) {
+                Ok(history) => (Some(history), None),
+                Err(e) => (None, Some(e)),
+            },
+            false => (None, None),
+        };
+        let channel_manager = ChannelManager::new(message_tx, ChatLog::new(&config.log), history);
         
         let mut app = Self {
             should_quit: false,
@@ -360,8 +458,18 @@ impl App {
             input_horizontal_scroll: 0,
             should_autoscroll: true,
             
+            spam_filter: SpamFilter::new(&config.spam),
+            config,
+            config_path,
+            
             nostr_client,
+            nip05,
+            session_identities: vec![identity.clone()],
             identity,
+            awaiting_nsec: false,
+            pow_jobs: Vec::new(),
+            pending_sends: Vec::new(),
+            next_send_id: 0,
             
             channel_manager,
             current_channel: Some("system".to_string()),
@@ -372,11 +480,15 @@ impl App {
             tab_completion_state: None,
             blocked_users: HashSet::new(),
             private_chats: HashMap::new(),
-            spam_filter: SpamFilter::new(),
             clickable_regions: Vec::new(),
             viewport_height: 25, // Default fallback, will be updated by UI
             input_width: 80, // Default fallback, will be updated by UI
             just_processed_messages: false,
+            search_results: None,
+            mentions: Vec::new(),
+            control: None,
+            scripts: ScriptHost::new(),
+            aliases: Aliases::load(),
         };
         
         // Add welcome message to system channel
@@ -384,21 +496,31 @@ impl App {
         app.add_status_message(format!("Welcome to BitchatX v{}!", version));
         app.add_status_message(format!("Connected as {} ({})",
             app.identity.nickname,
-            if nsec.is_some() { "authenticated" } else { "ephemeral" }
+            if app.identity.is_ephemeral { "ephemeral" } else { "authenticated" }
         ));
         app.add_status_message("Type /help for available commands".to_string());
         app.add_status_message("To receive messages, join a geohash channel: /join <geohash>".to_string());
+        if let Some(e) = history_error {
+            app.add_status_message(format!("Message history disabled: {}", e));
+        }
+        for path in app.scripts.autorun_paths() {
+            app.load_script(&path.to_string_lossy());
+        }
+        app.run_script_actions().await?;
         
-        // Auto-join channel if specified
+        // Auto-join configured channels, then the one from the command line (which ends up current)
+        let mut auto_join = app.config.auto_join.clone();
         if let Some(channel) = auto_channel {
-            app.join_channel(channel).await?;
+            auto_join.retain(|joined| joined != channel);
+            auto_join.push(channel.to_string());
+        }
+        for channel in auto_join {
+            app.join_channel(&channel).await?;
         }
         
-        // Start Nostr client
+        // Start Nostr client; the state follows relay health from here on (see on_tick)
         match app.nostr_client.connect().await {
-            Ok(()) => {
-                app.state = AppState::Connected;
-            }
+            Ok(()) => {}
             Err(e) => {
                 app.state = AppState::Error(format!("Connection failed: {}", e));
                 app.add_status_message(format!("Connection error: {}", e));
@@ -431,7 +553,7 @@ impl App {
                     // Handle Ctrl key combinations for clipboard operations
                     match key.code {
                         KeyCode::Char('c') => {
-                            if self.input_mode == InputMode::Editing {
+                            if self.input_mode == InputMode::Editing && !self.awaiting_nsec {
                                 self.copy_to_clipboard();
                             }
                             return Ok(());
@@ -443,7 +565,7 @@ impl App {
                             return Ok(());
                         }
                         KeyCode::Char('x') => {
-                            if self.input_mode == InputMode::Editing {
+                            if self.input_mode == InputMode::Editing && !self.awaiting_nsec {
                                 self.cut_to_clipboard();
                             }
                             return Ok(());
@@ -454,18 +576,43 @@ impl App {
                             }
                             return Ok(());
                         }
+                        KeyCode::Char('f') => {
+                            if !self.awaiting_nsec {
+                                self.input_mode = InputMode::Editing;
+                                self.input = "/search ".to_string();
+                                self.cursor_position = self.input.len();
+                                self.update_input_scroll();
+                            }
+                            return Ok(());
+                        }
                         _ => {
                             // Ignore other Ctrl combinations
                             return Ok(());
                         }
                     }
                 }
+                KeyModifiers::ALT => {
+                    // Alt+1..9 and Alt+0 for window 10, as in BitchX and irssi
+                    if let KeyCode::Char(digit @ '0'..='9') = key.code {
+                        if !self.awaiting_nsec {
+                            let number = digit.to_digit(10).unwrap_or(0) as usize;
+                            self.switch_to_window(if number == 0 { 10 } else { number });
+                        }
+                    }
+                    return Ok(());
+                }
                 _ => {
-                    // Ignore all other modifier combinations (Alt, etc.)
+                    // Ignore all other modifier combinations
                     return Ok(());
                 }
             }
         }
+        
+        // The search results view takes the navigation keys until closed
+        if self.search_results.is_some() && self.input.is_empty() && self.handle_search_key(key.code) {
+            return Ok(());
+        }
+        
         match self.input_mode {
             InputMode::Normal => {
                 match key.code {
@@ -505,6 +652,13 @@ impl App {
             }
             InputMode::Editing => {
                 match key.code {
+                    KeyCode::Enter if self.awaiting_nsec => {
+                        let nsec = std::mem::take(&mut self.input);
+                        self.cursor_position = 0;
+                        self.input_horizontal_scroll = 0;
+                        self.awaiting_nsec = false;
+                        self.load_identity_from_nsec(nsec.trim()).await;
+                    }
                     KeyCode::Enter => {
                         self.submit_input().await?;
                         self.input.clear();
@@ -512,6 +666,13 @@ impl App {
                         self.input_horizontal_scroll = 0;
                         // Stay in input mode after sending message
                     }
+                    KeyCode::Esc if self.awaiting_nsec => {
+                        self.input.clear();
+                        self.cursor_position = 0;
+                        self.input_horizontal_scroll = 0;
+                        self.awaiting_nsec = false;
+                        self.add_status_message("Identity load cancelled".to_string());
+                    }
                     KeyCode::Esc => {
                         self.input.clear();
                         self.cursor_position = 0;
@@ -528,7 +689,9 @@ impl App {
                         self.update_input_scroll();
                     }
                     KeyCode::Tab => {
-                        self.handle_tab_completion().await;
+                        if !self.awaiting_nsec {
+                            self.handle_tab_completion().await;
+                        }
                     }
                     KeyCode::Backspace => {
                         self.tab_completion_state = None;
@@ -644,10 +807,8 @@ impl App {
         
         if input.starts_with('/') {
             self.handle_command(&input).await?;
-        } else if let Some(channel) = self.current_channel.clone() {
-            self.send_message(&channel, &input).await?;
         } else {
-            self.add_status_message("No channel selected. Use /join <geohash> to join a channel.".to_string());
+            self.send_to_current_channel(&input).await?;
         }
         
         // Enable auto-scrolling after sending a message
@@ -656,7 +817,46 @@ impl App {
         Ok(())
     }
     
+    // Text typed without a command goes to whatever channel is open
+    async fn send_to_current_channel(&mut self, text: &str) -> Result<()> {
+        if let Some(channel) = self.current_channel.clone() {
+            if let Some(pubkey) = channel.strip_prefix("dm:") {
+                // Replies typed in a private chat stay private
+                let nickname = self.private_chats.get(pubkey).cloned()
+                    .unwrap_or_else(|| format!("anon{}", &pubkey[..8.min(pubkey.len())]));
+                self.send_private_message_to_pubkey(pubkey, &nickname, text).await?;
+            } else {
+                self.send_message(&channel, text).await?;
+            }
+        } else {
+            self.add_status_message("No channel selected. Use /join <geohash> to join a channel.".to_string());
+        }
+        Ok(())
+    }
+    
     async fn handle_command(&mut self, input: &str) -> Result<()> {
+        // Aliases come first so they can override built-in commands
+        let channel = self.current_channel.clone().unwrap_or_default();
+        match self.aliases.expand(input, &self.identity.nickname, &channel) {
+            Ok(Some(lines)) => {
+                for line in lines {
+                    if line.starts_with('/') {
+                        self.run_command(&line).await?;
+                    } else {
+                        self.send_to_current_channel(&line).await?;
+                    }
+                }
+                Ok(())
+            }
+            Ok(None) => self.run_command(input).await,
+            Err(e) => {
+                self.add_status_message(e.to_string());
+                Ok(())
+            }
+        }
+    }
+    
+    async fn run_command(&mut self, input: &str) -> Result<()> {
         let parts = self.parse_command_args(&input[1..]);
         if parts.is_empty() {
             return Ok(());
@@ -675,7 +875,7 @@ impl App {
                     if channel == "system" {
                         self.add_status_message("Cannot leave system channel".to_string());
                     } else {
-                        self.leave_channel(&channel).await?;
+                        self.leave_channel(channel).await?;
                     }
                 } else {
                     self.add_status_message("No channel to leave".to_string());
@@ -688,6 +888,41 @@ impl App {
                 }
                 self.change_nickname(&parts[1]).await?;
             }
+            "identity" | "id" => {
+                match parts.get(1).map(|s| s.to_lowercase()).as_deref() {
+                    Some("new") => {
+                        self.new_ephemeral_identity().await;
+                    }
+                    Some("load") => {
+                        self.awaiting_nsec = true;
+                        self.add_status_message("Paste your nsec and press Enter (input is hidden, Esc cancels)".to_string());
+                    }
+                    Some("list") => {
+                        self.list_identities();
+                    }
+                    Some("use") if parts.len() == 3 => {
+                        self.use_session_identity(&parts[2]).await;
+                    }
+                    _ => {
+                        self.add_status_message("Usage: /identity <new|load|list|use <number>>".to_string());
+                    }
+                }
+            }
+            "profile" => {
+                match parts.get(1).map(|s| s.to_lowercase()).as_deref() {
+                    Some("show") => {
+                        self.show_profile().await;
+                    }
+                    Some(action @ ("set" | "create")) if parts.len() >= 4 => {
+                        let field = parts[2].to_lowercase();
+                        let value = parts[3..].join(" ");
+                        self.set_profile_field(&field, &value, action == "create").await;
+                    }
+                    _ => {
+                        self.add_status_message("Usage: /profile show | /profile <set|create> <name|about|picture|nip05> <value>".to_string());
+                    }
+                }
+            }
             "msg" | "m" => {
                 if parts.len() < 3 {
                     self.add_status_message("Usage: /msg <channel/nickname> <message>".to_string());
@@ -700,6 +935,12 @@ impl App {
             "list" | "channels" => {
                 self.list_channels();
             }
+            "window" | "win" => {
+                match parts.get(1).and_then(|number| number.parse::<usize>().ok()) {
+                    Some(number) => self.switch_to_window(number),
+                    None => self.add_status_message("Usage: /window <number> (as numbered in the channel list)".to_string()),
+                }
+            }
             "all" => {
                 self.show_all_recent_messages().await;
             }
@@ -745,6 +986,42 @@ impl App {
                     self.add_status_message("Usage: /whois <nickname> or /whois <nickname#pubkey>".to_string());
                 }
             }
+            "relay" => {
+                match (parts.get(1).map(|s| s.to_lowercase()).as_deref(), parts.get(2)) {
+                    (Some("list"), None) | (None, None) => {
+                        self.list_relays().await;
+                    }
+                    (Some("add"), Some(url)) => {
+                        match self.nostr_client.add_manual_relay(url).await {
+                            Ok(url) => {
+                                if !self.config.relays.contains(&url) {
+                                    self.config.relays.push(url.clone());
+                                }
+                                self.save_relays(format!("Added relay {}", url));
+                            }
+                            Err(e) => self.add_status_message(format!("Could not add relay: {}", e)),
+                        }
+                    }
+                    (Some("remove" | "rm"), Some(url)) => {
+                        match self.nostr_client.remove_relay(url).await {
+                            Ok(url) => {
+                                self.config.relays.retain(|relay| relay != &url);
+                                self.save_relays(format!("Removed relay {}", url));
+                            }
+                            Err(e) => self.add_status_message(format!("Could not remove relay: {}", e)),
+                        }
+                    }
+                    (Some("info"), Some(url)) => {
+                        self.show_relay_info(url).await;
+                    }
+                    _ => {
+                        self.add_status_message("Usage: /relay list | add <wss://...> | remove <url> | info <url>".to_string());
+                    }
+                }
+            }
+            "inspect" => {
+                self.inspect_message(parts.get(1).map(|s| s.trim_start_matches('@')));
+            }
             "version" => {
                 self.show_version().await?;
             }
@@ -776,6 +1053,107 @@ impl App {
                     }
                 }
             }
+            "pow" => {
+                match (parts.get(1).map(|s| s.to_lowercase()).as_deref(), parts.get(2)) {
+                    (None, _) => {
+                        self.show_pow_status();
+                    }
+                    (Some("cancel"), None) => {
+                        self.cancel_pow_jobs();
+                    }
+                    (Some("min"), Some(bits)) => {
+                        if let Some(bits) = Self::parse_pow_bits(bits) {
+                            self.spam_filter.set_min_pow_difficulty(bits);
+                            self.add_status_message(match bits {
+                                0 => "Channel messages no longer need proof of work".to_string(),
+                                bits => format!("Dropping channel messages with less than {} bits of proof of work", bits),
+                            });
+                        } else {
+                            self.add_status_message(format!("PoW difficulty must be off or 0-{} bits", MAX_POW_DIFFICULTY));
+                        }
+                    }
+                    (Some(bits), None) => {
+                        if let Some(bits) = Self::parse_pow_bits(bits) {
+                            self.nostr_client.set_pow_difficulty(bits);
+                            self.add_status_message(match bits {
+                                0 => "Proof of work mining disabled".to_string(),
+                                bits => format!("Mining {} bits of proof of work on every channel message", bits),
+                            });
+                        } else {
+                            self.add_status_message(format!("PoW difficulty must be off or 0-{} bits", MAX_POW_DIFFICULTY));
+                        }
+                    }
+                    _ => {
+                        self.add_status_message("Usage: /pow [<bits>|off] | /pow min <bits|off> | /pow cancel".to_string());
+                    }
+                }
+            }
+            "set" => {
+                match parts.len() {
+                    1 => self.list_settings(),
+                    2 => self.show_setting(&parts[1]),
+                    _ => self.change_setting(&parts[1], &parts[2..].join(" ")),
+                }
+            }
+            "save" => {
+                self.save_config();
+            }
+            "log" => {
+                match parts.get(1).map(|s| s.to_lowercase()).as_deref() {
+                    None => self.show_log_status(),
+                    Some("on") => self.set_channel_logging(true),
+                    Some("off") => self.set_channel_logging(false),
+                    _ => self.add_status_message("Usage: /log [on|off]".to_string()),
+                }
+            }
+            "search" | "find" => {
+                if parts.len() < 2 {
+                    self.add_status_message("Usage: /search <text|/regex/> [#channel] [from:nick]".to_string());
+                } else {
+                    self.search(&parts[1..]);
+                }
+            }
+            "mentions" => {
+                if parts.get(1).is_some_and(|arg| arg.eq_ignore_ascii_case("clear")) {
+                    self.mentions.clear();
+                    self.add_status_message("Mentions cleared".to_string());
+                } else {
+                    self.show_mentions();
+                }
+            }
+            "alias" => {
+                self.alias_command(input);
+            }
+            "unalias" => {
+                match parts.get(1) {
+                    Some(name) if self.aliases.remove(name) => {
+                        match self.aliases.save() {
+                            Ok(()) => self.add_status_message(format!("Removed alias /{}", name.trim_start_matches('/'))),
+                            Err(e) => self.add_status_message(format!("Failed to save aliases: {}", e)),
+                        }
+                    }
+                    Some(name) => self.add_status_message(format!("No alias /{}", name.trim_start_matches('/'))),
+                    None => self.add_status_message("Usage: /unalias <name>".to_string()),
+                }
+            }
+            "script" => {
+                match (parts.get(1).map(|s| s.to_lowercase()).as_deref(), parts.get(2)) {
+                    (None | Some("list"), _) => self.list_scripts(),
+                    (Some("load"), Some(name)) => {
+                        let name = name.clone();
+                        self.load_script(&name);
+                        self.run_script_actions().await?;
+                    }
+                    (Some("unload"), Some(name)) => {
+                        if self.scripts.unload(name) {
+                            self.add_status_message(format!("Unloaded script {}", name));
+                        } else {
+                            self.add_status_message(format!("No script {} loaded", name));
+                        }
+                    }
+                    _ => self.add_status_message("Usage: /script [list|load <name>|unload <name>]".to_string()),
+                }
+            }
             "clear" => {
                 self.clear_current_channel();
             }
@@ -786,6 +1164,11 @@ impl App {
             "quit" | "q" | "exit" => {
                 self.should_quit = true;
             }
+            name if self.scripts.has_command(name) => {
+                self.update_script_context();
+                self.scripts.run_command(name, &parts[1..]);
+                self.run_script_actions().await?;
+            }
             _ => {
                 self.add_status_message(format!("Unknown command: {}. Type /help for available commands.", parts[0]));
             }
@@ -794,18 +1177,22 @@ impl App {
         Ok(())
     }
     
-    async fn join_channel(&mut self, geohash: &str) -> Result<()> {
+    pub async fn join_channel(&mut self, geohash: &str) -> Result<()> {
         // Validate geohash format
         if !self.is_valid_geohash(geohash) {
             self.add_status_message(format!("Invalid geohash format: {}", geohash));
             return Ok(());
         }
         
-        self.current_channel = Some(geohash.to_string());
+        self.set_current_channel(geohash);
         self.channel_manager.join_channel(geohash).await?;
+        let restored = self.channel_manager.restore_history(geohash, self.config.history.restore_messages);
         self.nostr_client.subscribe_to_channel(geohash).await?;
         
         self.add_status_message(format!("Joined channel #{}", geohash));
+        if restored > 0 {
+            self.add_status_message(format!("Restored {} earlier messages of #{} from history", restored, geohash));
+        }
         
         // Force scroll to bottom when joining a channel
         self.force_scroll_to_bottom();
@@ -813,7 +1200,7 @@ impl App {
         Ok(())
     }
     
-    async fn leave_channel(&mut self, geohash: &str) -> Result<()> {
+    pub async fn leave_channel(&mut self, geohash: &str) -> Result<()> {
         // Prevent leaving system channel
         if geohash == "system" {
             self.add_status_message("Cannot leave system channel".to_string());
@@ -824,7 +1211,7 @@ impl App {
         self.nostr_client.unsubscribe_from_channel(geohash).await?;
         
         if self.current_channel.as_deref() == Some(geohash) {
-            self.current_channel = Some(self.system_channel.clone());
+            self.set_current_channel(&self.system_channel.clone());
         }
         
         self.add_status_message(format!("Left channel #{}", geohash));
@@ -838,24 +1225,18 @@ impl App {
             nickname: self.identity.nickname.clone(),
             content: content.to_string(),
             timestamp: chrono::Utc::now(),
-            pubkey: Some(self.identity.pubkey.clone()),
+            pubkey: Some(self.identity.pubkey_for_geohash(channel)),
             is_own: true,
             is_private: false,
             recipient_pubkey: None,
+            pow_difficulty: self.nostr_client.pow_difficulty(),
+            event_id: None,
+            relay_url: None,
+            is_mention: false,
+            send_id: None,
         };
         
-        // Use sync version for immediate display
-        let _ = self.channel_manager.add_message_sync(message);
-        
-        // Send to network after UI is updated
-        // Send to network in background without blocking UI
-        // Network send variables removed to fix borrow checker
-        
-        // Create a future for the network send
-        // Network send will happen after scroll_to_bottom()
-        
-        // Send to network (now non-blocking via NostrClient modification)
-        let _ = self.nostr_client.send_message(channel, content, &self.identity.nickname).await;
+        self.send_with_local_echo(message).await;
         
         // Enable auto-scrolling before network operations
         self.should_autoscroll = true;
@@ -864,6 +1245,184 @@ impl App {
         Ok(())
     }
     
+    /// Send a channel message, showing it as pending until a relay accepts it
+    ///
+    /// The echo is only logged and stored once sent; on_tick takes it back if
+    /// mining is cancelled or the send fails.
+    async fn send_with_local_echo(&mut self, mut message: Message) {
+        let channel = message.channel.clone();
+        let content = message.content.clone();
+        let outgoing = match self.nostr_client.send_message(&channel, &content, &self.identity.nickname).await {
+            Ok(outgoing) => outgoing,
+            Err(e) => {
+                self.add_status_message(format!("Message to #{} not sent ({}): {}", channel, e, content));
+                return;
+            }
+        };
+        
+        let id = self.next_send_id;
+        self.next_send_id += 1;
+        message.send_id = Some(id);
+        self.channel_manager.add_pending(message);
+        if let Some(job) = outgoing.pow {
+            self.pow_jobs.push(job);
+        }
+        self.pending_sends.push(PendingSend { id, channel, content, sent: outgoing.sent });
+    }
+    
+    // Confirm or take back local echoes whose send finished
+    fn check_pending_sends(&mut self) {
+        let mut index = 0;
+        while index < self.pending_sends.len() {
+            let outcome = match self.pending_sends[index].sent.try_recv() {
+                Err(oneshot::error::TryRecvError::Empty) => {
+                    index += 1;
+                    continue;
+                }
+                Ok(outcome) => outcome,
+                Err(oneshot::error::TryRecvError::Closed) => Err(anyhow::anyhow!("send task stopped")),
+            };
+            let pending = self.pending_sends.remove(index);
+            match outcome {
+                Ok(event_id) => {
+                    self.channel_manager.confirm_sent(&pending.channel, pending.id, event_id.to_hex());
+                }
+                Err(e) => {
+                    self.channel_manager.remove_pending(&pending.channel, pending.id);
+                    self.add_status_message(format!("Message to #{} not sent ({}): {}", pending.channel, e, pending.content));
+                }
+            }
+        }
+    }
+    
+    /// /alias, /alias <name> or /alias <name> <expansion>
+    fn alias_command(&mut self, input: &str) {
+        // The expansion is taken as typed, quotes and all
+        let rest = input.trim_start_matches('/')
+            .split_once(char::is_whitespace)
+            .map(|(_, rest)| rest.trim())
+            .unwrap_or("");
+        let (name, expansion) = rest.split_once(char::is_whitespace)
+            .map(|(name, expansion)| (name, expansion.trim()))
+            .unwrap_or((rest, ""));
+
+        if name.is_empty() {
+            if self.aliases.list().next().is_none() {
+                self.add_status_message(format!("No aliases - /alias <name> <expansion> adds one to {}", Aliases::path_display()));
+                return;
+            }
+            let aliases: Vec<String> = self.aliases.list()
+                .map(|(name, expansion)| format!("  /{} = {}", name, expansion))
+                .collect();
+            self.add_status_message("Aliases:".to_string());
+            for alias in aliases {
+                self.add_status_message(alias);
+            }
+        } else if expansion.is_empty() {
+            match self.aliases.get(name) {
+                Some(expansion) => self.add_status_message(format!("/{} = {}", name.trim_start_matches('/'), expansion)),
+                None => self.add_status_message(format!("No alias /{}", name.trim_start_matches('/'))),
+            }
+        } else if let Err(e) = self.aliases.set(name, expansion) {
+            self.add_status_message(e.to_string());
+        } else {
+            match self.aliases.save() {
+                Ok(()) => self.add_status_message(format!("/{} = {}", name.trim_start_matches('/').to_lowercase(), expansion)),
+                Err(e) => self.add_status_message(format!("Failed to save aliases: {}", e)),
+            }
+        }
+    }
+
+    fn load_script(&mut self, name_or_path: &str) {
+        match self.scripts.load(name_or_path) {
+            Ok(name) => self.add_status_message(format!("Loaded script {}", name)),
+            Err(e) => self.add_status_message(e.to_string()),
+        }
+    }
+    
+    fn list_scripts(&mut self) {
+        let scripts = self.scripts.list();
+        if scripts.is_empty() {
+            self.add_status_message(format!("No scripts loaded - /script load <name> loads {}/<name>.rhai", self.scripts.dir().display()));
+            return;
+        }
+        self.add_status_message("Loaded scripts:".to_string());
+        for (name, path, commands) in scripts {
+            let commands = if commands.is_empty() { String::new() } else { format!(" - {}", commands.join(" ")) };
+            self.add_status_message(format!("  {} ({}){}", name, path.display(), commands));
+        }
+    }
+    
+    /// Let scripts see the session as it is now
+    fn update_script_context(&mut self) {
+        let channels = self.channel_manager.list_channels().into_iter()
+            .map(|name| {
+                let participants = self.channel_manager.get_channel(&name)
+                    .map(|channel| channel.participants.values()
+                        .map(|p| ScriptParticipant {
+                            nickname: p.nickname.clone(),
+                            pubkey: p.pubkey.clone().unwrap_or_default(),
+                            messages: p.message_count,
+                        })
+                        .collect())
+                    .unwrap_or_default();
+                (name, participants)
+            })
+            .collect();
+        self.scripts.set_context(ScriptContext {
+            nickname: self.identity.nickname.clone(),
+            current_channel: self.current_channel.clone().unwrap_or_default(),
+            channels,
+        });
+    }
+    
+    /// Carry out what scripts asked for while they ran
+    async fn run_script_actions(&mut self) -> Result<()> {
+        for action in self.scripts.take_actions() {
+            match action {
+                ScriptAction::Send { channel, text } => self.send_channel_message(&channel, &text).await?,
+                ScriptAction::SendPrivate { to, text } => self.send_private_message_to(&to, &text).await?,
+                ScriptAction::Join(channel) => self.join_channel(&channel).await?,
+                ScriptAction::Leave(channel) => self.leave_channel(&channel).await?,
+                ScriptAction::Status(text) => self.add_status_message(text),
+            }
+        }
+        Ok(())
+    }
+    
+    /// Serve the control API on a Unix socket; failing to is reported, not fatal
+    pub fn start_control_socket(&mut self, path: &std::path::Path) {
+        match ControlServer::bind(path) {
+            Ok(server) => {
+                self.add_status_message(format!("Control API listening on {}", server.path().display()));
+                self.control = Some(server);
+            }
+            Err(e) => self.add_status_message(format!("Control API disabled: {}", e)),
+        }
+    }
+    
+    /// Send to a geohash channel, joined or not (for headless mode and gateways)
+    pub async fn send_channel_message(&mut self, channel: &str, content: &str) -> Result<()> {
+        if !self.is_valid_geohash(channel) {
+            self.add_status_message(format!("Invalid geohash format: {}", channel));
+            return Ok(());
+        }
+        self.send_message(channel, content).await
+    }
+    
+    /// Send a private message to a nickname, npub or hex pubkey (for headless mode and gateways)
+    pub async fn send_private_message_to(&mut self, recipient: &str, content: &str) -> Result<()> {
+        let Ok(public_key) = PublicKey::parse(recipient) else {
+            return self.send_private_message(recipient, content).await;
+        };
+        
+        let pubkey = public_key.to_hex();
+        let nickname = self.private_chats.get(&pubkey).cloned()
+            .or_else(|| self.find_nickname_for_pubkey(&pubkey))
+            .unwrap_or_else(|| format!("anon{}", &pubkey[..8]));
+        self.send_private_message_to_pubkey(&pubkey, &nickname, content).await
+    }
+    
     async fn send_msg_to_target(&mut self, target: &str, content: &str) -> Result<()> {
         // First check if target is a joined channel
         let joined_channels = self.channel_manager.list_channels();
@@ -886,45 +1445,250 @@ impl App {
     }
     
     async fn send_private_message(&mut self, nickname: &str, content: &str) -> Result<()> {
-        // Find the pubkey for this nickname
-        let recipient_pubkey = self.find_pubkey_for_nickname(nickname).await;
+        // Prefer an existing private chat, then fall back to searching the channels
+        let recipient_pubkey = match self.private_chats
+            .iter()
+            .find(|(_, nick)| nick.eq_ignore_ascii_case(nickname))
+        {
+            Some((pubkey, _)) => Some(pubkey.clone()),
+            None => self.find_pubkey_for_nickname(nickname).await,
+        };
         
         if let Some(pubkey) = recipient_pubkey {
-            // Create a private message channel name based on the pubkey
-            let dm_channel = format!("dm:{}", &pubkey);
-            
-            // Add to private chats if not already there
-            self.private_chats.insert(pubkey.clone(), nickname.to_string());
-            
-            // Create the private message
-            let message = Message {
-                channel: dm_channel.clone(),
-                nickname: self.identity.nickname.clone(),
-                content: content.to_string(),
-                timestamp: chrono::Utc::now(),
-                pubkey: Some(self.identity.pubkey.clone()),
-                is_own: true,
-                is_private: true,
-                recipient_pubkey: Some(pubkey.clone()),
-            };
-            
-            // Add to channel manager for display
-            let _ = self.channel_manager.add_message_sync(message);
-            
-            // TODO: Send via Nostr using NIP-17 (for now just show locally)
-            self.add_status_message(format!("Private message sent to {} (local only for now)", nickname));
-            
-            // Enable auto-scrolling
-            self.should_autoscroll = true;
-            self.scroll_to_bottom();
+            self.send_private_message_to_pubkey(&pubkey, nickname, content).await?;
         } else {
             self.add_status_message(format!("User '{}' not found. They must have sent a message in a channel first.", nickname));
         }
         
-        Ok(())
+        Ok(())
+    }
+    
+    async fn send_private_message_to_pubkey(&mut self, pubkey: &str, nickname: &str, content: &str) -> Result<()> {
+        // Create a private message channel name based on the pubkey
+        let dm_channel = format!("dm:{}", pubkey);
+        
+        // Add to private chats if not already there
+        self.private_chats.insert(pubkey.to_string(), nickname.to_string());
+        
+        // Create the private message
+        let message = Message {
+            channel: dm_channel,
+            nickname: self.identity.nickname.clone(),
+            content: content.to_string(),
+            timestamp: chrono::Utc::now(),
+            pubkey: Some(self.identity.pubkey.clone()),
+            is_own: true,
+            is_private: true,
+            recipient_pubkey: Some(pubkey.to_string()),
+            pow_difficulty: 0,
+            event_id: None,
+            relay_url: None,
+            is_mention: false,
+            send_id: None,
+        };
+        
+        // Add to channel manager for display
+        self.channel_manager.add_message_sync(message);
+        
+        // Send gift wrapped via Nostr (NIP-17), from the identity of the channel we met them in
+        let channel_hint = self.find_channel_for_pubkey(pubkey);
+        if let Err(e) = self.nostr_client.send_private_message(pubkey, content, &self.identity.nickname, channel_hint.as_deref()).await {
+            self.add_status_message(format!("Failed to send private message to {}: {}", nickname, e));
+        }
+        
+        // Enable auto-scrolling
+        self.should_autoscroll = true;
+        self.scroll_to_bottom();
+        
+        Ok(())
+    }
+    
+    /// Switch to a fresh ephemeral keypair
+    async fn new_ephemeral_identity(&mut self) {
+        // Derived channel keys come from the device seed, so keeping them would make
+        // the new identity indistinguishable from the old one in channels
+        if self.identity.uses_geohash_identities() {
+            self.add_status_message("Per-geohash keys are not used by the new ephemeral identity".to_string());
+        }
+        let mut identity = Identity::ephemeral();
+        if let Some(nickname) = &self.config.nickname {
+            identity.nickname = nickname.clone();
+        }
+        self.switch_identity(identity).await;
+    }
+    
+    /// Switch to the identity of an nsec entered at the hidden prompt
+    async fn load_identity_from_nsec(&mut self, nsec: &str) {
+        if nsec.is_empty() {
+            self.add_status_message("No nsec entered".to_string());
+            return;
+        }
+        
+        let mut identity = match Identity::from_nsec(nsec).await {
+            Ok(identity) => identity,
+            Err(e) => {
+                self.add_status_message(format!("Cannot load identity: {}", e));
+                return;
+            }
+        };
+        
+        if self.identity.uses_geohash_identities() {
+            if let Err(e) = identity.enable_geohash_identities() {
+                self.add_status_message(format!("Per-geohash keys unavailable: {}", e));
+            }
+        }
+        
+        self.switch_identity(identity).await;
+    }
+    
+    /// Switch back to an identity from /identity list
+    async fn use_session_identity(&mut self, index: &str) {
+        let identity = index
+            .parse::<usize>()
+            .ok()
+            .and_then(|n| n.checked_sub(1))
+            .and_then(|i| self.session_identities.get(i))
+            .cloned();
+        
+        match identity {
+            Some(identity) if identity.pubkey == self.identity.pubkey => {
+                self.add_status_message(format!("Already using {}", identity.nickname));
+            }
+            Some(identity) => self.switch_identity(identity).await,
+            None => self.add_status_message(format!("No identity #{} - see /identity list", index)),
+        }
+    }
+    
+    /// Make `identity` the active one and re-sign, resubscribe everything with it
+    async fn switch_identity(&mut self, identity: Identity) {
+        // Remember the outgoing identity as it is now (nickname may have changed)
+        let previous = std::mem::replace(&mut self.identity, identity);
+        self.remember_identity(previous);
+        self.remember_identity(self.identity.clone());
+        
+        if let Err(e) = self.nostr_client.set_identity(&self.identity).await {
+            self.add_status_message(format!("Error resubscribing with new identity: {}", e));
+        }
+        
+        let npub = self.identity.public_key().to_bech32().unwrap_or_else(|_| self.identity.pubkey.clone());
+        self.add_status_message(format!("Now using identity {} ({}, {})",
+            self.identity.nickname,
+            if self.identity.is_ephemeral { "ephemeral" } else { "authenticated" },
+            npub
+        ));
+    }
+    
+    fn remember_identity(&mut self, identity: Identity) {
+        match self.session_identities.iter_mut().find(|known| known.pubkey == identity.pubkey) {
+            Some(known) => *known = identity,
+            None => self.session_identities.push(identity),
+        }
+    }
+    
+    fn list_identities(&mut self) {
+        // Keep the list in sync with nickname changes of the active identity
+        self.remember_identity(self.identity.clone());
+        
+        let lines: Vec<String> = self.session_identities
+            .iter()
+            .enumerate()
+            .map(|(i, identity)| {
+                let npub = identity.public_key().to_bech32().unwrap_or_else(|_| identity.pubkey.clone());
+                format!("{} {}. {} ({}) {}",
+                    if identity.pubkey == self.identity.pubkey { "*" } else { " " },
+                    i + 1,
+                    identity.nickname,
+                    if identity.is_ephemeral { "ephemeral" } else { "authenticated" },
+                    npub
+                )
+            })
+            .collect();
+        
+        self.add_status_message("Identities this session (* = active, /identity use <number> to switch):".to_string());
+        for line in lines {
+            self.add_status_message(line);
+        }
+    }
+    
+    /// Show our published kind 0 profile
+    async fn show_profile(&mut self) {
+        if self.identity.is_ephemeral {
+            self.add_status_message("Ephemeral identities have no profile - use /identity load or --nsec".to_string());
+            return;
+        }
+        
+        let profile = match self.nostr_client.fetch_own_profile().await {
+            Ok(Some(profile)) if !profile.is_empty() => profile,
+            Ok(_) => {
+                self.add_status_message("No profile found on the connected relays - /profile create <field> <value> publishes a new one".to_string());
+                return;
+            }
+            Err(e) => {
+                self.add_status_message(format!("Cannot fetch profile: {}", e));
+                return;
+            }
+        };
+        
+        self.add_status_message(format!("Profile of {}:", self.identity.nickname));
+        for (field, value) in &profile {
+            let value = match value {
+                serde_json::Value::String(text) => text.clone(),
+                other => other.to_string(),
+            };
+            self.add_status_message(format!("  {}: {}", field, value));
+        }
+    }
+    
+    /// Change one field of our kind 0 profile and republish it
+    ///
+    /// Without `create` nothing is published unless the current profile was found,
+    /// a relay that is slow or lost it would otherwise get the other fields wiped.
+    async fn set_profile_field(&mut self, field: &str, value: &str, create: bool) {
+        if self.identity.is_ephemeral {
+            self.add_status_message("Ephemeral identities have no profile - use /identity load or --nsec".to_string());
+            return;
+        }
+        
+        match field {
+            "name" | "about" => {}
+            "picture" if !(value.starts_with("https://") || value.starts_with("http://")) => {
+                self.add_status_message("Picture must be an http(s) URL".to_string());
+                return;
+            }
+            "nip05" if !value.contains('.') => {
+                self.add_status_message("NIP-05 must look like name@example.com".to_string());
+                return;
+            }
+            "picture" | "nip05" => {}
+            _ => {
+                self.add_status_message("Profile field must be one of: name, about, picture, nip05".to_string());
+                return;
+            }
+        }
+        
+        // Merge into what is already published so other clients' fields are kept
+        let mut profile = match self.nostr_client.fetch_own_profile().await {
+            Ok(Some(profile)) => profile,
+            Ok(None) if create => serde_json::Map::new(),
+            Ok(None) => {
+                self.add_status_message("No profile found on the connected relays, not publishing to avoid overwriting it".to_string());
+                self.add_status_message(format!("If you never published one, start a new profile with /profile create {} <value>", field));
+                return;
+            }
+            Err(e) => {
+                self.add_status_message(format!("Cannot fetch current profile, not publishing to avoid overwriting it: {}", e));
+                return;
+            }
+        };
+        profile.insert(field.to_string(), serde_json::Value::String(value.to_string()));
+        
+        match self.nostr_client.publish_profile(&profile).await {
+            Ok(()) => self.add_status_message(format!("Profile {} updated", field)),
+            Err(e) => self.add_status_message(format!("Failed to publish profile: {}", e)),
+        }
     }
     
-    async fn change_nickname(&mut self, new_nick: &str) -> Result<()> {
+    pub async fn change_nickname(&mut self, new_nick: &str) -> Result<()> {
         let old_nick = self.identity.nickname.clone();
         self.identity.nickname = new_nick.to_string();
         self.add_status_message(format!("Nickname changed from {} to {}", old_nick, new_nick));
@@ -1017,18 +1781,31 @@ impl App {
             "BitchatX Commands:".to_string(),
             "/join, /j <geohash> - Join a geohash channel".to_string(),
             "/leave, /part, /l - Leave current channel".to_string(),
-            "/msg, /m <channel> <message> - Send message to specific channel".to_string(),
+            "/msg, /m <channel|nickname> <message> - Send message to a channel or a private message (NIP-17) to a user".to_string(),
             "/nick, /n <nickname> - Change your display name (session only)".to_string(),
+            "/profile show | set <name|about|picture|nip05> <value> - View or edit your Nostr profile (kind 0); create instead of set starts a new one".to_string(),
+            "/identity <new|load|list|use <n>> - Switch to a fresh ephemeral key, an nsec (hidden prompt) or an earlier identity".to_string(),
             "/list, /channels - List joined channels".to_string(),
+            "/window, /win <number> - Switch to a channel by its number in the channel list (Alt+1..9, Alt+0 for 10)".to_string(),
             "/all - Show recent activity from all geohash channels with active users (last 10 minutes)".to_string(),
             "/hug <nickname> - Send a hug to someone 🫂".to_string(),
             "/slap <nickname> - Slap someone with a large trout".to_string(),
             "/block [nickname] - Block user or list blocked users".to_string(),
             "/unblock <nickname> - Unblock a user".to_string(),
             "/spam <list|unmute|status> - Manage spam filter".to_string(),
-            "/whois, /w <nickname[#pubkey]> - Show user information (npub, channels)".to_string(),
+            "/pow [<bits>|off] | min <bits|off> | cancel - Mine NIP-13 proof of work on sends, require it on receive".to_string(),
+            "/whois, /w <nickname[#pubkey]> - Show user information (npub, channels, NIP-05)".to_string(),
+            "/inspect [nickname[#pubkey]|event id] - Show a message's event id, author and the relays that delivered it".to_string(),
+            "/search <text|/regex/> [#channel] [from:nick] - Search messages in all channels (Ctrl+F)".to_string(),
+            "/mentions [clear] - Messages that mentioned you or a highlight word; Enter jumps to one".to_string(),
+            "/script [list|load <name>|unload <name>] - Manage Rhai scripts from ~/.config/bitchatx/scripts".to_string(),
+            "/alias [name [expansion]] - List or define aliases ($0, $1-, $*, $nick, $chan; chain with ;), /unalias <name> removes one".to_string(),
             "/clear - Clear all messages from current channel".to_string(),
             "/status - Show connection status and relay information".to_string(),
+            "/relay list | add <wss://...> | remove <url> | info <url> - Manage relays (changes are remembered)".to_string(),
+            "/set [<key> [<value>]] - List, show or change settings (lists are comma separated)".to_string(),
+            "/save - Write the current settings to config.toml".to_string(),
+            "/log [on|off] - Show or switch logging of the current channel to ~/.local/share/bitchatx/logs".to_string(),
             "/version - Show application version and fun quote".to_string(),
             "/help, /h, /commands - Show this help".to_string(),
             "/quit, /q, /exit - Exit BitchatX".to_string(),
@@ -1052,10 +1829,8 @@ impl App {
         self.scroll_to_bottom();
     }
     
-    fn is_valid_geohash(&self, geohash: &str) -> bool {
-        // Basic geohash validation
-        geohash.len() >= 1 && geohash.len() <= 12 && 
-        geohash.chars().all(|c| "0123456789bcdefghjkmnpqrstuvwxyz".contains(c))
+    pub fn is_valid_geohash(&self, geohash: &str) -> bool {
+        crate::channels::is_valid_geohash(geohash)
     }
     
     pub fn add_status_message(&mut self, message: String) {
@@ -1069,11 +1844,16 @@ impl App {
             pubkey: None,
             is_private: false,
             recipient_pubkey: None,
+            pow_difficulty: 0,
+            event_id: None,
+            relay_url: None,
+            is_mention: false,
+            send_id: None,
         };
         
         // Add directly to channel manager without going through async receiver
         // This ensures immediate display
-        let _ = self.channel_manager.add_message_sync(system_message);
+        self.channel_manager.add_message_sync(system_message);
         
         // Trigger autoscroll if we're in system channel
         if self.current_channel.as_deref() == Some(&self.system_channel) && self.should_autoscroll {
@@ -1093,10 +1873,15 @@ impl App {
             pubkey: None,
             is_private: false,
             recipient_pubkey: None,
+            pow_difficulty: 0,
+            event_id: None,
+            relay_url: None,
+            is_mention: false,
+            send_id: None,
         };
         
         // Add directly to channel manager without going through async receiver
-        let _ = self.channel_manager.add_message_sync(system_message);
+        self.channel_manager.add_message_sync(system_message);
         
         // Trigger autoscroll since we added a new message
         if self.should_autoscroll {
@@ -1105,41 +1890,113 @@ impl App {
     }
     
     pub async fn on_tick(&mut self) -> Result<()> {
+        self.update_connection_state();
+        
+        // Drop finished proof of work jobs from the input bar indicator
+        self.pow_jobs.retain(|job| !job.is_finished());
+        self.check_pending_sends();
+        
         // Process incoming messages
         let mut new_messages_count = 0;
-        while let Ok(message) = self.message_rx.try_recv() {
+        let run_hooks = self.scripts.has_hooks();
+        if run_hooks {
+            self.update_script_context();
+        }
+        while let Ok(mut message) = self.message_rx.try_recv() {
+            // The same event arrives once per relay; only the first copy counts
+            if !self.channel_manager.record_delivery(&message) {
+                continue;
+            }
+            
             // Filter out messages from blocked users (like Android app's MeshDelegateHandler)
             if self.is_user_blocked(&message.pubkey) {
                 continue; // Skip blocked messages entirely
             }
             
+            if run_hooks && !self.scripts.run_hooks(HookStage::BeforeSpam, &message) {
+                continue;
+            }
+            
             // Filter out spam messages and notify if timestamp manipulation detected
             if self.spam_filter.is_spam(&message) {
                 // Check if this was timestamp-based spam for notification
                 let current_time = chrono::Utc::now();
-                let is_future_spam = message.timestamp > current_time + chrono::Duration::seconds(300);
-                let is_old_spam = message.timestamp < current_time - chrono::Duration::hours(24);
+                let max_future = self.config.spam.max_future_time_seconds;
+                let max_past = self.config.spam.max_past_time_hours;
+                let is_future_spam = message.timestamp > current_time + chrono::Duration::seconds(max_future as i64);
+                let is_old_spam = message.timestamp < current_time - chrono::Duration::hours(max_past as i64);
                 
                 if is_future_spam {
                     let nickname = message.nickname.clone();
-                    let minutes_future = (message.timestamp - current_time).num_minutes();
-                    self.add_status_message(format!("⚠️ Filtered future-dated message from {} ({}min in future)", nickname, minutes_future));
+                    let seconds_future = (message.timestamp - current_time).num_seconds();
+                    self.add_status_message(format!("⚠️ Filtered future-dated message from {} ({}s in future, limit {}s)", nickname, seconds_future, max_future));
                 } else if is_old_spam {
                     let nickname = message.nickname.clone();
                     let hours_old = (current_time - message.timestamp).num_hours();
-                    self.add_status_message(format!("⚠️ Filtered old message from {} ({}hr old)", nickname, hours_old));
+                    self.add_status_message(format!("⚠️ Filtered old message from {} ({}hr old, limit {}hr)", nickname, hours_old, max_past));
                 }
                 
                 continue; // Skip spam messages
             }
             
+            // Track who we're in a private chat with so the DM shows up in the channel list
+            if message.is_private {
+                if let Some(pubkey) = &message.pubkey {
+                    // Bitchat packets carry no nickname, so reuse the one seen in channels
+                    if message.nickname == format!("anon{}", &pubkey[..8.min(pubkey.len())]) {
+                        if let Some(known) = self.private_chats.get(pubkey).cloned()
+                            .or_else(|| self.find_nickname_for_pubkey(pubkey))
+                        {
+                            message.nickname = known;
+                        }
+                    }
+                    
+                    if self.private_chats.insert(pubkey.clone(), message.nickname.clone()).is_none() {
+                        self.add_status_message(format!("New private message from {}", message.nickname));
+                    }
+                }
+            }
+            
+            // Look up who they claim to be in the background, for the verified mark
+            if self.config.ui.verify_nip05 {
+                if let Some(pubkey) = &message.pubkey {
+                    self.nip05.request(pubkey);
+                }
+            }
+            
+            if !message.is_own && !message.is_private && message.pubkey.is_some() && self.mentions_us(&message.content) {
+                message.is_mention = true;
+            }
+            if message.is_mention {
+                self.mentions.push(SearchHit::new(&message));
+                if self.mentions.len() > MAX_MENTIONS {
+                    self.mentions.remove(0);
+                }
+            }
+            
             // Use sync version for faster processing (no await overhead)
-            let _ = self.channel_manager.add_message_sync(message);
+            if run_hooks {
+                self.scripts.run_hooks(HookStage::AfterSpam, &message);
+            }
+            // Channels in the background keep count for the activity bar
+            let channel = message.channel.clone();
+            let is_mention = message.is_mention || message.is_private;
+            self.channel_manager.add_message_sync(message);
+            if self.current_channel.as_deref() != Some(channel.as_str()) {
+                if let Some(channel) = self.channel_manager.get_channel_mut(&channel) {
+                    channel.unread += 1;
+                    if is_mention {
+                        channel.unread_mentions += 1;
+                    }
+                }
+            }
             new_messages_count += 1;
         }
+        self.run_script_actions().await?;
         
-        // Auto-scroll to bottom if we received new messages
-        if new_messages_count > 0 {
+        // Auto-scroll to bottom if we received new messages, unless the user is reading
+        // further back (a search hit or older history)
+        if new_messages_count > 0 && self.should_autoscroll {
             // For new messages, completely reset scrolling state to ensure visibility
             self.force_scroll_to_bottom();
             self.just_processed_messages = true;
@@ -1152,12 +2009,44 @@ impl App {
             self.add_status_message(status);
         }
         
+        // Follow the scroll position, so drawing never has to touch history
+        self.page_history();
+        
+        if let Some(error) = self.channel_manager.take_error() {
+            self.add_status_message(error);
+        }
+        
+        // Calls from the control socket run here, like commands typed in the UI
+        while let Some(request) = self.control.as_mut().and_then(ControlServer::try_next) {
+            crate::control::handle(self, request).await;
+        }
+        
         // Periodically clean up old spam filter data
         self.spam_filter.cleanup_old_data();
         
         Ok(())
     }
     
+    /// Page older messages in from history when scrolled to the top of what's in memory,
+    /// and let them go again once back at the bottom
+    fn page_history(&mut self) {
+        let Some(channel_name) = self.current_channel.clone() else {
+            return;
+        };
+        
+        if self.should_autoscroll {
+            let has_scrollback = self.get_current_channel().is_some_and(|channel| channel.scrollback > 0 || channel.history_exhausted);
+            if has_scrollback {
+                if let Some(channel) = self.channel_manager.get_channel_mut(&channel_name) {
+                    channel.release_scrollback();
+                }
+            }
+        } else if self.scroll_offset == 0 {
+            // Keep the same messages on screen, now further down
+            self.scroll_offset += self.channel_manager.load_older(&channel_name, HISTORY_PAGE_SIZE);
+        }
+    }
+    
     pub fn get_current_channel(&self) -> Option<&Channel> {
         if let Some(channel_name) = &self.current_channel {
             self.channel_manager.get_channel(channel_name)
@@ -1180,40 +2069,80 @@ impl App {
             }
         }
         
+        // Add private chats so they can be switched to and searched
+        for (channel, _) in self.channel_manager.list_all_channels() {
+            if channel.starts_with("dm:") {
+                channels.push(channel);
+            }
+        }
+        
         channels
     }
     
+    /// Open a channel, which counts its messages as read
+    fn set_current_channel(&mut self, channel: &str) {
+        self.current_channel = Some(channel.to_string());
+        if let Some(channel) = self.channel_manager.get_channel_mut(channel) {
+            channel.mark_read();
+        }
+    }
+    
+    /// Channels numbered like BitchX windows: system is 1, the others follow in the
+    /// order they were opened. The channel list, activity bar, Tab and Alt+N use these
+    pub fn windows(&self) -> Vec<String> {
+        let mut windows = vec![self.system_channel.clone()];
+        for channel in self.channel_manager.list_windows() {
+            if *channel == self.system_channel {
+                continue;
+            }
+            // Private chats are listed once we know who they're with
+            if let Some(pubkey) = channel.strip_prefix("dm:") {
+                if !self.private_chats.contains_key(pubkey) {
+                    continue;
+                }
+            }
+            windows.push(channel.clone());
+        }
+        windows
+    }
+    
     fn switch_to_next_channel(&mut self) {
-        let all_channels = self.get_all_channels();
-        if all_channels.len() <= 1 {
+        let windows = self.windows();
+        if windows.len() <= 1 {
             return; // No other channels to switch to
         }
         
-        if let Some(current) = &self.current_channel {
-            if let Some(current_index) = all_channels.iter().position(|ch| ch == current) {
-                let next_index = (current_index + 1) % all_channels.len();
-                self.current_channel = Some(all_channels[next_index].clone());
-                
-                // Force scroll to bottom when switching channels
-                self.force_scroll_to_bottom();
-                
-                // Add status message about channel switch
-                let new_channel = &all_channels[next_index];
-                if new_channel == "system" {
-                    self.add_status_message("Switched to system channel".to_string());
-                } else {
-                    self.add_status_message(format!("Switched to channel #{}", new_channel));
-                }
-            }
-        } else {
-            // If no current channel, switch to first channel (system)
-            self.current_channel = Some(all_channels[0].clone());
-            self.force_scroll_to_bottom();
+        // Without a current channel this starts at the first one (system)
+        let next_index = self.current_channel.as_ref()
+            .and_then(|current| windows.iter().position(|ch| ch == current))
+            .map_or(0, |current_index| (current_index + 1) % windows.len());
+        self.switch_to_window(next_index + 1);
+    }
+    
+    /// Make window `number` from the channel list current (Alt+N, /window N)
+    fn switch_to_window(&mut self, number: usize) {
+        let windows = self.windows();
+        let Some(new_channel) = number.checked_sub(1).and_then(|index| windows.get(index)) else {
+            self.add_status_message(format!("No window {} (1-{})", number, windows.len()));
+            return;
+        };
+        self.set_current_channel(new_channel);
+        
+        // Force scroll to bottom when switching channels
+        self.force_scroll_to_bottom();
+        
+        // Add status message about channel switch
+        if new_channel == "system" {
             self.add_status_message("Switched to system channel".to_string());
+        } else if let Some(pubkey) = new_channel.strip_prefix("dm:") {
+            let nickname = self.private_chats.get(pubkey).cloned().unwrap_or_else(|| pubkey[..8].to_string());
+            self.add_status_message(format!("Switched to private chat with @{}", nickname));
+        } else {
+            self.add_status_message(format!("Switched to channel #{}", new_channel));
         }
     }
     
-    pub fn get_visible_messages(&self, height: usize) -> (Vec<(String, String, String, bool, Option<String>)>, usize) {
+    pub fn get_visible_messages(&self, height: usize) -> (Vec<VisibleMessage>, usize) {
         if let Some(channel) = self.get_current_channel() {
             let message_count = channel.messages.len();
             
@@ -1245,11 +2174,7 @@ impl App {
                     }
                 } else {
                     // Ensure scroll_offset doesn't go beyond bounds
-                    let max_offset = if total_messages > viewport_height {
-                        total_messages - viewport_height
-                    } else {
-                        0
-                    };
+                    let max_offset = total_messages.saturating_sub(viewport_height);
                     self.scroll_offset.min(max_offset)
                 }
             };
@@ -1262,11 +2187,13 @@ impl App {
             let message_data: Vec<_> = channel.messages[start_index..end_index]
                 .iter()
                 .map(|msg| (
-                    msg.timestamp.with_timezone(&chrono::Local).format("%H:%M:%S").to_string(),
+                    msg.timestamp.with_timezone(&chrono::Local).format(&self.config.ui.timestamp_format).to_string(),
                     msg.nickname.clone(),
                     msg.content.clone(),
                     msg.is_own,
-                    msg.pubkey.clone()
+                    msg.pubkey.clone(),
+                    msg.is_mention,
+                    msg.send_id.is_some()
                 ))
                 .collect();
             (message_data, effective_scroll_offset)
@@ -1294,7 +2221,10 @@ impl App {
             let word_info = self.find_current_word();
             if let Some((word, start_pos, _end_pos)) = word_info {
                 if word.len() >= 2 { // Minimum 2 characters to start completion
-                    let matches = if self.is_action_command_context(start_pos) && 
+                    let matches = if start_pos == 0 && word.starts_with('/') {
+                        // Command names: the user's aliases
+                        self.aliases.matching(&word)
+                    } else if self.is_action_command_context(start_pos) && 
                                      (self.input.trim_start().starts_with("/msg ") || self.input.trim_start().starts_with("/m ")) {
                         // For /msg command, complete both channels and nicknames
                         self.get_msg_completion_matches(&word)
@@ -1375,7 +2305,10 @@ impl App {
             chars.drain(start_pos..end_pos);
             
             // Determine the appropriate suffix based on context
-            let replacement_with_suffix = if is_slash_command_context {
+            let replacement_with_suffix = if start_pos == 0 && state.prefix.starts_with('/') {
+                // Completed command name, ready for its arguments
+                format!("{} ", replacement)
+            } else if is_slash_command_context {
                 replacement.to_string()
             } else if is_msg_command {
                 // For /msg and /m commands, use space instead of ": "
@@ -1453,21 +2386,22 @@ impl App {
                 nickname: self.identity.nickname.clone(),
                 content: action.to_string(),
                 timestamp: chrono::Utc::now(),
-                pubkey: Some(self.identity.pubkey.clone()),
+                pubkey: Some(self.identity.pubkey_for_geohash(channel)),
                 is_own: true,
                 is_private: false,
                 recipient_pubkey: None,
+                pow_difficulty: 0,
+                event_id: None,
+                relay_url: None,
+                is_mention: false,
+                send_id: None,
             };
             
             if channel == "system" {
                 // For system channel, just show locally without sending to network
                 self.channel_manager.add_message_sync(message);
             } else {
-                // Add local echo first for instant feedback
-                self.channel_manager.add_message_sync(message);
-                
-                // Send to Nostr for other channels (now non-blocking)
-                let _ = self.nostr_client.send_message(channel, action, &self.identity.nickname).await;
+                self.send_with_local_echo(message).await;
             }
         } else {
             self.add_status_message("No channel selected".to_string());
@@ -1498,7 +2432,7 @@ impl App {
             "No quotes available."
         } else {
             let index = rand::thread_rng().gen_range(0..quotes.len());
-            &quotes[index]
+            quotes[index]
         };
         
         let version_message = format!(
@@ -1525,7 +2459,7 @@ impl App {
     async fn show_status(&mut self) {
         // Get relay connection information
         let relay_count = self.nostr_client.get_relay_count();
-        let (default_relays, georelays) = self.nostr_client.get_relay_stats();
+        let (default_relays, georelays, manual_relays) = self.nostr_client.get_relay_stats();
         
         // Build status message
         let mut status_lines = Vec::new();
@@ -1533,6 +2467,7 @@ impl App {
         status_lines.push(format!("Connected Relays: {}", relay_count));
         status_lines.push(format!("  Default Relays: {}", default_relays));
         status_lines.push(format!("  GeoRelays: {}", georelays));
+        status_lines.push(format!("  Manual Relays: {}", manual_relays));
         
         // Show current channel info
         if let Some(current) = &self.current_channel {
@@ -1546,6 +2481,12 @@ impl App {
         };
         status_lines.push(format!("Your NPub: {}", npub));
         status_lines.push(format!("Nickname: {}", self.identity.nickname));
+        status_lines.push(format!("Signer: {}",
+            if self.identity.uses_remote_signer() { "NIP-46 remote signer" } else { "local key" }
+        ));
+        status_lines.push(format!("Channel Keys: {}",
+            if self.identity.uses_geohash_identities() { "derived per geohash" } else { "shared" }
+        ));
         
         // Show some stats
         let joined_channels = self.channel_manager.list_channels();
@@ -1559,6 +2500,19 @@ impl App {
             status_lines.push("Spam Filter: Disabled".to_string());
         }
         
+        status_lines.push(format!("Message History: {}", if self.channel_manager.has_history() { "saved on disk" } else { "off" }));
+        
+        // Relays caught passing off events with bad ids or signatures
+        let event_stats = self.nostr_client.relay_event_stats();
+        if event_stats.is_empty() {
+            status_lines.push("Event Verification: no forged or malformed events received".to_string());
+        } else {
+            status_lines.push("Event Verification: invalid events dropped".to_string());
+            for (relay_url, stats) in event_stats {
+                status_lines.push(format!("  {}: {} forged, {} malformed", relay_url, stats.forged, stats.malformed));
+            }
+        }
+
         status_lines.push("=== End Status ===".to_string());
         
         // Output each line to current channel
@@ -1609,9 +2563,8 @@ impl App {
         let mut args = Vec::new();
         let mut current_arg = String::new();
         let mut in_quotes = false;
-        let mut chars = input.chars().peekable();
         
-        while let Some(ch) = chars.next() {
+        for ch in input.chars() {
             match ch {
                 '"' => {
                     in_quotes = !in_quotes;
@@ -1798,6 +2751,17 @@ impl App {
         None
     }
     
+    /// Find a joined geohash channel where this pubkey has been active
+    fn find_channel_for_pubkey(&self, pubkey: &str) -> Option<String> {
+        self.channel_manager.list_channels()
+            .into_iter()
+            .find(|channel_name| {
+                self.channel_manager.get_channel(channel_name)
+                    .map(|channel| channel.participants.values().any(|p| p.pubkey.as_deref() == Some(pubkey)))
+                    .unwrap_or(false)
+            })
+    }
+    
     pub fn is_user_blocked(&self, pubkey: &Option<String>) -> bool {
         if let Some(pk) = pubkey {
             self.blocked_users.contains(pk)
@@ -1806,7 +2770,25 @@ impl App {
         }
     }
     
-    #[allow(dead_code)]
+    /// Follow relay health: connected while any relay is up, disconnected once none are
+    /// and none are still trying for the first time
+    fn update_connection_state(&mut self) {
+        let (connected, connecting, _) = self.nostr_client.relay_health();
+        
+        if connected > 0 {
+            if self.state == AppState::Disconnected {
+                self.add_status_message(format!("Reconnected to the Nostr network ({} relays)", connected));
+            }
+            self.state = AppState::Connected;
+        } else if connecting == 0 {
+            if self.state != AppState::Disconnected && !matches!(self.state, AppState::Error(_)) {
+                self.handle_connection_lost();
+            }
+        } else if self.state == AppState::Connected {
+            self.state = AppState::Connecting;
+        }
+    }
+    
     pub fn handle_connection_lost(&mut self) {
         self.state = AppState::Disconnected;
         self.add_status_message("Connection lost. Attempting to reconnect...".to_string());
@@ -1820,7 +2802,7 @@ impl App {
     
     fn copy_to_clipboard(&self) {
         if let Ok(mut clipboard) = Clipboard::new() {
-            if let Err(_) = clipboard.set_text(self.input.clone()) {
+            if clipboard.set_text(self.input.clone()).is_err() {
                 // Silently fail if clipboard access fails
             }
         }
@@ -1840,7 +2822,7 @@ impl App {
     
     fn cut_to_clipboard(&mut self) {
         if let Ok(mut clipboard) = Clipboard::new() {
-            if let Err(_) = clipboard.set_text(self.input.clone()) {
+            if clipboard.set_text(self.input.clone()).is_err() {
                 // Silently fail if clipboard access fails
             }
             self.input.clear();
@@ -1853,7 +2835,130 @@ impl App {
         self.cursor_position = self.input.len();
     }
     
+    async fn list_relays(&mut self) {
+        let relays = self.nostr_client.relay_list().await;
+        
+        self.add_status_message(format!("=== Relays ({}) ===", relays.len()));
+        for relay in relays {
+            let latency = relay.latency
+                .map(|latency| format!("{}ms", latency.as_millis()))
+                .unwrap_or_else(|| "-".to_string());
+            self.add_status_message(format!("{:<8} {:<13} {:>7}  {}", relay.source, relay.status, latency, relay.url));
+        }
+    }
+    
+    async fn show_relay_info(&mut self, url: &str) {
+        self.add_status_message(format!("Fetching relay information for {}...", url));
+        
+        let document = match self.nostr_client.relay_info(url).await {
+            Ok(document) => document,
+            Err(e) => {
+                self.add_status_message(format!("Could not fetch relay information: {}", e));
+                return;
+            }
+        };
+        
+        let mut lines = vec![format!("=== {} ===", document.name.as_deref().unwrap_or(url))];
+        let fields = [
+            ("Description", document.description),
+            ("Operator", document.pubkey),
+            ("Contact", document.contact),
+            ("Software", document.software),
+            ("Version", document.version),
+            ("Posting policy", document.posting_policy),
+        ];
+        lines.extend(fields.into_iter().filter_map(|(label, value)| value.map(|value| format!("{}: {}", label, value))));
+        if let Some(nips) = document.supported_nips {
+            let nips: Vec<String> = nips.iter().map(|nip| nip.to_string()).collect();
+            lines.push(format!("Supported NIPs: {}", nips.join(", ")));
+        }
+        if let Some(limitation) = document.limitation {
+            if limitation.auth_required == Some(true) {
+                lines.push("Requires authentication (NIP-42)".to_string());
+            }
+            if limitation.payment_required == Some(true) {
+                lines.push("Requires payment".to_string());
+            }
+            if let Some(difficulty) = limitation.min_pow_difficulty.filter(|d| *d > 0) {
+                lines.push(format!("Minimum proof of work: {} bits", difficulty));
+            }
+            if let Some(length) = limitation.max_content_length {
+                lines.push(format!("Max content length: {}", length));
+            }
+        }
+        
+        for line in lines {
+            self.add_status_message(line);
+        }
+    }
+    
+    /// Show event details for the newest matching message in the current channel
+    fn inspect_message(&mut self, target: Option<&str>) {
+        let Some(channel) = self.current_channel.as_ref().and_then(|name| self.channel_manager.get_channel(name)) else {
+            self.add_status_message("No channel selected".to_string());
+            return;
+        };
+        
+        let found = channel.messages.iter().rev().find(|message| {
+            let Some(event_id) = &message.event_id else {
+                return false;
+            };
+            match target {
+                None => true,
+                Some(target) => {
+                    let (nickname, pubkey_suffix) = match target.rsplit_once('#') {
+                        Some((nickname, suffix)) => (nickname, Some(suffix)),
+                        None => (target, None),
+                    };
+                    let by_author = message.nickname.eq_ignore_ascii_case(nickname)
+                        && pubkey_suffix.is_none_or(|suffix| message.pubkey.as_deref().is_some_and(|pk| pk.ends_with(suffix) || pk.starts_with(suffix)));
+                    by_author || (target.len() >= 4 && event_id.starts_with(&target.to_lowercase()))
+                }
+            }
+        });
+        
+        let Some(message) = found.cloned() else {
+            self.add_status_message(match target {
+                Some(target) => format!("No received message from or with id {} in this channel", target),
+                None => "No received messages in this channel to inspect".to_string(),
+            });
+            return;
+        };
+        
+        let event_id = message.event_id.clone().unwrap_or_default();
+        let author = message.pubkey.as_deref()
+            .map(|pubkey| PublicKey::from_hex(pubkey)
+                .ok()
+                .and_then(|pk| pk.to_bech32().ok())
+                .unwrap_or_else(|| pubkey.to_string()))
+            .unwrap_or_else(|| "unknown".to_string());
+        let relays = self.channel_manager.event_relays(&event_id).map(|relays| relays.to_vec()).unwrap_or_default();
+        
+        let mut lines = vec![
+            format!("=== Message {} ===", &event_id[..8.min(event_id.len())]),
+            format!("Event ID: {}", event_id),
+            format!("From: {} ({})", self.format_display_nickname(&message.nickname, &message.pubkey), author),
+            format!("Time: {}", message.timestamp.format("%Y-%m-%d %H:%M:%S UTC")),
+            format!("Content: {}", message.content),
+        ];
+        if message.pow_difficulty > 0 {
+            lines.push(format!("Proof of Work: {} bits", message.pow_difficulty));
+        }
+        lines.push(format!("Delivered by {} relay{}:", relays.len(), if relays.len() == 1 { "" } else { "s" }));
+        lines.extend(relays.iter().map(|relay| format!("  {}", relay)));
+        
+        for line in lines {
+            self.add_message_to_current_channel(line);
+        }
+    }
+    
     async fn whois_user(&mut self, input: &str) {        
+        // Our own nickname shows which key we use in each channel
+        if input.eq_ignore_ascii_case(&self.identity.nickname) {
+            self.whois_self();
+            return;
+        }
+        
         // Parse input to extract nickname and optional pubkey suffix
         let (target_nickname, target_pubkey_prefix) = if let Some(hash_pos) = input.rfind('#') {
             let nickname = &input[..hash_pos];
@@ -1974,6 +3079,13 @@ impl App {
                 self.add_message_to_current_channel(format!("PubKey: {}", short_pubkey));
                 self.add_message_to_current_channel(format!("Full PubKey: {}", pubkey));
                 
+                let nip05_line = match self.nip05.verify(&pubkey).await {
+                    Nip05Status::Verified(identifier) => format!("NIP-05: {} ✓ verified", identifier),
+                    Nip05Status::Unverified(identifier) => format!("NIP-05: {} ✗ not confirmed by its domain", identifier),
+                    Nip05Status::None | Nip05Status::Pending => "NIP-05: none".to_string(),
+                };
+                self.add_message_to_current_channel(nip05_line);
+                
                 if channels_found.is_empty() {
                     self.add_message_to_current_channel("Channels: No recent activity".to_string());
                 } else {
@@ -1994,13 +3106,44 @@ impl App {
         }
     }
     
+    fn whois_self(&mut self) {
+        let to_npub = |pubkey: &str| match PublicKey::from_hex(pubkey) {
+            Ok(pk) => pk.to_bech32().unwrap_or_else(|_| "invalid".to_string()),
+            Err(_) => "invalid".to_string(),
+        };
+        
+        self.add_message_to_current_channel("=== WHOIS Information ===".to_string());
+        self.add_message_to_current_channel(format!("Nickname: {} (you)", self.identity.nickname));
+        self.add_message_to_current_channel(format!("NPub: {}", to_npub(&self.identity.pubkey)));
+        
+        if self.identity.uses_geohash_identities() {
+            self.add_message_to_current_channel("Channel keys (derived per geohash):".to_string());
+            let channels = self.channel_manager.list_channels();
+            if channels.is_empty() {
+                self.add_message_to_current_channel("  No joined channels".to_string());
+            }
+            for channel in channels {
+                let pubkey = self.identity.pubkey_for_geohash(&channel);
+                let display_name = self.format_display_nickname(&self.identity.nickname, &Some(pubkey.clone()));
+                self.add_message_to_current_channel(format!("  #{} as {} - {}", channel, display_name, to_npub(&pubkey)));
+            }
+        } else {
+            self.add_message_to_current_channel("Channel keys: same key in every channel".to_string());
+        }
+        self.add_message_to_current_channel("=== End WHOIS ===".to_string());
+    }
+    
     /// Format a nickname with pubkey suffix if available (e.g., "alice#7b9f")
     pub fn format_display_nickname(&self, nickname: &str, pubkey: &Option<String>) -> String {
         match pubkey {
             Some(pk) if pk.len() >= 4 => {
                 // Take last 4 characters of pubkey as suffix for better uniqueness
                 let suffix = &pk[pk.len()-4..];
-                format!("{}#{}", nickname, suffix)
+                if self.nip05.is_verified(pk) {
+                    format!("{}#{} ✓", nickname, suffix)
+                } else {
+                    format!("{}#{}", nickname, suffix)
+                }
             }
             _ => nickname.to_string(),
         }
@@ -2063,6 +3206,45 @@ impl App {
         }
     }
     
+    /// NIP-13 difficulty to mine on send and to require on receive (0 = off)
+    pub fn set_pow_policy(&mut self, mining: u8, minimum: u8) {
+        self.nostr_client.set_pow_difficulty(mining);
+        self.spam_filter.set_min_pow_difficulty(minimum);
+    }
+    
+    fn parse_pow_bits(input: &str) -> Option<u8> {
+        if input.eq_ignore_ascii_case("off") {
+            return Some(0);
+        }
+        input.parse::<u8>().ok().filter(|bits| *bits <= MAX_POW_DIFFICULTY)
+    }
+    
+    fn show_pow_status(&mut self) {
+        let mining = match self.nostr_client.pow_difficulty() {
+            0 => "off".to_string(),
+            bits => format!("{} bits", bits),
+        };
+        let minimum = match self.spam_filter.min_pow_difficulty() {
+            0 => "off".to_string(),
+            bits => format!("{} bits", bits),
+        };
+        
+        self.add_status_message("=== Proof of Work (NIP-13) ===".to_string());
+        self.add_status_message(format!("Mining on send: {}", mining));
+        self.add_status_message(format!("Required on receive: {}", minimum));
+        self.add_status_message(format!("Messages being mined: {}", self.pow_jobs.len()));
+    }
+    
+    fn cancel_pow_jobs(&mut self) {
+        if self.pow_jobs.is_empty() {
+            self.add_status_message("No messages are being mined".to_string());
+            return;
+        }
+        for job in self.pow_jobs.drain(..) {
+            job.cancel();
+        }
+    }
+    
     fn show_spam_filter_status(&mut self) {
         let auto_muted = self.spam_filter.get_auto_muted_users();
         let muted_count = auto_muted.len();
@@ -2070,17 +3252,194 @@ impl App {
         self.add_message_to_current_channel("=== Spam Filter Status ===".to_string());
         self.add_message_to_current_channel(format!("Currently auto-muted users: {}", muted_count));
         self.add_message_to_current_channel("Filters enabled:".to_string());
-        self.add_message_to_current_channel("  • Message frequency limit (15/minute)".to_string());
-        self.add_message_to_current_channel("  • Duplicate message detection".to_string());
-        self.add_message_to_current_channel("  • Spam keyword filtering".to_string());
+        self.add_message_to_current_channel(format!("  • Message frequency limit ({}/minute)", self.config.spam.max_messages_per_minute));
+        self.add_message_to_current_channel(format!("  • Duplicate message detection (mute after {})", self.config.spam.duplicate_message_threshold));
+        self.add_message_to_current_channel(format!("  • Spam keyword filtering ({} keywords)", self.config.spam.keywords.len()));
         self.add_message_to_current_channel("  • Excessive caps detection".to_string());
-        self.add_message_to_current_channel("  • Future timestamp rejection (>5min)".to_string());
-        self.add_message_to_current_channel("  • Old timestamp rejection (>24hr)".to_string());
+        self.add_message_to_current_channel(format!("  • Future timestamp rejection (>{}s)", self.config.spam.max_future_time_seconds));
+        self.add_message_to_current_channel(format!("  • Old timestamp rejection (>{}hr)", self.config.spam.max_past_time_hours));
+        if self.spam_filter.min_pow_difficulty() > 0 {
+            self.add_message_to_current_channel(format!("  • Proof of work below {} bits", self.spam_filter.min_pow_difficulty()));
+        }
         self.add_message_to_current_channel("Auto-mute duration: 10 minutes".to_string());
         self.add_message_to_current_channel("Use '/spam list' to see muted users".to_string());
         self.add_message_to_current_channel("Use '/spam unmute <nickname>' to manually unmute".to_string());
     }
     
+    fn list_settings(&mut self) {
+        self.add_status_message(format!("Settings ({}):", self.config_path.display()));
+        for key in config::SETTINGS {
+            let value = self.config.get(key).unwrap_or_default();
+            self.add_status_message(format!("  {} = {}", key, value));
+        }
+        self.add_status_message("Change with /set <key> <value>, keep with /save".to_string());
+    }
+    
+    fn show_setting(&mut self, key: &str) {
+        match self.config.get(key) {
+            Some(value) => self.add_status_message(format!("{} = {}", key, value)),
+            None => self.add_status_message(format!("Unknown setting {} (see /set for the list)", key)),
+        }
+    }
+    
+    fn change_setting(&mut self, key: &str, value: &str) {
+        if let Err(e) = self.config.set(key, value) {
+            self.add_status_message(format!("Cannot set {}: {}", key, e));
+            return;
+        }
+        
+        // Apply what can change at runtime; the rest is only read on startup
+        let applies = match key {
+            "spam.min_pow_difficulty" => {
+                self.spam_filter.set_min_pow_difficulty(self.config.spam.min_pow_difficulty);
+                "now"
+            }
+            _ if key.starts_with("spam.") => {
+                self.spam_filter.configure(&self.config.spam);
+                "now"
+            }
+            "pow.difficulty" => {
+                self.nostr_client.set_pow_difficulty(self.config.pow.difficulty);
+                "now"
+            }
+            "ui.tick_rate_ms" | "ui.timestamp_format" | "ui.highlight_words" | "ui.verify_nip05" => "now",
+            "dm.bitchat_format" => {
+                self.nostr_client.set_bitchat_first_contact(self.config.dm.bitchat_format);
+                "now"
+            }
+            _ if key.starts_with("log.") => {
+                self.channel_manager.chat_log_mut().configure(&self.config.log);
+                "now"
+            }
+            "nickname" => "on the next start or /identity new",
+            _ => "on the next start",
+        };
+        
+        let value = self.config.get(key).unwrap_or_default();
+        self.add_status_message(format!("{} = {} (applies {}, /save to keep it)", key, value, applies));
+    }
+    
+    fn show_log_status(&mut self) {
+        let channel = self.current_channel.clone().unwrap_or_else(|| self.system_channel.clone());
+        let chat_log = self.channel_manager.chat_log();
+        let status = if chat_log.is_logging(&channel) {
+            format!("Logging #{} to {}", channel, chat_log.path_for(&channel).display())
+        } else {
+            format!("Not logging #{} (/log on to start)", channel)
+        };
+        let default = if self.config.log.enabled { "on" } else { "off" };
+        
+        self.add_status_message(status);
+        self.add_status_message(format!("Logging is {} by default for other channels (/set log.enabled)", default));
+    }
+    
+    fn set_channel_logging(&mut self, on: bool) {
+        let channel = self.current_channel.clone().unwrap_or_else(|| self.system_channel.clone());
+        self.channel_manager.chat_log_mut().set_channel_logging(&channel, on);
+        if on {
+            let path = self.channel_manager.chat_log().path_for(&channel);
+            self.add_status_message(format!("Logging #{} to {}", channel, path.display()));
+        } else {
+            self.add_status_message(format!("Stopped logging #{}", channel));
+        }
+    }
+    
+    // Keep config.toml's relay list in step with /relay add and remove
+    fn save_relays(&mut self, done: String) {
+        self.config.removed_relays = self.nostr_client.removed_relays().to_vec();
+        match self.config.save_relays(&self.config_path) {
+            Ok(()) => self.add_status_message(format!("{} (saved to {})", done, self.config_path.display())),
+            Err(e) => self.add_status_message(format!("{}, but cannot save it: {}", done, e)),
+        }
+    }
+    
+    fn save_config(&mut self) {
+        match self.config.save(&self.config_path) {
+            Ok(()) => self.add_status_message(format!("Settings saved to {}", self.config_path.display())),
+            Err(e) => self.add_status_message(format!("Cannot save settings: {}", e)),
+        }
+    }
+    
+    fn search(&mut self, args: &[String]) {
+        let query = match SearchQuery::parse(args) {
+            Ok(query) => query,
+            Err(e) => {
+                self.add_status_message(format!("Search: {}", e));
+                return;
+            }
+        };
+        
+        let hits = self.channel_manager.search(&query, MAX_SEARCH_HITS);
+        let query = args.join(" ");
+        if hits.is_empty() {
+            self.add_status_message(format!("No messages match {}", query));
+            return;
+        }
+        self.search_results = Some(SearchResults { title: format!("Search: {}", query), hits, selected: 0 });
+    }
+    
+    /// Open the mentions in the results view, newest first, to jump to them like search hits
+    fn show_mentions(&mut self) {
+        if self.mentions.is_empty() {
+            self.add_status_message("No mentions yet - your nickname, /set ui.highlight_words and p tags with your pubkey count".to_string());
+            return;
+        }
+        let hits: Vec<SearchHit> = self.mentions.iter().rev().cloned().collect();
+        self.search_results = Some(SearchResults { title: "Mentions".to_string(), hits, selected: 0 });
+    }
+    
+    /// Our nickname or a highlight word, as a whole word and ignoring case
+    ///
+    /// The nickname also catches the nick#abcd form others see us as.
+    fn mentions_us(&self, content: &str) -> bool {
+        let content = content.to_lowercase();
+        std::iter::once(&self.identity.nickname)
+            .chain(&self.config.ui.highlight_words)
+            .any(|word| contains_word(&content, &word.to_lowercase()))
+    }
+    
+    /// Up/Down/PageUp/PageDown pick a hit, Enter jumps to it, Esc closes the results
+    ///
+    /// Returns false for keys the view doesn't use.
+    fn handle_search_key(&mut self, code: KeyCode) -> bool {
+        let Some(results) = &mut self.search_results else {
+            return false;
+        };
+        let last = results.hits.len().saturating_sub(1);
+        
+        match code {
+            KeyCode::Up => results.selected = results.selected.saturating_sub(1),
+            KeyCode::Down => results.selected = (results.selected + 1).min(last),
+            KeyCode::PageUp => results.selected = results.selected.saturating_sub(10),
+            KeyCode::PageDown => results.selected = (results.selected + 10).min(last),
+            KeyCode::Home => results.selected = 0,
+            KeyCode::End => results.selected = last,
+            KeyCode::Esc => self.search_results = None,
+            KeyCode::Enter => self.jump_to_search_hit(),
+            _ => return false,
+        }
+        true
+    }
+    
+    /// Switch to the selected hit's channel, scrolled so the hit is the top line
+    fn jump_to_search_hit(&mut self) {
+        let Some(hit) = self.search_results.take().and_then(|results| results.hits.get(results.selected).cloned()) else {
+            return;
+        };
+        
+        let index = self.channel_manager
+            .get_channel(&hit.channel)
+            .and_then(|channel| channel.messages.iter().position(|message| hit.is(message)));
+        let Some(index) = index else {
+            self.add_status_message(format!("That message from {} is no longer in #{}", hit.nickname, hit.channel));
+            return;
+        };
+        
+        self.set_current_channel(&hit.channel);
+        self.should_autoscroll = false;
+        self.scroll_offset = index;
+    }
+    
     fn clear_current_channel(&mut self) {
         if let Some(channel_name) = &self.current_channel {
             let was_cleared = self.channel_manager.clear_channel(channel_name);
@@ -2093,9 +3452,8 @@ impl App {
                 // Add a confirmation message
                 if channel_name == "system" {
                     self.add_status_message("🧹 System channel cleared".to_string());
-                } else if channel_name.starts_with("dm:") {
+                } else if let Some(pubkey) = channel_name.strip_prefix("dm:") {
                     // For private messages, show the nickname instead of the channel ID
-                    let pubkey = &channel_name[3..];
                     let display_name = self.private_chats.get(pubkey)
                         .map(|nick| format!("@{}", nick))
                         .unwrap_or_else(|| format!("dm:{}", &pubkey[..8]));
@@ -2144,4 +3502,14 @@ impl App {
             }
         }
     }
-}
\ No newline at end of file
+}
+
+// `word` in `text` with no letter, digit or _ right before or after it
+fn contains_word(text: &str, word: &str) -> bool {
+    let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+    !word.is_empty() && text.match_indices(word).any(|(start, _)| {
+        let before = text[..start].chars().next_back();
+        let after = text[start + word.len()..].chars().next();
+        !before.is_some_and(is_word_char) && !after.is_some_and(is_word_char)
+    })
+}
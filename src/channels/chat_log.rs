@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use super::Message;
+use crate::config::LogConfig;
+use crate::nostr::data_dir;
+
+/// Appends channel, private and system messages to irssi-style log files
+///
+/// One file per channel and day: ~/.local/share/bitchatx/logs/<channel>/<date>.log.
+/// Files are opened per line, so logs can be moved or rotated while running.
+pub struct ChatLog {
+    dir: PathBuf,
+    // log.enabled, the default for channels not switched with /log
+    enabled: bool,
+    // strftime line format with {nick}, {pubkey}, {channel} and {message} placeholders
+    format: String,
+    // Channels switched on or off with /log, overriding `enabled`
+    overrides: HashMap<String, bool>,
+    // First write failure since last asked, for the status window
+    error: Option<String>,
+}
+
+impl ChatLog {
+    pub fn new(config: &LogConfig) -> Self {
+        Self {
+            dir: data_dir().join("logs"),
+            enabled: config.enabled,
+            format: config.format.clone(),
+            overrides: HashMap::new(),
+            error: None,
+        }
+    }
+
+    /// Apply log.enabled and log.format from /set; per-channel choices are kept
+    pub fn configure(&mut self, config: &LogConfig) {
+        self.enabled = config.enabled;
+        self.format = config.format.clone();
+    }
+
+    pub fn set_channel_logging(&mut self, channel: &str, on: bool) {
+        self.overrides.insert(channel.to_string(), on);
+    }
+
+    pub fn is_logging(&self, channel: &str) -> bool {
+        self.overrides.get(channel).copied().unwrap_or(self.enabled)
+    }
+
+    /// Today's log file for a channel
+    pub fn path_for(&self, channel: &str) -> PathBuf {
+        self.file_path(channel, chrono::Local::now().date_naive())
+    }
+
+    pub fn write(&mut self, message: &Message) {
+        if message.content.is_empty() || !self.is_logging(&message.channel) {
+            return;
+        }
+
+        let timestamp = message.timestamp.with_timezone(&chrono::Local);
+        let path = self.file_path(&message.channel, timestamp.date_naive());
+        let line = self.format_line(message);
+
+        if let Err(e) = Self::append(&path, &line) {
+            // Stop rather than fail on every line (reporting the error is itself logged)
+            self.overrides.insert(message.channel.clone(), false);
+            self.error.get_or_insert_with(|| {
+                format!("Cannot write log {}: {} - stopped logging #{}", path.display(), e, message.channel)
+            });
+        }
+    }
+
+    pub fn take_error(&mut self) -> Option<String> {
+        self.error.take()
+    }
+
+    fn file_path(&self, channel: &str, date: chrono::NaiveDate) -> PathBuf {
+        // DM channels are "dm:<pubkey>", keep directory names portable
+        let directory: String = channel
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+            .collect();
+        self.dir.join(directory).join(format!("{}.log", date.format("%Y-%m-%d")))
+    }
+
+    fn format_line(&self, message: &Message) -> String {
+        // Time first, so '%' in nicknames and messages is left alone
+        let template = message.timestamp.with_timezone(&chrono::Local).format(&self.format).to_string();
+        let content = message.content.replace(['\r', '\n'], " ");
+
+        let mut line = String::with_capacity(template.len() + content.len());
+        let mut rest = template.as_str();
+        while let Some(start) = rest.find('{') {
+            line.push_str(&rest[..start]);
+            rest = &rest[start..];
+
+            let placeholder = rest.find('}').map(|end| (&rest[1..end], end));
+            let value = match placeholder.map(|(name, _)| name) {
+                Some("nick") => Some(message.nickname.as_str()),
+                Some("pubkey") => Some(message.pubkey.as_deref().unwrap_or("")),
+                Some("channel") => Some(message.channel.as_str()),
+                Some("message") => Some(content.as_str()),
+                _ => None,
+            };
+            match (value, placeholder) {
+                (Some(value), Some((_, end))) => {
+                    line.push_str(value);
+                    rest = &rest[end + 1..];
+                }
+                // Not one of ours, keep the brace as written
+                _ => {
+                    line.push('{');
+                    rest = &rest[1..];
+                }
+            }
+        }
+        line.push_str(rest);
+        line
+    }
+
+    fn append(path: &Path, line: &str) -> std::io::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        // Private messages end up here too, so keep logs to ourselves
+        let mut options = std::fs::OpenOptions::new();
+        options.append(true).create(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+
+        let mut file = options.open(path)?;
+        writeln!(file, "{}", line)
+    }
+}
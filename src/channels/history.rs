@@ -0,0 +1,225 @@
+use anyhow::{anyhow, Result};
+use rusqlite::{params, Connection, Row};
+use std::path::Path;
+
+use super::Message;
+use crate::nostr::data_dir;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS messages (
+        id INTEGER PRIMARY KEY,
+        event_id TEXT UNIQUE,
+        channel TEXT NOT NULL,
+        pubkey TEXT,
+        nickname TEXT NOT NULL,
+        content TEXT NOT NULL,
+        timestamp INTEGER NOT NULL,
+        is_own INTEGER NOT NULL,
+        is_private INTEGER NOT NULL,
+        recipient_pubkey TEXT,
+        pow_difficulty INTEGER NOT NULL,
+        relay_url TEXT
+    );
+    CREATE INDEX IF NOT EXISTS messages_channel_timestamp ON messages (channel, timestamp);
+";
+
+const COLUMNS: &str =
+    "event_id, channel, pubkey, nickname, content, timestamp, is_own, is_private, recipient_pubkey, pow_difficulty, relay_url";
+
+/// On-disk message history in ~/.local/share/bitchatx/history.db
+///
+/// Keeps what scrolled out of a channel's in-memory window, for scrollback and
+/// for restoring a channel when it is joined again. Timestamps are stored in
+/// milliseconds; events received from several relays are stored once.
+pub struct MessageStore {
+    connection: Connection,
+}
+
+impl MessageStore {
+    pub fn open_default(max_age_days: u32) -> Result<Self> {
+        Self::open(&data_dir().join("history.db"), max_age_days)
+    }
+
+    /// Open or create the store, dropping messages older than `max_age_days` (0 keeps everything)
+    pub fn open(path: &Path, max_age_days: u32) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // Private messages end up here too, so create the file for our eyes only
+        if !path.exists() {
+            crate::nostr::write_private_file(path, b"")?;
+        }
+
+        let connection = Connection::open(path)
+            .map_err(|e| anyhow!("Cannot open history {}: {}", path.display(), e))?;
+        connection.execute_batch(SCHEMA)?;
+
+        if max_age_days > 0 {
+            let cutoff = chrono::Utc::now() - chrono::Duration::days(max_age_days.into());
+            connection.execute("DELETE FROM messages WHERE timestamp < ?1", params![cutoff.timestamp_millis()])?;
+        }
+
+        Ok(Self { connection })
+    }
+
+    pub fn insert(&self, message: &Message) -> Result<()> {
+        self.connection.execute(
+            &format!("INSERT OR IGNORE INTO messages ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)", COLUMNS),
+            params![
+                message.event_id,
+                message.channel,
+                message.pubkey,
+                message.nickname,
+                message.content,
+                message.timestamp.timestamp_millis(),
+                message.is_own,
+                message.is_private,
+                message.recipient_pubkey,
+                message.pow_difficulty,
+                message.relay_url,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// The newest messages of a channel, oldest first
+    pub fn recent(&self, channel: &str, limit: usize) -> Result<Vec<Message>> {
+        self.query(
+            &format!("SELECT {} FROM messages WHERE channel = ?1 ORDER BY timestamp DESC, id DESC LIMIT ?2", COLUMNS),
+            params![channel, limit as i64],
+        )
+    }
+
+    /// Messages of a channel up to and including a time, oldest first
+    ///
+    /// Includes the boundary so messages sharing a second with the oldest one in
+    /// memory aren't skipped; the caller drops the ones it already has.
+    pub fn until(&self, channel: &str, until: chrono::DateTime<chrono::Utc>, limit: usize) -> Result<Vec<Message>> {
+        self.query(
+            &format!(
+                "SELECT {} FROM messages WHERE channel = ?1 AND timestamp <= ?2 ORDER BY timestamp DESC, id DESC LIMIT ?3",
+                COLUMNS
+            ),
+            params![channel, until.timestamp_millis(), limit as i64],
+        )
+    }
+
+    fn query(&self, sql: &str, params: impl rusqlite::Params) -> Result<Vec<Message>> {
+        let mut statement = self.connection.prepare_cached(sql)?;
+        let mut messages = statement
+            .query_map(params, Self::message_from_row)?
+            .collect::<rusqlite::Result<Vec<Message>>>()?;
+        messages.reverse();
+        Ok(messages)
+    }
+
+    fn message_from_row(row: &Row<'_>) -> rusqlite::Result<Message> {
+        let millis: i64 = row.get(5)?;
+        Ok(Message {
+            event_id: row.get(0)?,
+            channel: row.get(1)?,
+            pubkey: row.get(2)?,
+            nickname: row.get(3)?,
+            content: row.get(4)?,
+            timestamp: chrono::DateTime::from_timestamp_millis(millis).unwrap_or_default(),
+            is_own: row.get(6)?,
+            is_private: row.get(7)?,
+            recipient_pubkey: row.get(8)?,
+            pow_difficulty: row.get(9)?,
+            relay_url: row.get(10)?,
+            is_mention: false,
+            send_id: None,
+        })
+    }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use chrono::{DateTime, Duration, Utc};
+    use std::path::PathBuf;
+
+    /// A store in its own file under the temp dir, removed when dropped
+    pub(crate) struct TempStore(PathBuf);
+
+    impl TempStore {
+        pub(crate) fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("bitchatx-history-{}-{}.db", std::process::id(), name));
+            let _ = std::fs::remove_file(&path);
+            Self(path)
+        }
+
+        pub(crate) fn open(&self, max_age_days: u32) -> MessageStore {
+            MessageStore::open(&self.0, max_age_days).unwrap()
+        }
+    }
+
+    impl Drop for TempStore {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    pub(crate) fn message(event_id: &str, content: &str, timestamp: DateTime<Utc>) -> Message {
+        Message {
+            channel: "dr5reg".to_string(),
+            nickname: "alice".to_string(),
+            content: content.to_string(),
+            timestamp,
+            pubkey: Some("ab".repeat(32)),
+            is_own: false,
+            is_private: false,
+            recipient_pubkey: None,
+            pow_difficulty: 0,
+            event_id: Some(event_id.to_string()),
+            relay_url: None,
+            is_mention: false,
+            send_id: None,
+        }
+    }
+
+    pub(crate) fn contents(messages: &[Message]) -> Vec<&str> {
+        messages.iter().map(|message| message.content.as_str()).collect()
+    }
+
+    #[test]
+    fn until_includes_the_boundary() {
+        let temp = TempStore::new("until");
+        let store = temp.open(0);
+        let boundary = Utc::now();
+        store.insert(&message("1", "older", boundary - Duration::seconds(5))).unwrap();
+        store.insert(&message("2", "same time", boundary)).unwrap();
+        store.insert(&message("3", "newer", boundary + Duration::seconds(5))).unwrap();
+
+        assert_eq!(contents(&store.until("dr5reg", boundary, 10).unwrap()), ["older", "same time"]);
+        // The limit keeps the newest of them
+        assert_eq!(contents(&store.until("dr5reg", boundary, 1).unwrap()), ["same time"]);
+        assert!(store.until("u4pr", boundary, 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn stores_an_event_once() {
+        let temp = TempStore::new("dedup");
+        let store = temp.open(0);
+        let now = Utc::now();
+        store.insert(&message("1", "from relay a", now)).unwrap();
+        store.insert(&message("1", "from relay b", now)).unwrap();
+
+        assert_eq!(contents(&store.recent("dr5reg", 10).unwrap()), ["from relay a"]);
+    }
+
+    #[test]
+    fn prunes_old_messages_on_open() {
+        let temp = TempStore::new("prune");
+        let now = Utc::now();
+        {
+            let store = temp.open(0);
+            store.insert(&message("1", "last month", now - Duration::days(40))).unwrap();
+            store.insert(&message("2", "last week", now - Duration::days(7))).unwrap();
+        }
+
+        // 0 keeps everything
+        assert_eq!(contents(&temp.open(0).recent("dr5reg", 10).unwrap()), ["last month", "last week"]);
+        assert_eq!(contents(&temp.open(30).recent("dr5reg", 10).unwrap()), ["last week"]);
+    }
+}
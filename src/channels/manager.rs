@@ -1,19 +1,41 @@
 use anyhow::Result;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use tokio::sync::mpsc;
 
-use super::{Channel, Message};
+use super::{Channel, ChatLog, Message, MessageStore, SearchHit, SearchQuery};
+
+// Remember this many event ids for deduplication, well beyond what relays replay
+const MAX_SEEN_EVENTS: usize = 5000;
 
 pub struct ChannelManager {
     channels: HashMap<String, Channel>,
+    // Channel names in the order they were opened, so window numbers stay put
+    window_order: Vec<String>,
+    // Event id -> relays that delivered it, oldest ids evicted first
+    seen_events: HashMap<String, Vec<String>>,
+    seen_order: VecDeque<String>,
+    // Optional log files, written as messages are added
+    chat_log: ChatLog,
+    // Optional on-disk history for scrollback and rejoins
+    history: Option<MessageStore>,
+    history_error: Option<String>,
+    // Receivers of every message as it is added (headless mode, gateways)
+    subscribers: Vec<mpsc::UnboundedSender<Message>>,
     #[allow(dead_code)]
     message_tx: mpsc::UnboundedSender<Message>,
 }
 
 impl ChannelManager {
-    pub fn new(message_tx: mpsc::UnboundedSender<Message>) -> Self {
+    pub fn new(message_tx: mpsc::UnboundedSender<Message>, chat_log: ChatLog, history: Option<MessageStore>) -> Self {
         Self {
             channels: HashMap::new(),
+            window_order: Vec::new(),
+            seen_events: HashMap::new(),
+            seen_order: VecDeque::new(),
+            chat_log,
+            history,
+            history_error: None,
+            subscribers: Vec::new(),
             message_tx,
         }
     }
@@ -26,12 +48,14 @@ impl ChannelManager {
             // Create new joined channel
             let channel = Channel::new_joined(geohash);
             self.channels.insert(geohash.to_string(), channel);
+            self.window_order.push(geohash.to_string());
         }
         Ok(())
     }
     
     pub async fn leave_channel(&mut self, geohash: &str) -> Result<()> {
         self.channels.remove(geohash);
+        self.window_order.retain(|name| name != geohash);
         Ok(())
     }
     
@@ -43,18 +67,222 @@ impl ChannelManager {
         if !self.channels.contains_key(&channel_name) {
             let channel = Channel::new(&channel_name);
             self.channels.insert(channel_name.clone(), channel);
+            self.window_order.push(channel_name.clone());
         }
         
+        self.chat_log.write(&message);
+        self.store(&message);
+        self.subscribers.retain(|subscriber| subscriber.send(message.clone()).is_ok());
+        
         // Add message to channel
         if let Some(channel) = self.channels.get_mut(&channel_name) {
             channel.add_message(message);
         }
     }
     
+    /// Show our own message while it is still being mined, signed or sent
+    ///
+    /// Unlike add_message_sync nothing is logged, stored or passed to subscribers
+    /// yet, the message may never go out.
+    pub fn add_pending(&mut self, message: Message) {
+        let channel_name = message.channel.clone();
+        if !self.channels.contains_key(&channel_name) {
+            self.channels.insert(channel_name.clone(), Channel::new(&channel_name));
+            self.window_order.push(channel_name.clone());
+        }
+        if let Some(channel) = self.channels.get_mut(&channel_name) {
+            channel.add_message(message);
+        }
+    }
+    
+    /// A pending message went out as `event_id`: log, store and pass it on like any other
+    pub fn confirm_sent(&mut self, channel_name: &str, send_id: u64, event_id: String) {
+        let Some(message) = self.channels
+            .get_mut(channel_name)
+            .and_then(|channel| channel.messages.iter_mut().find(|m| m.send_id == Some(send_id)))
+        else {
+            return;
+        };
+        message.send_id = None;
+        message.event_id = Some(event_id);
+        let message = message.clone();
+        
+        self.chat_log.write(&message);
+        self.store(&message);
+        self.subscribers.retain(|subscriber| subscriber.send(message.clone()).is_ok());
+    }
+    
+    /// Take back a pending message that wasn't sent
+    pub fn remove_pending(&mut self, channel_name: &str, send_id: u64) {
+        if let Some(channel) = self.channels.get_mut(channel_name) {
+            channel.messages.retain(|message| message.send_id != Some(send_id));
+        }
+    }
+    
+    /// Receive a copy of every message added from now on, including own and status messages
+    pub fn subscribe(&mut self) -> mpsc::UnboundedReceiver<Message> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.subscribers.push(tx);
+        rx
+    }
+    
+    /// Save a message to history; status lines (no author) aren't kept
+    fn store(&mut self, message: &Message) {
+        if message.pubkey.is_none() {
+            return;
+        }
+        if let Some(history) = &self.history {
+            if let Err(e) = history.insert(message) {
+                // Don't fail on every message
+                self.history = None;
+                self.history_error = Some(format!("History disabled, cannot save messages: {}", e));
+            }
+        }
+    }
+    
+    pub fn has_history(&self) -> bool {
+        self.history.is_some()
+    }
+    
+    /// Fill an empty channel with its newest messages from history
+    ///
+    /// Returns how many were restored. Their events count as seen, so relays
+    /// replaying them don't show them twice.
+    pub fn restore_history(&mut self, geohash: &str, limit: usize) -> usize {
+        let Some(history) = &self.history else {
+            return 0;
+        };
+        if limit == 0 || self.channels.get(geohash).is_none_or(|channel| !channel.messages.is_empty()) {
+            return 0;
+        }
+        
+        let messages = match history.recent(geohash, limit) {
+            Ok(messages) => messages,
+            Err(e) => {
+                self.history_error = Some(format!("Cannot read history of #{}: {}", geohash, e));
+                return 0;
+            }
+        };
+        
+        let count = messages.len();
+        for message in &messages {
+            self.remember_event(message);
+        }
+        if let Some(channel) = self.channels.get_mut(geohash) {
+            channel.prepend_history(messages);
+        }
+        count
+    }
+    
+    /// Page older messages from history in front of a channel's oldest one
+    ///
+    /// Returns how many were added, 0 once history has nothing older.
+    pub fn load_older(&mut self, channel_name: &str, limit: usize) -> usize {
+        let (Some(history), Some(channel)) = (&self.history, self.channels.get_mut(channel_name)) else {
+            return 0;
+        };
+        let Some(oldest) = channel.messages.first().map(|message| message.timestamp) else {
+            return 0;
+        };
+        if channel.history_exhausted {
+            return 0;
+        }
+        
+        // History includes the oldest timestamp itself, some of those are already shown
+        let boundary: Vec<&Message> = channel.messages.iter().take_while(|m| m.timestamp == oldest).collect();
+        let stored = match history.until(channel_name, oldest, limit + boundary.len()) {
+            Ok(stored) => stored,
+            Err(e) => {
+                self.history_error = Some(format!("Cannot read history of #{}: {}", channel_name, e));
+                return 0;
+            }
+        };
+        let older: Vec<Message> = stored
+            .into_iter()
+            .filter(|stored| !boundary.iter().any(|shown| Self::same_message(shown, stored)))
+            .collect();
+        
+        if older.is_empty() {
+            channel.history_exhausted = true;
+            return 0;
+        }
+        let count = older.len();
+        channel.prepend_history(older);
+        count
+    }
+    
+    fn same_message(a: &Message, b: &Message) -> bool {
+        match (&a.event_id, &b.event_id) {
+            (Some(a), Some(b)) => a == b,
+            _ => a.timestamp == b.timestamp && a.pubkey == b.pubkey && a.content == b.content,
+        }
+    }
+    
+    /// Problems writing logs or history since last asked, for the status window
+    pub fn take_error(&mut self) -> Option<String> {
+        self.chat_log.take_error().or_else(|| self.history_error.take())
+    }
+    
+    /// Note which relay delivered the message's event
+    ///
+    /// Returns false if the event was already seen from another relay, in which
+    /// case the copy should be dropped. Messages without an event id are always new.
+    pub fn record_delivery(&mut self, message: &Message) -> bool {
+        let Some(event_id) = &message.event_id else {
+            return true;
+        };
+        
+        if let Some(relays) = self.seen_events.get_mut(event_id) {
+            if let Some(relay_url) = &message.relay_url {
+                if !relays.contains(relay_url) {
+                    relays.push(relay_url.clone());
+                }
+            }
+            return false;
+        }
+        
+        self.remember_event(message);
+        true
+    }
+    
+    fn remember_event(&mut self, message: &Message) {
+        let Some(event_id) = &message.event_id else {
+            return;
+        };
+        if self.seen_events.contains_key(event_id) {
+            return;
+        }
+        
+        self.seen_events.insert(event_id.clone(), message.relay_url.iter().cloned().collect());
+        self.seen_order.push_back(event_id.clone());
+        if self.seen_order.len() > MAX_SEEN_EVENTS {
+            if let Some(oldest) = self.seen_order.pop_front() {
+                self.seen_events.remove(&oldest);
+            }
+        }
+    }
+    
+    /// Relays that delivered an event, in order of arrival
+    pub fn event_relays(&self, event_id: &str) -> Option<&[String]> {
+        self.seen_events.get(event_id).map(|relays| relays.as_slice())
+    }
+    
+    pub fn chat_log(&self) -> &ChatLog {
+        &self.chat_log
+    }
+    
+    pub fn chat_log_mut(&mut self) -> &mut ChatLog {
+        &mut self.chat_log
+    }
+    
     pub fn get_channel(&self, geohash: &str) -> Option<&Channel> {
         self.channels.get(geohash)
     }
     
+    pub fn get_channel_mut(&mut self, geohash: &str) -> Option<&mut Channel> {
+        self.channels.get_mut(geohash)
+    }
+    
     
     pub fn list_channels(&self) -> Vec<String> {
         // Only return actually joined channels
@@ -71,6 +299,11 @@ impl ChannelManager {
         channels
     }
     
+    /// Every channel in the order it was opened (joined or first messaged)
+    pub fn list_windows(&self) -> &[String] {
+        &self.window_order
+    }
+    
     pub fn list_all_channels(&self) -> Vec<(String, bool)> {
         // Return all channels with joined status
         let mut channels: Vec<(String, bool)> = self.channels
@@ -86,6 +319,23 @@ impl ChannelManager {
     }
     
     
+    /// Messages in memory matching a query, newest first
+    pub fn search(&self, query: &SearchQuery, limit: usize) -> Vec<SearchHit> {
+        let mut hits: Vec<SearchHit> = self.channels
+            .iter()
+            .filter(|(name, _)| match &query.channel {
+                Some(channel) => *name == channel,
+                None => name.as_str() != "system",
+            })
+            .flat_map(|(_, channel)| channel.messages.iter())
+            .filter(|message| query.matches(message))
+            .map(SearchHit::new)
+            .collect();
+        hits.sort_by_key(|hit| std::cmp::Reverse(hit.timestamp));
+        hits.truncate(limit);
+        hits
+    }
+    
     pub fn get_active_user_count(&self, geohash: &str) -> usize {
         self.channels.get(geohash)
             .map(|c| c.get_participant_count())
@@ -103,4 +353,55 @@ impl ChannelManager {
             false
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channels::history::tests::{contents, message, TempStore};
+    use chrono::{Duration, Utc};
+
+    #[test]
+    fn paging_skips_messages_already_shown_at_the_boundary() {
+        let temp = TempStore::new("paging");
+        let store = temp.open(0);
+        let boundary = Utc::now();
+        store.insert(&message("1", "older", boundary - Duration::seconds(5))).unwrap();
+        store.insert(&message("2", "same time, scrolled out", boundary)).unwrap();
+        store.insert(&message("3", "same time, shown", boundary)).unwrap();
+
+        let (message_tx, _) = mpsc::unbounded_channel();
+        let chat_log = ChatLog::new(&crate::config::LogConfig::default());
+        let mut manager = ChannelManager::new(message_tx, chat_log, Some(store));
+        manager.add_message_sync(message("3", "same time, shown", boundary));
+
+        assert_eq!(manager.load_older("dr5reg", 10), 2);
+        let channel = manager.get_channel("dr5reg").unwrap();
+        assert_eq!(contents(&channel.messages), ["older", "same time, scrolled out", "same time, shown"]);
+        assert_eq!(manager.load_older("dr5reg", 10), 0);
+    }
+
+    #[test]
+    fn pending_messages_are_stored_once_sent() {
+        let temp = TempStore::new("pending");
+        let now = Utc::now();
+        let (message_tx, _) = mpsc::unbounded_channel();
+        let chat_log = ChatLog::new(&crate::config::LogConfig::default());
+        let mut manager = ChannelManager::new(message_tx, chat_log, Some(temp.open(0)));
+        let mut subscriber = manager.subscribe();
+        let pending = |send_id, content| Message { event_id: None, send_id: Some(send_id), ..message("", content, now) };
+        manager.add_pending(pending(1, "sent"));
+        manager.add_pending(pending(2, "cancelled"));
+        assert_eq!(contents(&manager.get_channel("dr5reg").unwrap().messages), ["sent", "cancelled"]);
+
+        manager.confirm_sent("dr5reg", 1, "1".to_string());
+        manager.remove_pending("dr5reg", 2);
+        let channel = manager.get_channel("dr5reg").unwrap();
+        assert_eq!(contents(&channel.messages), ["sent"]);
+        assert_eq!(channel.messages[0].event_id.as_deref(), Some("1"));
+        assert_eq!(channel.messages[0].send_id, None);
+        assert_eq!(subscriber.try_recv().unwrap().content, "sent");
+        assert!(subscriber.try_recv().is_err());
+        assert_eq!(contents(&temp.open(0).recent("dr5reg", 10).unwrap()), ["sent"]);
+    }
+}
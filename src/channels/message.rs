@@ -10,5 +10,15 @@ pub struct Message {
     pub is_own: bool,
     pub is_private: bool,
     pub recipient_pubkey: Option<String>,
+    // NIP-13 proof of work of the source event in leading zero bits
+    pub pow_difficulty: u8,
+    // Nostr event id (hex), None for local echo and system messages
+    pub event_id: Option<String>,
+    // Relay this copy was received from
+    pub relay_url: Option<String>,
+    // Addresses us: our nickname, a highlight word or a p tag with our pubkey
+    pub is_mention: bool,
+    // Local echo still being mined, signed or sent; the id its outcome comes back under
+    pub send_id: Option<u64>,
 }
 
@@ -1,5 +1,8 @@
 use std::collections::HashMap;
 
+// Messages kept in memory per channel; older ones are paged in from history
+const MAX_MESSAGES: usize = 250;
+
 #[derive(Debug, Clone)]
 pub struct Participant {
     pub nickname: String,
@@ -8,11 +11,23 @@ pub struct Participant {
     pub message_count: usize,
 }
 
+pub use chat_log::ChatLog;
+pub use history::MessageStore;
 pub use manager::ChannelManager;
 pub use message::Message;
+pub use search::{SearchHit, SearchQuery};
 
+mod chat_log;
+mod history;
 mod manager;
 mod message;
+mod search;
+
+/// Basic geohash validation: 1-12 characters of the geohash base32 alphabet
+pub fn is_valid_geohash(geohash: &str) -> bool {
+    !geohash.is_empty() && geohash.len() <= 12 &&
+    geohash.chars().all(|c| "0123456789bcdefghjkmnpqrstuvwxyz".contains(c))
+}
 
 #[derive(Debug, Clone)]
 pub struct Channel {
@@ -24,6 +39,14 @@ pub struct Channel {
     pub participants: HashMap<String, Participant>,
     pub last_activity: chrono::DateTime<chrono::Utc>,
     pub is_joined: bool,
+    // Older messages paged in from history while scrolled back, kept on top of the cap
+    pub scrollback: usize,
+    // History has nothing older than the first message
+    pub history_exhausted: bool,
+    // Messages that arrived while another channel was open, and how many of them
+    // were mentions or private messages
+    pub unread: usize,
+    pub unread_mentions: usize,
 }
 
 impl Channel {
@@ -35,6 +58,10 @@ impl Channel {
             participants: HashMap::new(),
             last_activity: chrono::Utc::now(),
             is_joined: false,
+            scrollback: 0,
+            history_exhausted: false,
+            unread: 0,
+            unread_mentions: 0,
         }
     }
     
@@ -46,9 +73,18 @@ impl Channel {
             participants: HashMap::new(),
             last_activity: chrono::Utc::now(),
             is_joined: true,
+            scrollback: 0,
+            history_exhausted: false,
+            unread: 0,
+            unread_mentions: 0,
         }
     }
     
+    pub fn mark_read(&mut self) {
+        self.unread = 0;
+        self.unread_mentions = 0;
+    }
+    
     pub fn add_message(&mut self, message: Message) {
         let now = chrono::Utc::now();
         
@@ -75,7 +111,7 @@ impl Channel {
         // Insert message in timestamp order (newer messages at the end)
         // For performance: assume most messages are in chronological order
         // Just append to end and only sort if timestamp is out of order
-        if self.messages.last().map_or(true, |last| last.timestamp <= message.timestamp) {
+        if self.messages.last().is_none_or(|last| last.timestamp <= message.timestamp) {
             // Fast path: message is in order, just append
             self.messages.push(message);
         } else {
@@ -87,10 +123,11 @@ impl Channel {
         }
         self.last_activity = now;
         
-        // Keep only last 250 messages per channel (reduced for better performance)
-        if self.messages.len() > 250 {
+        // Keep only last 250 messages per channel (reduced for better performance),
+        // plus whatever was paged in from history while scrolled back
+        if self.messages.len() > MAX_MESSAGES + self.scrollback {
             // Remove oldest messages in batches for better performance
-            let remove_count = self.messages.len() - 250;
+            let remove_count = self.messages.len() - MAX_MESSAGES - self.scrollback;
             self.messages.drain(0..remove_count);
         }
         
@@ -100,6 +137,24 @@ impl Channel {
     }
     
     
+    /// Put messages from history in front of the ones in memory
+    ///
+    /// They must be older; participants aren't touched, history says nothing about who is around now.
+    pub fn prepend_history(&mut self, older: Vec<Message>) {
+        self.messages.splice(0..0, older);
+        self.scrollback = self.messages.len().saturating_sub(MAX_MESSAGES);
+    }
+    
+    /// Drop paged-in history once the user is back at the bottom
+    pub fn release_scrollback(&mut self) {
+        self.scrollback = 0;
+        self.history_exhausted = false;
+        if self.messages.len() > MAX_MESSAGES {
+            let remove_count = self.messages.len() - MAX_MESSAGES;
+            self.messages.drain(0..remove_count);
+        }
+    }
+    
     pub fn get_participant_count(&self) -> usize {
         self.participants.len()
     }
@@ -109,7 +164,7 @@ impl Channel {
     pub fn get_active_participants(&self) -> Vec<&Participant> {
         let mut participants: Vec<&Participant> = self.participants.values().collect();
         // Sort by last activity (most recent first)
-        participants.sort_by(|a, b| b.last_seen.cmp(&a.last_seen));
+        participants.sort_by_key(|p| std::cmp::Reverse(p.last_seen));
         participants
     }
     
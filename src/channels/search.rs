@@ -0,0 +1,191 @@
+use anyhow::{anyhow, Result};
+use regex::{Regex, RegexBuilder};
+
+use super::Message;
+
+/// A parsed `/search <text|/regex/> [#channel] [from:nick]`
+pub struct SearchQuery {
+    matcher: Matcher,
+    /// Only this channel (without '#'); the system channel is skipped otherwise
+    pub channel: Option<String>,
+    /// Only messages from this nickname, optionally with its #pubkey suffix
+    from: Option<String>,
+}
+
+enum Matcher {
+    /// Case-insensitive substring
+    Text(String),
+    Regex(Regex),
+}
+
+/// A message that matched, with enough to find it again in its channel
+#[derive(Debug, Clone)]
+pub struct SearchHit {
+    pub channel: String,
+    pub nickname: String,
+    pub pubkey: Option<String>,
+    pub content: String,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub event_id: Option<String>,
+}
+
+impl SearchQuery {
+    /// Build a query from /search arguments; text wrapped in slashes is a regex
+    pub fn parse(args: &[String]) -> Result<Self> {
+        let mut channel = None;
+        let mut from = None;
+        let mut words = Vec::new();
+
+        for arg in args {
+            if let Some(name) = arg.strip_prefix('#').filter(|name| !name.is_empty()) {
+                channel = Some(name.to_lowercase());
+            } else if let Some(nick) = arg.strip_prefix("from:").filter(|nick| !nick.is_empty()) {
+                from = Some(nick.trim_start_matches('@').to_lowercase());
+            } else {
+                words.push(arg.as_str());
+            }
+        }
+
+        let text = words.join(" ");
+        let matcher = match text.strip_prefix('/').and_then(|rest| rest.strip_suffix('/')) {
+            Some(pattern) if !pattern.is_empty() => Matcher::Regex(
+                RegexBuilder::new(pattern)
+                    .case_insensitive(true)
+                    .build()
+                    .map_err(|e| anyhow!("Invalid regex: {}", e))?,
+            ),
+            _ => Matcher::Text(text.to_lowercase()),
+        };
+
+        if matches!(&matcher, Matcher::Text(text) if text.is_empty()) && from.is_none() {
+            return Err(anyhow!("Nothing to search for"));
+        }
+
+        Ok(Self { matcher, channel, from })
+    }
+
+    pub fn matches(&self, message: &Message) -> bool {
+        if let Some(from) = &self.from {
+            if !Self::is_from(message, from) {
+                return false;
+            }
+        }
+
+        match &self.matcher {
+            Matcher::Text(text) => text.is_empty() || message.content.to_lowercase().contains(text),
+            Matcher::Regex(regex) => regex.is_match(&message.content),
+        }
+    }
+
+    // nick, or nick#abcd with the last four hex digits of the pubkey like the chat shows
+    fn is_from(message: &Message, from: &str) -> bool {
+        let (nick, suffix) = match from.split_once('#') {
+            Some((nick, suffix)) => (nick, Some(suffix)),
+            None => (from, None),
+        };
+        if message.nickname.to_lowercase() != nick {
+            return false;
+        }
+        match (suffix, &message.pubkey) {
+            (None, _) => true,
+            (Some(suffix), Some(pubkey)) => pubkey.to_lowercase().ends_with(suffix),
+            (Some(_), None) => false,
+        }
+    }
+}
+
+impl SearchHit {
+    pub fn new(message: &Message) -> Self {
+        Self {
+            channel: message.channel.clone(),
+            nickname: message.nickname.clone(),
+            pubkey: message.pubkey.clone(),
+            content: message.content.clone(),
+            timestamp: message.timestamp,
+            event_id: message.event_id.clone(),
+        }
+    }
+
+    /// Whether this hit is the given message
+    pub fn is(&self, message: &Message) -> bool {
+        match (&self.event_id, &message.event_id) {
+            (Some(a), Some(b)) => a == b,
+            _ => self.timestamp == message.timestamp && self.pubkey == message.pubkey && self.content == message.content,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<SearchQuery> {
+        SearchQuery::parse(&args.split_whitespace().map(String::from).collect::<Vec<_>>())
+    }
+
+    fn message(nickname: &str, pubkey: Option<&str>, content: &str) -> Message {
+        Message {
+            channel: "dr5reg".to_string(),
+            nickname: nickname.to_string(),
+            content: content.to_string(),
+            timestamp: chrono::Utc::now(),
+            pubkey: pubkey.map(String::from),
+            is_own: false,
+            is_private: false,
+            recipient_pubkey: None,
+            pow_difficulty: 0,
+            event_id: None,
+            relay_url: None,
+            is_mention: false,
+            send_id: None,
+        }
+    }
+
+    #[test]
+    fn text_matches_ignoring_case() {
+        let query = parse("Lunch Time").unwrap();
+        assert!(query.matches(&message("alice", None, "is it LUNCH TIME yet?")));
+        assert!(!query.matches(&message("alice", None, "lunch at noon")));
+        assert_eq!(query.channel, None);
+    }
+
+    #[test]
+    fn slashes_make_a_regex() {
+        let query = parse("/lunch|dinner/").unwrap();
+        assert!(query.matches(&message("alice", None, "Dinner at 8")));
+        assert!(!query.matches(&message("alice", None, "breakfast")));
+        assert!(parse("/(unclosed/").is_err());
+        // A lone slash or an empty pattern is plain text
+        assert!(parse("/").unwrap().matches(&message("alice", None, "either/or")));
+        assert!(parse("//").unwrap().matches(&message("alice", None, "https://example.com")));
+    }
+
+    #[test]
+    fn picks_out_channel_and_sender() {
+        let query = parse("hello #DR5REG from:@Alice").unwrap();
+        assert_eq!(query.channel.as_deref(), Some("dr5reg"));
+        assert!(query.matches(&message("alice", None, "hello")));
+        assert!(!query.matches(&message("bob", None, "hello")));
+        // A bare `#` or `from:` is search text
+        assert!(parse("# from:").unwrap().matches(&message("bob", None, "# from:")));
+    }
+
+    #[test]
+    fn from_checks_the_pubkey_suffix() {
+        let pubkey = format!("{}1a2b", "0".repeat(60));
+        let alice = message("Alice", Some(&pubkey), "hi");
+        assert!(SearchQuery::is_from(&alice, "alice"));
+        assert!(SearchQuery::is_from(&alice, "alice#1a2b"));
+        assert!(!SearchQuery::is_from(&alice, "alice#ffff"));
+        assert!(!SearchQuery::is_from(&message("alice", None, "hi"), "alice#1a2b"));
+        assert!(!SearchQuery::is_from(&alice, "bob"));
+    }
+
+    #[test]
+    fn needs_text_or_a_sender() {
+        assert!(parse("").is_err());
+        assert!(parse("#dr5reg").is_err());
+        // A sender alone lists everything they said
+        assert!(parse("from:alice").unwrap().matches(&message("alice", None, "anything")));
+    }
+}
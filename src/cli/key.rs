@@ -0,0 +1,97 @@
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgMatches, Command};
+use nostr::nips::nip49::KeySecurity;
+use nostr_sdk::prelude::*;
+
+use super::{prompt_new_passphrase, prompt_secret};
+use crate::nostr::Keystore;
+
+/// `bitchatx key ...` subcommand definition
+pub fn command() -> Command {
+    let force = Arg::new("force")
+        .long("force")
+        .action(clap::ArgAction::SetTrue)
+        .help("Replace an existing keystore");
+    
+    Command::new("key")
+        .about("Manage the encrypted NIP-49 keystore")
+        .subcommand_required(true)
+        .subcommand(
+            Command::new("generate")
+                .about("Generate a new key and store it encrypted")
+                .arg(force.clone())
+        )
+        .subcommand(
+            Command::new("import")
+                .about("Encrypt an existing nsec or ncryptsec into the keystore (prompted, never echoed)")
+                .arg(force)
+        )
+        .subcommand(
+            Command::new("export")
+                .about("Print the stored ncryptsec")
+                .arg(
+                    Arg::new("nsec")
+                        .long("nsec")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Decrypt and print the plain nsec instead - WARNING: handle with care!")
+                )
+        )
+}
+
+/// Run a `bitchatx key ...` subcommand
+pub async fn run(matches: &ArgMatches) -> Result<()> {
+    let keystore = Keystore::open_default();
+    
+    match matches.subcommand() {
+        Some(("generate", args)) => {
+            keystore.ensure_writable(args.get_flag("force"))?;
+            let keys = Keys::generate();
+            let passphrase = prompt_new_passphrase()?;
+            keystore.save(keys.secret_key()?, &passphrase, KeySecurity::Medium, true)?;
+            
+            println!("Generated new key {}", keys.public_key().to_bech32()?);
+            println!("Saved to {}", keystore.path().display());
+        }
+        Some(("import", args)) => {
+            keystore.ensure_writable(args.get_flag("force"))?;
+            let input = prompt_secret("nsec or ncryptsec to import: ")?;
+            let input = input.trim();
+            
+            // An ncryptsec needs its own passphrase before it can be re-encrypted
+            let (secret_key, key_security) = if input.starts_with("ncryptsec") {
+                let encrypted = nostr::nips::nip49::EncryptedSecretKey::from_bech32(input)
+                    .map_err(|_| anyhow!("Invalid ncryptsec format"))?;
+                let key_security = encrypted.key_security();
+                let passphrase = prompt_secret("Passphrase of the ncryptsec: ")?;
+                let secret_key = encrypted
+                    .to_secret_key(passphrase)
+                    .map_err(|_| anyhow!("Wrong passphrase"))?;
+                (secret_key, key_security)
+            } else {
+                let secret_key = SecretKey::from_bech32(input)
+                    .map_err(|_| anyhow!("Invalid nsec format"))?;
+                // The key existed in plaintext somewhere before this import
+                (secret_key, KeySecurity::Weak)
+            };
+            
+            let passphrase = prompt_new_passphrase()?;
+            keystore.save(&secret_key, &passphrase, key_security, true)?;
+            
+            let keys = Keys::new(secret_key);
+            println!("Imported key {}", keys.public_key().to_bech32()?);
+            println!("Saved to {}", keystore.path().display());
+        }
+        Some(("export", args)) => {
+            if args.get_flag("nsec") {
+                let passphrase = prompt_secret("Keystore passphrase: ")?;
+                let secret_key = keystore.unlock(&passphrase)?;
+                println!("{}", secret_key.to_bech32()?);
+            } else {
+                println!("{}", keystore.load()?.to_bech32()?);
+            }
+        }
+        _ => unreachable!("clap requires a key subcommand"),
+    }
+    
+    Ok(())
+}
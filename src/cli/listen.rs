@@ -0,0 +1,143 @@
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgMatches, Command};
+use std::time::Duration;
+
+use crate::app::App;
+use crate::channels::{is_valid_geohash, Message};
+
+/// `bitchatx tail ...` subcommand definition
+pub fn tail_command() -> Command {
+    Command::new("tail")
+        .about("Print a channel's latest messages, then follow it until Ctrl+C")
+        .arg(
+            Arg::new("channel")
+                .value_name("GEOHASH")
+                .required(true)
+                .help("Channel to follow")
+        )
+        .arg(
+            Arg::new("lines")
+                .short('n')
+                .long("lines")
+                .value_name("COUNT")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("10")
+                .help("Earlier messages to print first, from history")
+        )
+}
+
+/// `bitchatx listen ...` subcommand definition
+pub fn listen_command() -> Command {
+    Command::new("listen")
+        .about("Print incoming channel and private messages until Ctrl+C")
+        .arg(
+            Arg::new("channels")
+                .value_name("GEOHASH")
+                .num_args(0..)
+                .help("Channels to join besides auto_join from the config")
+        )
+        .arg(
+            Arg::new("json")
+                .long("json")
+                .action(clap::ArgAction::SetTrue)
+                .help("One JSON object per message, like --headless")
+        )
+}
+
+/// The geohash argument of `tail`, for joining it on startup
+pub fn tail_channel(matches: &ArgMatches) -> Result<String> {
+    let channel = matches
+        .get_one::<String>("channel")
+        .map(|channel| channel.trim_start_matches('#').to_lowercase())
+        .unwrap_or_default();
+    if !is_valid_geohash(&channel) {
+        return Err(anyhow!("Invalid geohash format: {}", channel));
+    }
+    Ok(channel)
+}
+
+/// Follow the app's current channel (joined by main from the `tail` argument)
+pub async fn tail(app: &mut App, matches: &ArgMatches) -> Result<()> {
+    let channel = tail_channel(matches)?;
+    let lines = matches.get_one::<usize>("lines").copied().unwrap_or(10);
+
+    if let Some(joined) = app.channel_manager.get_channel(&channel) {
+        let start = joined.messages.len().saturating_sub(lines);
+        for message in &joined.messages[start..] {
+            println!("{}", format_line(app, message, false));
+        }
+    }
+
+    follow(app, |message| message.channel == channel, |app, message| {
+        println!("{}", format_line(app, message, false));
+        Ok(())
+    })
+    .await
+}
+
+pub async fn listen(app: &mut App, matches: &ArgMatches) -> Result<()> {
+    let channels: Vec<String> = matches
+        .get_many::<String>("channels")
+        .into_iter()
+        .flatten()
+        .map(|channel| channel.trim_start_matches('#').to_lowercase())
+        .collect();
+    if let Some(invalid) = channels.iter().find(|channel| !is_valid_geohash(channel)) {
+        return Err(anyhow!("Invalid geohash format: {}", invalid));
+    }
+    for channel in &channels {
+        app.join_channel(channel).await?;
+    }
+
+    let json = matches.get_flag("json");
+    follow(app, |_| true, |app, message| {
+        if json {
+            println!("{}", serde_json::to_string(message)?);
+        } else {
+            println!("{}", format_line(app, message, true));
+        }
+        Ok(())
+    })
+    .await
+}
+
+/// Print messages from others that pass `filter` until Ctrl+C
+///
+/// Status lines are left out, they are about the session rather than the channel.
+async fn follow(
+    app: &mut App,
+    filter: impl Fn(&Message) -> bool,
+    mut print: impl FnMut(&App, &Message) -> Result<()>,
+) -> Result<()> {
+    let mut messages = app.channel_manager.subscribe();
+    let mut ticker = tokio::time::interval(Duration::from_millis(app.config.ui.tick_rate_ms));
+
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => app.on_tick().await?,
+            _ = tokio::signal::ctrl_c() => break,
+        }
+
+        while let Ok(message) = messages.try_recv() {
+            if message.pubkey.is_some() && !message.is_own && filter(&message) {
+                print(app, &message)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// [12:34:56] <nick#abcd> text, with #channel in front when following several
+fn format_line(app: &App, message: &Message, with_channel: bool) -> String {
+    let time = message.timestamp.with_timezone(&chrono::Local).format(&app.config.ui.timestamp_format);
+    let nick = app.format_display_nickname(&message.nickname, &message.pubkey);
+
+    if message.is_private {
+        format!("[{}] *{}* {}", time, nick, message.content)
+    } else if with_channel {
+        format!("[{}] #{} <{}> {}", time, message.channel, nick, message.content)
+    } else {
+        format!("[{}] <{}> {}", time, nick, message.content)
+    }
+}
@@ -0,0 +1,24 @@
+use anyhow::{anyhow, Result};
+
+pub mod key;
+pub mod listen;
+pub mod send;
+
+/// Prompt for a secret on the terminal without echoing it
+pub fn prompt_secret(prompt: &str) -> Result<String> {
+    let secret = rpassword::prompt_password(prompt)?;
+    if secret.is_empty() {
+        return Err(anyhow!("Nothing entered"));
+    }
+    Ok(secret)
+}
+
+/// Prompt for a new passphrase twice and make sure both entries match
+pub fn prompt_new_passphrase() -> Result<String> {
+    let passphrase = prompt_secret("New keystore passphrase: ")?;
+    let confirmation = prompt_secret("Repeat passphrase: ")?;
+    if passphrase != confirmation {
+        return Err(anyhow!("Passphrases do not match"));
+    }
+    Ok(passphrase)
+}
@@ -0,0 +1,80 @@
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgMatches, Command};
+use std::io::Read;
+use tokio::sync::mpsc;
+
+use crate::channels::is_valid_geohash;
+use crate::config::Config;
+use crate::nostr::{Identity, NostrClient};
+
+/// `bitchatx send ...` subcommand definition
+pub fn command() -> Command {
+    Command::new("send")
+        .about("Publish one message to a geohash channel and exit once relays confirm it")
+        .arg(
+            Arg::new("channel")
+                .short('c')
+                .long("channel")
+                .value_name("GEOHASH")
+                .required(true)
+                .help("Channel to send to")
+        )
+        .arg(
+            Arg::new("nick")
+                .long("nick")
+                .value_name("NICKNAME")
+                .help("Nickname to send as (default: the config nickname or a random one)")
+        )
+        .arg(
+            Arg::new("text")
+                .value_name("TEXT")
+                .required(true)
+                .help("Message to send, - reads it from stdin")
+        )
+}
+
+/// Send the message and report how many relays accepted it
+///
+/// Fails when none did, so scripts can check the exit status.
+pub async fn run(matches: &ArgMatches, identity: Identity, config: &Config, pow_difficulty: u8) -> Result<()> {
+    let channel = matches
+        .get_one::<String>("channel")
+        .map(|channel| channel.trim_start_matches('#').to_lowercase())
+        .unwrap_or_default();
+    if !is_valid_geohash(&channel) {
+        return Err(anyhow!("Invalid geohash format: {}", channel));
+    }
+
+    let text = match matches.get_one::<String>("text").map(String::as_str) {
+        Some("-") => {
+            let mut text = String::new();
+            std::io::stdin().read_to_string(&mut text)?;
+            text.trim_end().to_string()
+        }
+        text => text.unwrap_or_default().to_string(),
+    };
+    if text.trim().is_empty() {
+        return Err(anyhow!("Nothing to send"));
+    }
+
+    // Same nickname the UI would use for this identity
+    let nickname = match (matches.get_one::<String>("nick"), &config.nickname) {
+        (Some(nick), _) => nick.clone(),
+        (None, Some(nick)) if identity.is_ephemeral => nick.clone(),
+        _ => identity.nickname.clone(),
+    };
+
+    // Nothing is received, the client only publishes
+    let (message_tx, _) = mpsc::unbounded_channel();
+    let (status_tx, _) = mpsc::unbounded_channel();
+    let mut client = NostrClient::new(&identity, &config.relays, &config.removed_relays, message_tx, status_tx).await?;
+    client.set_pow_difficulty(pow_difficulty);
+
+    let (accepted, total) = client.publish_message(&channel, &text, &nickname).await?;
+    if accepted == 0 {
+        return Err(anyhow!("No relay accepted the message ({} tried)", total));
+    }
+
+    println!("Sent to #{} as {} - accepted by {} of {} relays", channel, nickname, accepted, total);
+    Ok(())
+}
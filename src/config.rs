@@ -0,0 +1,411 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::nostr::{config_dir, normalize_relay_url, DEFAULT_RELAYS};
+
+// Redrawing faster than this only burns CPU, slower makes typing feel laggy
+const MIN_TICK_RATE_MS: u64 = 20;
+const MAX_TICK_RATE_MS: u64 = 1000;
+
+// Below these the spam filter drops ordinary chat: 0 messages a minute, the
+// first repeat of a message, or any clock skew at all
+const MIN_MESSAGES_PER_MINUTE: u32 = 1;
+const MIN_DUPLICATE_THRESHOLD: u32 = 2;
+const MIN_FUTURE_TIME_SECONDS: u64 = 10;
+const MIN_PAST_TIME_HOURS: u64 = 1;
+// Above these chrono's durations overflow
+const MAX_FUTURE_TIME_SECONDS: u64 = 365 * 24 * 60 * 60;
+const MAX_PAST_TIME_HOURS: u64 = 365 * 24;
+
+/// Settings read from ~/.config/bitchatx/config.toml
+///
+/// Every field has a default, so a missing file or a partial one is fine.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Nickname for ephemeral identities instead of a random one
+    pub nickname: Option<String>,
+    /// Geohash channels joined on startup
+    pub auto_join: Vec<String>,
+    /// Relays connected on startup, before any georelays; /relay add and remove edit it
+    pub relays: Vec<String>,
+    /// Relays dropped with /relay remove that are never connected to as georelays
+    pub removed_relays: Vec<String>,
+    pub spam: SpamConfig,
+    pub pow: PowConfig,
+    pub ui: UiConfig,
+    pub dm: DmConfig,
+    pub log: LogConfig,
+    pub history: HistoryConfig,
+    pub control: ControlConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpamConfig {
+    pub max_messages_per_minute: u32,
+    /// Identical messages from one user before they are muted
+    pub duplicate_message_threshold: u32,
+    pub max_future_time_seconds: u64,
+    pub max_past_time_hours: u64,
+    /// Required NIP-13 leading zero bits for channel messages (0 = off)
+    pub min_pow_difficulty: u8,
+    pub keywords: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PowConfig {
+    /// NIP-13 difficulty mined into outgoing channel messages (0 = off)
+    pub difficulty: u8,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UiConfig {
+    /// How often the screen is redrawn and incoming messages are processed
+    pub tick_rate_ms: u64,
+    pub show_logo: bool,
+    /// chrono format string for message timestamps
+    pub timestamp_format: String,
+    /// Words that mark a message as a mention, like our nickname does
+    pub highlight_words: Vec<String>,
+    /// Check the NIP-05 identifier of everyone who speaks, not only on /whois
+    ///
+    /// Off by default: the lookups go to domains picked by the senders, which
+    /// learn our IP address.
+    pub verify_nip05: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DmConfig {
+    /// Write private messages to people who haven't messaged us yet as bitchat
+    /// packets (for bitchat Android/iOS) instead of plain NIP-17 text
+    pub bitchat_format: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogConfig {
+    /// Log every channel to ~/.local/share/bitchatx/logs (/log on|off overrides per channel)
+    pub enabled: bool,
+    /// chrono format string for log lines, with {nick}, {pubkey}, {channel} and {message}
+    pub format: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistoryConfig {
+    /// Keep messages in ~/.local/share/bitchatx/history.db for scrollback and rejoins
+    pub enabled: bool,
+    /// Messages put back into a channel when it is joined
+    pub restore_messages: usize,
+    /// Delete stored messages older than this on startup (0 = keep forever)
+    pub max_age_days: u32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ControlConfig {
+    /// Serve the JSON-RPC control API on $XDG_RUNTIME_DIR/bitchatx.sock
+    pub enabled: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            nickname: None,
+            auto_join: Vec::new(),
+            relays: DEFAULT_RELAYS.iter().map(|relay| relay.to_string()).collect(),
+            removed_relays: Vec::new(),
+            spam: SpamConfig::default(),
+            pow: PowConfig::default(),
+            ui: UiConfig::default(),
+            dm: DmConfig::default(),
+            log: LogConfig::default(),
+            history: HistoryConfig::default(),
+            control: ControlConfig::default(),
+        }
+    }
+}
+
+impl Default for SpamConfig {
+    fn default() -> Self {
+        Self {
+            max_messages_per_minute: 15, // Allow up to 15 messages per minute
+            duplicate_message_threshold: 3, // Mute after 3 identical messages
+            max_future_time_seconds: 300, // Allow up to 5 minutes into the future
+            max_past_time_hours: 24, // Allow up to 24 hours into the past
+            min_pow_difficulty: 0, // No proof of work required by default
+            keywords: [
+                "🚀🚀🚀",
+                "CLICK HERE",
+                "FREE MONEY",
+                "telegram.me",
+                "bit.ly",
+                "JOIN NOW",
+                "LIMITED TIME",
+                "EARN $$$",
+                "CRYPTO PUMP",
+                "🎰🎰🎰",
+            ]
+            .iter()
+            .map(|keyword| keyword.to_string())
+            .collect(),
+        }
+    }
+}
+
+impl Default for UiConfig {
+    fn default() -> Self {
+        Self {
+            tick_rate_ms: 100,
+            show_logo: true,
+            timestamp_format: "%H:%M:%S".to_string(),
+            highlight_words: Vec::new(),
+            verify_nip05: false,
+        }
+    }
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            format: "%H:%M:%S <{nick}> {message}".to_string(),
+        }
+    }
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            restore_messages: 100,
+            max_age_days: 30,
+        }
+    }
+}
+
+/// Keys accepted by /set, in the order /set lists them
+pub const SETTINGS: &[&str] = &[
+    "nickname",
+    "auto_join",
+    "relays",
+    "removed_relays",
+    "spam.max_messages_per_minute",
+    "spam.duplicate_message_threshold",
+    "spam.max_future_time_seconds",
+    "spam.max_past_time_hours",
+    "spam.min_pow_difficulty",
+    "spam.keywords",
+    "pow.difficulty",
+    "ui.tick_rate_ms",
+    "ui.show_logo",
+    "ui.timestamp_format",
+    "ui.highlight_words",
+    "ui.verify_nip05",
+    "dm.bitchat_format",
+    "log.enabled",
+    "log.format",
+    "history.enabled",
+    "history.restore_messages",
+    "history.max_age_days",
+    "control.enabled",
+];
+
+impl Config {
+    /// ~/.config/bitchatx/config.toml
+    pub fn default_path() -> PathBuf {
+        config_dir().join("config.toml")
+    }
+
+    /// Read the config file, or the defaults if it doesn't exist yet
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(anyhow!("Cannot read config {}: {}", path.display(), e)),
+        };
+        let mut config: Self = toml::from_str(&contents)
+            .map_err(|e| anyhow!("Invalid config {}: {}", path.display(), e))?;
+        config.relays = normalize_relays(&config.relays)
+            .map_err(|e| anyhow!("Invalid config {}: {}", path.display(), e))?;
+        config.removed_relays = normalize_relays(&config.removed_relays)
+            .map_err(|e| anyhow!("Invalid config {}: {}", path.display(), e))?;
+        config.validate()
+            .map_err(|e| anyhow!("Invalid config {}: {}", path.display(), e))?;
+        Ok(config)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Write `relays` and `removed_relays` to the file, keeping the other settings
+    /// there as they are (unsaved /set changes stay unsaved)
+    pub fn save_relays(&self, path: &Path) -> Result<()> {
+        let mut saved = Self::load(path)?;
+        saved.relays = self.relays.clone();
+        saved.removed_relays = self.removed_relays.clone();
+        saved.save(path)
+    }
+
+    /// Current value of a /set key, formatted the way /set accepts it
+    pub fn get(&self, key: &str) -> Option<String> {
+        Some(match key {
+            "nickname" => self.nickname.clone().unwrap_or_default(),
+            "auto_join" => self.auto_join.join(","),
+            "relays" => self.relays.join(","),
+            "removed_relays" => self.removed_relays.join(","),
+            "spam.max_messages_per_minute" => self.spam.max_messages_per_minute.to_string(),
+            "spam.duplicate_message_threshold" => self.spam.duplicate_message_threshold.to_string(),
+            "spam.max_future_time_seconds" => self.spam.max_future_time_seconds.to_string(),
+            "spam.max_past_time_hours" => self.spam.max_past_time_hours.to_string(),
+            "spam.min_pow_difficulty" => self.spam.min_pow_difficulty.to_string(),
+            "spam.keywords" => self.spam.keywords.join(","),
+            "pow.difficulty" => self.pow.difficulty.to_string(),
+            "ui.tick_rate_ms" => self.ui.tick_rate_ms.to_string(),
+            "ui.show_logo" => self.ui.show_logo.to_string(),
+            "ui.timestamp_format" => self.ui.timestamp_format.clone(),
+            "ui.highlight_words" => self.ui.highlight_words.join(","),
+            "ui.verify_nip05" => self.ui.verify_nip05.to_string(),
+            "dm.bitchat_format" => self.dm.bitchat_format.to_string(),
+            "log.enabled" => self.log.enabled.to_string(),
+            "log.format" => self.log.format.clone(),
+            "history.enabled" => self.history.enabled.to_string(),
+            "history.restore_messages" => self.history.restore_messages.to_string(),
+            "history.max_age_days" => self.history.max_age_days.to_string(),
+            "control.enabled" => self.control.enabled.to_string(),
+            _ => return None,
+        })
+    }
+
+    /// Change a setting from /set; lists are comma separated
+    ///
+    /// The config is left untouched if the value is invalid.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        let mut updated = self.clone();
+        match key {
+            "nickname" => updated.nickname = Some(value.trim()).filter(|nick| !nick.is_empty()).map(str::to_string),
+            "auto_join" => updated.auto_join = parse_list(value).into_iter().map(|c| c.trim_start_matches('#').to_lowercase()).collect(),
+            "relays" => updated.relays = normalize_relays(&parse_list(value))?,
+            "removed_relays" => updated.removed_relays = normalize_relays(&parse_list(value))?,
+            "spam.max_messages_per_minute" => updated.spam.max_messages_per_minute = parse_number(key, value)?,
+            "spam.duplicate_message_threshold" => updated.spam.duplicate_message_threshold = parse_number(key, value)?,
+            "spam.max_future_time_seconds" => updated.spam.max_future_time_seconds = parse_number(key, value)?,
+            "spam.max_past_time_hours" => updated.spam.max_past_time_hours = parse_number(key, value)?,
+            "spam.min_pow_difficulty" => updated.spam.min_pow_difficulty = parse_number(key, value)?,
+            "spam.keywords" => updated.spam.keywords = parse_list(value),
+            "pow.difficulty" => updated.pow.difficulty = parse_number(key, value)?,
+            "ui.tick_rate_ms" => updated.ui.tick_rate_ms = parse_number(key, value)?,
+            "ui.show_logo" => updated.ui.show_logo = parse_bool(key, value)?,
+            "ui.timestamp_format" => updated.ui.timestamp_format = value.to_string(),
+            "ui.highlight_words" => updated.ui.highlight_words = parse_list(value),
+            "ui.verify_nip05" => updated.ui.verify_nip05 = parse_bool(key, value)?,
+            "dm.bitchat_format" => updated.dm.bitchat_format = parse_bool(key, value)?,
+            "log.enabled" => updated.log.enabled = parse_bool(key, value)?,
+            "log.format" => updated.log.format = value.to_string(),
+            "history.enabled" => updated.history.enabled = parse_bool(key, value)?,
+            "history.restore_messages" => updated.history.restore_messages = parse_number(key, value)?,
+            "history.max_age_days" => updated.history.max_age_days = parse_number(key, value)?,
+            "control.enabled" => updated.control.enabled = parse_bool(key, value)?,
+            _ => return Err(anyhow!("Unknown setting {} (see /set for the list)", key)),
+        }
+        updated.validate()?;
+        *self = updated;
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.spam.min_pow_difficulty > 32 || self.pow.difficulty > 32 {
+            return Err(anyhow!("Proof of work difficulty must be 0-32 bits"));
+        }
+        if !(MIN_TICK_RATE_MS..=MAX_TICK_RATE_MS).contains(&self.ui.tick_rate_ms) {
+            return Err(anyhow!("ui.tick_rate_ms must be {}-{}", MIN_TICK_RATE_MS, MAX_TICK_RATE_MS));
+        }
+        if self.spam.max_messages_per_minute < MIN_MESSAGES_PER_MINUTE {
+            return Err(anyhow!("spam.max_messages_per_minute must be at least {}", MIN_MESSAGES_PER_MINUTE));
+        }
+        if self.spam.duplicate_message_threshold < MIN_DUPLICATE_THRESHOLD {
+            return Err(anyhow!("spam.duplicate_message_threshold must be at least {}", MIN_DUPLICATE_THRESHOLD));
+        }
+        if !(MIN_FUTURE_TIME_SECONDS..=MAX_FUTURE_TIME_SECONDS).contains(&self.spam.max_future_time_seconds) {
+            return Err(anyhow!("spam.max_future_time_seconds must be {}-{}", MIN_FUTURE_TIME_SECONDS, MAX_FUTURE_TIME_SECONDS));
+        }
+        if !(MIN_PAST_TIME_HOURS..=MAX_PAST_TIME_HOURS).contains(&self.spam.max_past_time_hours) {
+            return Err(anyhow!("spam.max_past_time_hours must be {}-{}", MIN_PAST_TIME_HOURS, MAX_PAST_TIME_HOURS));
+        }
+        check_time_format("ui.timestamp_format", &self.ui.timestamp_format)?;
+        check_time_format("log.format", &self.log.format)?;
+        Ok(())
+    }
+}
+
+// chrono panics while formatting with a bad format string, so catch it here
+fn check_time_format(key: &str, format: &str) -> Result<()> {
+    let valid = chrono::format::StrftimeItems::new(format)
+        .all(|item| !matches!(item, chrono::format::Item::Error));
+    if !valid {
+        return Err(anyhow!("{}: invalid format {}", key, format));
+    }
+    Ok(())
+}
+
+fn normalize_relays(relays: &[String]) -> Result<Vec<String>> {
+    relays.iter().map(|relay| normalize_relay_url(relay)).collect()
+}
+
+fn parse_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn parse_number<T: std::str::FromStr>(key: &str, value: &str) -> Result<T> {
+    value.trim().parse().map_err(|_| anyhow!("{} must be a number, got {}", key, value))
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool> {
+    match value.trim().to_lowercase().as_str() {
+        "true" | "on" | "yes" | "1" => Ok(true),
+        "false" | "off" | "no" | "0" => Ok(false),
+        _ => Err(anyhow!("{} must be on or off, got {}", key, value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_rejects_values_that_filter_everything() {
+        let mut config = Config::default();
+        assert!(config.set("spam.max_messages_per_minute", "0").is_err());
+        assert!(config.set("spam.duplicate_message_threshold", "1").is_err());
+        assert!(config.set("spam.max_future_time_seconds", "0").is_err());
+        assert!(config.set("spam.max_past_time_hours", "0").is_err());
+        // Large enough to overflow chrono
+        assert!(config.set("spam.max_past_time_hours", &u64::MAX.to_string()).is_err());
+        // Left as it was
+        assert_eq!(config.spam.max_messages_per_minute, 15);
+
+        config.set("spam.max_messages_per_minute", "1").unwrap();
+        config.set("spam.duplicate_message_threshold", "2").unwrap();
+        assert_eq!(config.get("spam.max_messages_per_minute").as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        Config::default().validate().unwrap();
+    }
+}
@@ -0,0 +1,318 @@
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::app::App;
+use crate::channels::Message;
+
+// JSON-RPC 2.0 error codes
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const APP_ERROR: i64 = -32000;
+
+const DEFAULT_MESSAGE_LIMIT: usize = 50;
+
+/// JSON-RPC API on a Unix socket, for editors, status bars and scripts
+///
+/// One request per line; methods are `channels`, `messages`, `send`, `join`,
+/// `leave` and `subscribe`. Requests are handed to the app on its next tick,
+/// so they run in the same session as the UI.
+pub struct ControlServer {
+    path: PathBuf,
+    requests: mpsc::UnboundedReceiver<ControlRequest>,
+}
+
+/// A call waiting for the app, answered through `reply`
+pub struct ControlRequest {
+    method: String,
+    params: Value,
+    reply: oneshot::Sender<Reply>,
+}
+
+enum Reply {
+    Result(Value),
+    Error(i64, String),
+    // Live messages for the connection that subscribed
+    Subscribed(mpsc::UnboundedReceiver<Message>),
+}
+
+#[derive(Deserialize)]
+struct RpcRequest {
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Deserialize)]
+struct ChannelParams {
+    channel: String,
+}
+
+#[derive(Deserialize)]
+struct MessagesParams {
+    channel: String,
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct SendParams {
+    channel: String,
+    text: String,
+}
+
+impl ControlServer {
+    /// $XDG_RUNTIME_DIR/bitchatx.sock, or next to the other data without one
+    pub fn default_path() -> PathBuf {
+        std::env::var_os("XDG_RUNTIME_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(crate::nostr::data_dir)
+            .join("bitchatx.sock")
+    }
+
+    /// Listen on `path`, replacing a socket left behind by a crashed session
+    #[cfg(unix)]
+    pub fn bind(path: &Path) -> Result<Self> {
+        use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+        match std::fs::symlink_metadata(path) {
+            Ok(metadata) => {
+                // Never delete something that isn't a stale socket, e.g. a mistyped --control-socket
+                if !metadata.file_type().is_socket() {
+                    return Err(anyhow!("{} exists and is not a socket", path.display()));
+                }
+                if std::os::unix::net::UnixStream::connect(path).is_ok() {
+                    return Err(anyhow!("{} is in use by another BitchatX session", path.display()));
+                }
+                std::fs::remove_file(path)?;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(anyhow!("Cannot check {}: {}", path.display(), e)),
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let listener = tokio::net::UnixListener::bind(path)
+            .map_err(|e| anyhow!("Cannot listen on {}: {}", path.display(), e))?;
+        // Anyone who can connect can post as us
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+
+        let (requests_tx, requests) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve_connection(stream, requests_tx.clone()));
+            }
+        });
+
+        Ok(Self { path: path.to_path_buf(), requests })
+    }
+
+    #[cfg(not(unix))]
+    pub fn bind(_path: &Path) -> Result<Self> {
+        Err(anyhow!("The control socket needs Unix domain sockets"))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn try_next(&mut self) -> Option<ControlRequest> {
+        self.requests.try_recv().ok()
+    }
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Run one request against the app and answer it
+pub async fn handle(app: &mut App, request: ControlRequest) {
+    let reply = match dispatch(app, &request.method, request.params).await {
+        Ok(reply) => reply,
+        Err(e) => Reply::Error(APP_ERROR, e.to_string()),
+    };
+    // The client may have hung up in the meantime
+    let _ = request.reply.send(reply);
+}
+
+async fn dispatch(app: &mut App, method: &str, params: Value) -> Result<Reply> {
+    let reply = match method {
+        "channels" => {
+            let channels: Vec<Value> = app
+                .channel_manager
+                .list_all_channels()
+                .into_iter()
+                .filter_map(|(name, joined)| {
+                    let channel = app.channel_manager.get_channel(&name)?;
+                    Some(json!({
+                        "name": name,
+                        "joined": joined,
+                        "current": app.current_channel.as_deref() == Some(name.as_str()),
+                        "participants": channel.participants.len(),
+                        "messages": channel.messages.len(),
+                        "unread": channel.unread,
+                        "unread_mentions": channel.unread_mentions,
+                    }))
+                })
+                .collect();
+            Reply::Result(json!(channels))
+        }
+        "messages" => {
+            let params: MessagesParams = match parse_params(params) {
+                Ok(params) => params,
+                Err(reply) => return Ok(reply),
+            };
+            let Some(channel) = app.channel_manager.get_channel(&params.channel) else {
+                return Ok(Reply::Error(INVALID_PARAMS, format!("Unknown channel {}", params.channel)));
+            };
+            let limit = params.limit.unwrap_or(DEFAULT_MESSAGE_LIMIT);
+            let start = channel.messages.len().saturating_sub(limit);
+            Reply::Result(json!(&channel.messages[start..]))
+        }
+        "send" => {
+            let params: SendParams = match parse_params(params) {
+                Ok(params) => params,
+                Err(reply) => return Ok(reply),
+            };
+            if !app.is_valid_geohash(&params.channel) {
+                return Ok(Reply::Error(INVALID_PARAMS, format!("Invalid geohash {}", params.channel)));
+            }
+            app.send_channel_message(&params.channel, &params.text).await?;
+            Reply::Result(json!(true))
+        }
+        "join" | "leave" => {
+            let params: ChannelParams = match parse_params(params) {
+                Ok(params) => params,
+                Err(reply) => return Ok(reply),
+            };
+            if !app.is_valid_geohash(&params.channel) {
+                return Ok(Reply::Error(INVALID_PARAMS, format!("Invalid geohash {}", params.channel)));
+            }
+            if method == "join" {
+                app.join_channel(&params.channel).await?;
+            } else {
+                app.leave_channel(&params.channel).await?;
+            }
+            Reply::Result(json!(true))
+        }
+        "subscribe" => Reply::Subscribed(app.channel_manager.subscribe()),
+        _ => Reply::Error(METHOD_NOT_FOUND, format!("Unknown method {}", method)),
+    };
+    Ok(reply)
+}
+
+fn parse_params<T: serde::de::DeserializeOwned>(params: Value) -> std::result::Result<T, Reply> {
+    serde_json::from_value(params).map_err(|e| Reply::Error(INVALID_PARAMS, format!("Invalid params: {}", e)))
+}
+
+#[cfg(unix)]
+async fn serve_connection(stream: tokio::net::UnixStream, requests: mpsc::UnboundedSender<ControlRequest>) {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    let mut events: Option<mpsc::UnboundedReceiver<Message>> = None;
+
+    loop {
+        let output = tokio::select! {
+            line = lines.next_line() => match line {
+                Ok(Some(line)) if line.trim().is_empty() => continue,
+                Ok(Some(line)) => match respond(&requests, &line, &mut events).await {
+                    Some(response) => response,
+                    None => continue,
+                },
+                _ => break,
+            },
+            Some(message) = next_event(&mut events) => {
+                json!({ "jsonrpc": "2.0", "method": "message", "params": message })
+            }
+        };
+
+        let mut line = output.to_string();
+        line.push('\n');
+        if writer.write_all(line.as_bytes()).await.is_err() {
+            break;
+        }
+    }
+}
+
+async fn next_event(events: &mut Option<mpsc::UnboundedReceiver<Message>>) -> Option<Message> {
+    match events {
+        Some(events) => events.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+// The response to one request line, None for notifications (no id)
+async fn respond(
+    requests: &mpsc::UnboundedSender<ControlRequest>,
+    line: &str,
+    events: &mut Option<mpsc::UnboundedReceiver<Message>>,
+) -> Option<Value> {
+    let request: RpcRequest = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(e) => return Some(error_response(Value::Null, PARSE_ERROR, &format!("Parse error: {}", e))),
+    };
+
+    let (reply_tx, reply_rx) = oneshot::channel();
+    let sent = requests.send(ControlRequest {
+        method: request.method,
+        params: request.params,
+        reply: reply_tx,
+    });
+    let reply = match sent {
+        Ok(()) => reply_rx.await.unwrap_or(Reply::Error(APP_ERROR, "BitchatX is shutting down".to_string())),
+        Err(_) => Reply::Error(APP_ERROR, "BitchatX is shutting down".to_string()),
+    };
+
+    let result = match reply {
+        Reply::Result(result) => Ok(result),
+        Reply::Error(code, message) => Err((code, message)),
+        Reply::Subscribed(receiver) => {
+            *events = Some(receiver);
+            Ok(json!(true))
+        }
+    };
+
+    let id = request.id?;
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err((code, message)) => error_response(id, code, &message),
+    })
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn bind_leaves_other_files_alone() {
+        let path = std::env::temp_dir().join(format!("bitchatx-control-{}", std::process::id()));
+        std::fs::write(&path, "notes").unwrap();
+
+        assert!(ControlServer::bind(&path).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "notes");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn bind_replaces_stale_socket() {
+        let path = std::env::temp_dir().join(format!("bitchatx-control-{}.sock", std::process::id()));
+        // Left behind like by a crashed session: bound, then the listener is gone
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+
+        let server = ControlServer::bind(&path).unwrap();
+        assert!(std::os::unix::net::UnixStream::connect(server.path()).is_ok());
+        assert!(ControlServer::bind(&path).is_err());
+    }
+}
@@ -0,0 +1,86 @@
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use std::io::Write;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+use crate::app::App;
+
+/// A command read from stdin, one JSON object per line
+///
+/// `{"cmd":"join","channel":"dr5reg"}`, `{"cmd":"leave","channel":"dr5reg"}`,
+/// `{"cmd":"send","channel":"dr5reg","text":"hi"}`, `{"cmd":"dm","to":"alice","text":"hi"}`
+/// (a nickname, npub or hex pubkey) and `{"cmd":"nick","nick":"bot"}`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "cmd", rename_all = "lowercase")]
+enum HeadlessCommand {
+    Join { channel: String },
+    Leave { channel: String },
+    Send { channel: String, text: String },
+    Dm { to: String, text: String },
+    Nick { nick: String },
+}
+
+/// Run without the terminal UI: every message is written to stdout as a JSON line,
+/// commands come in on stdin
+///
+/// Messages go through the same pipeline as in the UI (deduplication, blocking,
+/// spam filter), and include own messages and status lines (`"pubkey": null`).
+/// Stops at the end of stdin or on Ctrl+C.
+pub async fn run(app: &mut App) -> Result<()> {
+    let mut messages = app.channel_manager.subscribe();
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut ticker = tokio::time::interval(Duration::from_millis(app.config.ui.tick_rate_ms));
+
+    loop {
+        tokio::select! {
+            line = lines.next_line() => match line? {
+                Some(line) => {
+                    // A failed command is reported, the bot keeps running
+                    if let Err(e) = handle_line(app, &line).await {
+                        emit(&serde_json::json!({ "error": e.to_string() }))?;
+                    }
+                }
+                None => break,
+            },
+            _ = ticker.tick() => app.on_tick().await?,
+            _ = tokio::signal::ctrl_c() => break,
+        }
+
+        while let Ok(message) = messages.try_recv() {
+            emit(&message)?;
+        }
+
+        if app.should_quit {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_line(app: &mut App, line: &str) -> Result<()> {
+    let line = line.trim();
+    if line.is_empty() {
+        return Ok(());
+    }
+
+    let command: HeadlessCommand = serde_json::from_str(line).map_err(|e| anyhow!("Invalid command: {}", e))?;
+
+    match command {
+        HeadlessCommand::Join { channel } => app.join_channel(&channel).await,
+        HeadlessCommand::Leave { channel } => app.leave_channel(&channel).await,
+        HeadlessCommand::Send { channel, text } => app.send_channel_message(&channel, &text).await,
+        HeadlessCommand::Dm { to, text } => app.send_private_message_to(&to, &text).await,
+        HeadlessCommand::Nick { nick } => app.change_nickname(&nick).await,
+    }
+}
+
+fn emit<T: serde::Serialize>(value: &T) -> Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    serde_json::to_writer(&mut stdout, value)?;
+    writeln!(stdout)?;
+    stdout.flush()?;
+    Ok(())
+}
+
@@ -0,0 +1,731 @@
+use anyhow::{anyhow, Result};
+use nostr::{PublicKey, ToBech32};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::time::Duration;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+
+use crate::app::App;
+use crate::channels::Message;
+
+const SERVER_NAME: &str = "bitchatx";
+// Leaves room for the prefix and command within IRC's 512 byte lines
+const MAX_TEXT_BYTES: usize = 400;
+// Longest client line we read, CRLF included; IRC allows 512 but some clients send longer
+const MAX_LINE: usize = 4096;
+
+enum ClientEvent {
+    Line(u64, String),
+    Closed(u64),
+}
+
+struct IrcClient {
+    sender: mpsc::UnboundedSender<String>,
+    nick: Option<String>,
+    has_user: bool,
+    // Sent the right PASS, or the gateway has no password
+    authenticated: bool,
+    registered: bool,
+}
+
+/// A parsed client line: `[:prefix] COMMAND params... [:trailing]`
+struct IrcCommand {
+    command: String,
+    params: Vec<String>,
+}
+
+/// Local IRC server in front of the app, for irssi, weechat and friends
+///
+/// Every client sees the same session: the app's joined geohash channels are
+/// IRC channels (`#dr5reg`), others show up as `nick#abcd` with the last four
+/// hex digits of their pubkey, and status lines arrive as server notices.
+struct Gateway {
+    password: Option<String>,
+    clients: HashMap<u64, IrcClient>,
+    // Channels the clients were told they are in, following the app's joined channels
+    channels: BTreeSet<String>,
+    // IRC nicks already announced with a JOIN, per channel
+    members: HashMap<String, HashSet<String>>,
+}
+
+/// Run without the terminal UI, serving IRC clients on `address` (e.g. 127.0.0.1:6667)
+///
+/// Clients must send `password` with PASS when one is given; without one only
+/// loopback addresses are accepted, since every client can post as us.
+/// Stops on Ctrl+C; clients disconnecting doesn't stop the gateway.
+pub async fn run(app: &mut App, address: &str, password: Option<String>) -> Result<()> {
+    let listener = TcpListener::bind(address)
+        .await
+        .map_err(|e| anyhow!("Cannot listen on {}: {}", address, e))?;
+    let local_addr = listener.local_addr()?;
+    if password.is_none() && !local_addr.ip().is_loopback() {
+        return Err(anyhow!("Not listening on {} without --irc-password, anyone who can connect could post as you", local_addr));
+    }
+    eprintln!("IRC gateway listening on {}", local_addr);
+
+    let (events_tx, mut events) = mpsc::unbounded_channel();
+    let mut messages = app.channel_manager.subscribe();
+    let mut ticker = tokio::time::interval(Duration::from_millis(app.config.ui.tick_rate_ms));
+    let mut gateway = Gateway {
+        password,
+        clients: HashMap::new(),
+        channels: BTreeSet::new(),
+        members: HashMap::new(),
+    };
+    let mut next_id = 0;
+
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => {
+                    next_id += 1;
+                    let client = spawn_client(next_id, stream, events_tx.clone(), gateway.password.is_none());
+                    gateway.clients.insert(next_id, client);
+                }
+                Err(e) => eprintln!("IRC gateway: cannot accept connection: {}", e),
+            },
+            Some(event) = events.recv() => match event {
+                ClientEvent::Line(id, line) => gateway.handle_line(app, id, &line).await,
+                ClientEvent::Closed(id) => {
+                    gateway.clients.remove(&id);
+                }
+            },
+            _ = ticker.tick() => app.on_tick().await?,
+            _ = tokio::signal::ctrl_c() => break,
+        }
+
+        // Joins and parts first, so messages never arrive for a channel the client isn't in
+        gateway.sync_channels(app);
+        while let Ok(message) = messages.try_recv() {
+            gateway.relay_message(app, &message);
+        }
+
+        if app.should_quit {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+fn spawn_client(id: u64, stream: TcpStream, events: mpsc::UnboundedSender<ClientEvent>, authenticated: bool) -> IrcClient {
+    let (reader, mut writer) = stream.into_split();
+    let (sender, mut outgoing) = mpsc::unbounded_channel::<String>();
+
+    tokio::spawn(async move {
+        while let Some(line) = outgoing.recv().await {
+            if writer.write_all(format!("{}\r\n", line).as_bytes()).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    tokio::spawn(async move {
+        let mut reader = BufReader::new(reader);
+        // Ends on disconnect and on a line over MAX_LINE
+        while let Ok(Some(line)) = read_line(&mut reader).await {
+            if events.send(ClientEvent::Line(id, line)).is_err() {
+                return;
+            }
+        }
+        let _ = events.send(ClientEvent::Closed(id));
+    });
+
+    IrcClient {
+        sender,
+        nick: None,
+        has_user: false,
+        authenticated,
+        registered: false,
+    }
+}
+
+/// Next line without its line ending, None at the end of the stream
+///
+/// A client that never sends a newline would otherwise make us buffer forever.
+async fn read_line<R: AsyncBufRead + Unpin>(reader: &mut R) -> std::io::Result<Option<String>> {
+    let mut line = Vec::new();
+    if reader.take(MAX_LINE as u64 + 1).read_until(b'\n', &mut line).await? == 0 {
+        return Ok(None);
+    }
+    if line.len() > MAX_LINE {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "line too long"));
+    }
+    let line = String::from_utf8_lossy(&line);
+    Ok(Some(line.trim_end_matches(['\r', '\n']).to_string()))
+}
+
+impl IrcCommand {
+    fn parse(line: &str) -> Option<Self> {
+        let mut rest = line.trim_end();
+        if rest.starts_with(':') {
+            rest = rest.split_once(' ').map(|(_, rest)| rest).unwrap_or("");
+        }
+
+        let (head, trailing) = match rest.split_once(" :") {
+            Some((head, trailing)) => (head, Some(trailing)),
+            None => (rest, None),
+        };
+        let mut words = head.split(' ').filter(|word| !word.is_empty());
+        let command = words.next()?.to_uppercase();
+        let mut params: Vec<String> = words.map(str::to_string).collect();
+        if let Some(trailing) = trailing {
+            params.push(trailing.to_string());
+        }
+
+        Some(Self { command, params })
+    }
+}
+
+impl Gateway {
+    async fn handle_line(&mut self, app: &mut App, id: u64, line: &str) {
+        let Some(command) = IrcCommand::parse(line) else {
+            return;
+        };
+
+        // A failed send or join is the client's business, the gateway keeps running
+        if let Err(e) = self.handle_command(app, id, command).await {
+            self.notice(id, &e.to_string());
+        }
+    }
+
+    async fn handle_command(&mut self, app: &mut App, id: u64, command: IrcCommand) -> Result<()> {
+        let params = &command.params;
+        let registered = self.clients.get(&id).is_some_and(|client| client.registered);
+
+        match command.command.as_str() {
+            "CAP" if params.first().map(String::as_str) == Some("LS") => {
+                self.send(id, format!(":{} CAP * LS :", SERVER_NAME));
+            }
+            "PASS" if !registered => {
+                let correct = match (&self.password, params.first()) {
+                    (Some(password), Some(given)) => same_secret(password, given),
+                    (Some(_), None) => false,
+                    (None, _) => true,
+                };
+                if let Some(client) = self.clients.get_mut(&id) {
+                    client.authenticated = correct;
+                }
+            }
+            "CAP" | "PASS" | "PONG" => {}
+            "PING" => {
+                let token = params.first().map(String::as_str).unwrap_or(SERVER_NAME);
+                self.send(id, format!(":{} PONG {} :{}", SERVER_NAME, SERVER_NAME, token));
+            }
+            "NICK" => self.nick(app, id, params.first()).await?,
+            "USER" => {
+                if let Some(client) = self.clients.get_mut(&id) {
+                    client.has_user = true;
+                }
+                self.try_register(app, id).await?;
+            }
+            "QUIT" => {
+                self.send(id, "ERROR :Closing link".to_string());
+                // Dropping the sender closes the connection once the line is written
+                self.clients.remove(&id);
+            }
+            _ if !registered => self.reply(id, "451", ":You have not registered"),
+            "JOIN" => {
+                let Some(targets) = params.first() else {
+                    self.reply(id, "461", "JOIN :Not enough parameters");
+                    return Ok(());
+                };
+                for geohash in targets.split(',').map(channel_name) {
+                    if !self.channels.contains(&geohash) && geohash != app.system_channel {
+                        app.join_channel(&geohash).await?;
+                    }
+                }
+            }
+            "PART" => {
+                let Some(targets) = params.first() else {
+                    self.reply(id, "461", "PART :Not enough parameters");
+                    return Ok(());
+                };
+                for geohash in targets.split(',').map(channel_name) {
+                    if self.channels.contains(&geohash) {
+                        app.leave_channel(&geohash).await?;
+                    } else {
+                        self.reply(id, "442", &format!("#{} :You're not on that channel", geohash));
+                    }
+                }
+            }
+            "PRIVMSG" => {
+                let (Some(target), Some(text)) = (params.first(), params.get(1)) else {
+                    self.reply(id, "412", ":No text to send");
+                    return Ok(());
+                };
+                self.privmsg(app, target, text).await?;
+            }
+            // Notices are CTCP replies and bot chatter, nothing to publish
+            "NOTICE" => {}
+            "NAMES" => match params.first() {
+                Some(targets) => {
+                    for geohash in targets.split(',').map(channel_name) {
+                        self.names(app, id, &geohash);
+                    }
+                }
+                None => {
+                    for geohash in self.channels.clone() {
+                        self.names(app, id, &geohash);
+                    }
+                }
+            },
+            "WHOIS" => match params.last() {
+                Some(nick) => self.whois(app, id, nick),
+                None => self.reply(id, "431", ":No nickname given"),
+            },
+            "WHO" => {
+                let target = params.first().cloned().unwrap_or_default();
+                if target.starts_with('#') {
+                    self.who(app, id, &channel_name(&target));
+                }
+                self.reply(id, "315", &format!("{} :End of WHO list", target));
+            }
+            "MODE" => match params.first() {
+                Some(target) if target.starts_with('#') => match params.get(1).map(String::as_str) {
+                    None => self.reply(id, "324", &format!("{} +nt", target)),
+                    Some("b") => self.reply(id, "368", &format!("{} :End of channel ban list", target)),
+                    Some(_) => {}
+                },
+                Some(_) => self.reply(id, "221", "+i"),
+                None => self.reply(id, "461", "MODE :Not enough parameters"),
+            },
+            "TOPIC" => {
+                if let Some(target) = params.first() {
+                    self.reply(id, "331", &format!("{} :No topic is set", target));
+                }
+            }
+            "LIST" => {
+                self.reply(id, "321", "Channel :Users  Name");
+                for geohash in &self.channels {
+                    let users = app.channel_manager.get_channel(geohash).map_or(0, |channel| channel.participants.len());
+                    self.reply(id, "322", &format!("#{} {} :Geohash channel {}", geohash, users, geohash));
+                }
+                self.reply(id, "323", ":End of /LIST");
+            }
+            "ISON" => self.reply(id, "303", ":"),
+            "AWAY" => self.reply(id, "305", ":You are no longer marked as being away"),
+            other => self.reply(id, "421", &format!("{} :Unknown command", other)),
+        }
+
+        Ok(())
+    }
+
+    async fn nick(&mut self, app: &mut App, id: u64, nick: Option<&String>) -> Result<()> {
+        let Some(nick) = nick.map(|nick| nick.trim()).filter(|nick| !nick.is_empty()) else {
+            self.reply(id, "431", ":No nickname given");
+            return Ok(());
+        };
+        // '#' is what tells other people apart, our own nick can't have one
+        if nick.starts_with(':') || nick.contains(['#', ',', '!', '@', '*', '?']) {
+            self.reply(id, "432", &format!("{} :Erroneous nickname", nick));
+            return Ok(());
+        }
+
+        let Some(client) = self.clients.get_mut(&id) else {
+            return Ok(());
+        };
+        if client.registered {
+            return self.change_nick(app, nick).await;
+        }
+        // Kept on this client until it has registered, and so given the password
+        client.nick = Some(nick.to_string());
+        self.try_register(app, id).await
+    }
+
+    // The IRC nick is the chat nickname, for every connected client
+    async fn change_nick(&mut self, app: &mut App, nick: &str) -> Result<()> {
+        if nick != app.identity.nickname {
+            let old_prefix = self.own_prefix(app);
+            app.change_nickname(nick).await?;
+            self.broadcast(format!(":{} NICK :{}", old_prefix, nick));
+        }
+        for client in self.clients.values_mut().filter(|client| client.registered) {
+            client.nick = Some(nick.to_string());
+        }
+        Ok(())
+    }
+
+    async fn try_register(&mut self, app: &mut App, id: u64) -> Result<()> {
+        let Some(client) = self.clients.get(&id) else {
+            return Ok(());
+        };
+        let Some(requested_nick) = client.nick.clone() else {
+            return Ok(());
+        };
+        if client.registered || !client.has_user {
+            return Ok(());
+        }
+        if !client.authenticated {
+            self.reply(id, "464", ":Password incorrect");
+            self.send(id, "ERROR :Closing link (password required)".to_string());
+            self.clients.remove(&id);
+            return Ok(());
+        }
+        // Before this client counts as registered, so it isn't told about its own NICK
+        self.change_nick(app, &requested_nick).await?;
+        if let Some(client) = self.clients.get_mut(&id) {
+            client.registered = true;
+        }
+
+        let nick = app.identity.nickname.clone();
+        let version = env!("CARGO_PKG_VERSION");
+        self.reply(id, "001", &format!(":Welcome to BitchatX, {}", nick));
+        self.reply(id, "002", &format!(":Your host is {}, running version {}", SERVER_NAME, version));
+        self.reply(id, "003", ":This server is a gateway to Nostr geohash channels");
+        self.reply(id, "004", &format!("{} {} i nt", SERVER_NAME, version));
+        self.reply(id, "005", "CHANTYPES=# CASEMAPPING=ascii NETWORK=BitchatX :are supported by this server");
+        self.reply(id, "422", ":MOTD File is missing");
+
+        for geohash in self.channels.clone() {
+            self.send(id, format!(":{} JOIN #{}", self.own_prefix(app), geohash));
+            self.names(app, id, &geohash);
+        }
+        Ok(())
+    }
+
+    async fn privmsg(&mut self, app: &mut App, target: &str, text: &str) -> Result<()> {
+        // /me arrives as CTCP ACTION, other CTCP requests (VERSION, PING) stay local
+        let text = match text.strip_prefix('\x01') {
+            Some(ctcp) => match ctcp.trim_end_matches('\x01').strip_prefix("ACTION ") {
+                Some(action) => format!("* {} {}", app.identity.nickname, action),
+                None => return Ok(()),
+            },
+            None => text.to_string(),
+        };
+
+        if target.starts_with('#') {
+            app.send_channel_message(&channel_name(target), &text).await
+        } else {
+            let recipient = self.find_pubkey(app, target).unwrap_or_else(|| target.to_string());
+            app.send_private_message_to(&recipient, &text).await
+        }
+    }
+
+    fn names(&mut self, app: &App, id: u64, geohash: &str) {
+        if self.channels.contains(geohash) {
+            let mut nicks = vec![app.identity.nickname.clone()];
+            if let Some(channel) = app.channel_manager.get_channel(geohash) {
+                nicks.extend(channel.participants.values().map(|p| irc_nick(&p.nickname, &p.pubkey)));
+            }
+            nicks.sort();
+            nicks.dedup();
+
+            let members = self.members.entry(geohash.to_string()).or_default();
+            members.extend(nicks.iter().cloned());
+
+            // Several lines for busy channels, to stay within the line limit
+            for chunk in nicks.chunks(20) {
+                self.reply(id, "353", &format!("= #{} :{}", geohash, chunk.join(" ")));
+            }
+        }
+        self.reply(id, "366", &format!("#{} :End of /NAMES list", geohash));
+    }
+
+    fn who(&mut self, app: &App, id: u64, geohash: &str) {
+        let Some(channel) = app.channel_manager.get_channel(geohash) else {
+            return;
+        };
+        let lines: Vec<String> = channel
+            .participants
+            .values()
+            .filter_map(|p| {
+                let pubkey = p.pubkey.as_deref()?;
+                Some(format!(
+                    "#{} {} {} {} {} H :0 {}",
+                    geohash,
+                    user_name(pubkey),
+                    SERVER_NAME,
+                    SERVER_NAME,
+                    irc_nick(&p.nickname, &p.pubkey),
+                    npub(pubkey)
+                ))
+            })
+            .collect();
+        for line in lines {
+            self.reply(id, "352", &line);
+        }
+    }
+
+    fn whois(&mut self, app: &App, id: u64, nick: &str) {
+        let own = nick.eq_ignore_ascii_case(&app.identity.nickname);
+        let pubkey = if own { Some(app.identity.pubkey.clone()) } else { self.find_pubkey(app, nick) };
+
+        match pubkey {
+            Some(pubkey) => {
+                self.reply(id, "311", &format!("{} {} {} * :{}", nick, user_name(&pubkey), SERVER_NAME, npub(&pubkey)));
+
+                let channels: Vec<String> = self
+                    .channels
+                    .iter()
+                    .filter(|geohash| {
+                        own || app.channel_manager.get_channel(geohash).is_some_and(|channel| {
+                            channel.participants.values().any(|p| p.pubkey.as_deref() == Some(pubkey.as_str()))
+                        })
+                    })
+                    .map(|geohash| format!("#{}", geohash))
+                    .collect();
+                if !channels.is_empty() {
+                    self.reply(id, "319", &format!("{} :{}", nick, channels.join(" ")));
+                }
+            }
+            None => self.reply(id, "401", &format!("{} :No such nick", nick)),
+        }
+        self.reply(id, "318", &format!("{} :End of /WHOIS list", nick));
+    }
+
+    /// Pubkey behind an IRC nick (`nick#abcd`, or a bare nickname) seen in a channel or private chat
+    fn find_pubkey(&self, app: &App, nick: &str) -> Option<String> {
+        let participants = app
+            .channel_manager
+            .list_all_channels()
+            .into_iter()
+            .filter_map(|(name, _)| app.channel_manager.get_channel(&name))
+            .flat_map(|channel| channel.participants.values())
+            .filter_map(|p| Some((p.nickname.clone(), p.pubkey.clone()?)))
+            .collect::<Vec<_>>();
+        let private_chats = app.private_chats.iter().map(|(pubkey, nickname)| (nickname.clone(), pubkey.clone()));
+
+        participants
+            .into_iter()
+            .chain(private_chats)
+            .find(|(nickname, pubkey)| {
+                let pubkey = Some(pubkey.clone());
+                irc_nick(nickname, &pubkey).eq_ignore_ascii_case(nick) || nickname.eq_ignore_ascii_case(nick)
+            })
+            .map(|(_, pubkey)| pubkey)
+    }
+
+    /// Tell clients about channels the app joined or left since the last pass
+    fn sync_channels(&mut self, app: &App) {
+        let joined: BTreeSet<String> = app
+            .channel_manager
+            .list_channels()
+            .into_iter()
+            .filter(|name| *name != app.system_channel && !name.starts_with("dm:"))
+            .collect();
+
+        let left: Vec<String> = self.channels.difference(&joined).cloned().collect();
+        for geohash in left {
+            self.broadcast(format!(":{} PART #{}", self.own_prefix(app), geohash));
+            self.channels.remove(&geohash);
+            self.members.remove(&geohash);
+        }
+
+        let new: Vec<String> = joined.difference(&self.channels).cloned().collect();
+        for geohash in new {
+            self.channels.insert(geohash.clone());
+            self.broadcast(format!(":{} JOIN #{}", self.own_prefix(app), geohash));
+            let ids: Vec<u64> = self.registered_ids().collect();
+            for id in ids {
+                self.names(app, id, &geohash);
+            }
+        }
+    }
+
+    fn relay_message(&mut self, app: &App, message: &Message) {
+        // IRC clients show what they send themselves
+        if message.is_own || message.content.is_empty() {
+            return;
+        }
+
+        let Some(pubkey) = message.pubkey.as_deref() else {
+            for line in text_lines(&message.content) {
+                let ids: Vec<u64> = self.registered_ids().collect();
+                for id in ids {
+                    self.notice(id, &line);
+                }
+            }
+            return;
+        };
+
+        let nick = irc_nick(&message.nickname, &message.pubkey);
+        let prefix = format!("{}!{}@{}", nick, user_name(pubkey), SERVER_NAME);
+
+        let target = if message.is_private {
+            app.identity.nickname.clone()
+        } else if self.channels.contains(&message.channel) {
+            // Announce people the first time they speak, so nick lists stay current
+            let members = self.members.entry(message.channel.clone()).or_default();
+            if members.insert(nick.clone()) {
+                self.broadcast(format!(":{} JOIN #{}", prefix, message.channel));
+            }
+            format!("#{}", message.channel)
+        } else {
+            return;
+        };
+
+        for line in text_lines(&message.content) {
+            self.broadcast(format!(":{} PRIVMSG {} :{}", prefix, target, line));
+        }
+    }
+
+    fn own_prefix(&self, app: &App) -> String {
+        format!("{}!{}@{}", app.identity.nickname, user_name(&app.identity.pubkey), SERVER_NAME)
+    }
+
+    fn registered_ids(&self) -> impl Iterator<Item = u64> + '_ {
+        self.clients.iter().filter(|(_, client)| client.registered).map(|(id, _)| *id)
+    }
+
+    fn send(&self, id: u64, line: String) {
+        if let Some(client) = self.clients.get(&id) {
+            let _ = client.sender.send(line);
+        }
+    }
+
+    fn broadcast(&self, line: String) {
+        for client in self.clients.values().filter(|client| client.registered) {
+            let _ = client.sender.send(line.clone());
+        }
+    }
+
+    /// Numeric reply addressed to the client's nick
+    fn reply(&self, id: u64, code: &str, text: &str) {
+        let nick = self.clients.get(&id).and_then(|client| client.nick.clone()).unwrap_or_else(|| "*".to_string());
+        self.send(id, format!(":{} {} {} {}", SERVER_NAME, code, nick, text));
+    }
+
+    fn notice(&self, id: u64, text: &str) {
+        let nick = self.clients.get(&id).and_then(|client| client.nick.clone()).unwrap_or_else(|| "*".to_string());
+        self.send(id, format!(":{} NOTICE {} :{}", SERVER_NAME, nick, text));
+    }
+}
+
+// Compares every byte, so the time taken doesn't give away how much of a guess was right
+fn same_secret(expected: &str, given: &str) -> bool {
+    expected.len() == given.len()
+        && expected.bytes().zip(given.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+// "#DR5REG" -> "dr5reg"
+fn channel_name(target: &str) -> String {
+    target.trim().trim_start_matches('#').to_lowercase()
+}
+
+/// The chat's nick#abcd, made safe for IRC (no spaces or hostmask characters)
+fn irc_nick(nickname: &str, pubkey: &Option<String>) -> String {
+    let nickname: String = nickname
+        .chars()
+        .map(|c| if c.is_whitespace() || matches!(c, '!' | '@' | ',' | ':' | '*' | '?') { '_' } else { c })
+        .collect();
+    match pubkey {
+        Some(pubkey) if pubkey.len() >= 4 => format!("{}#{}", nickname, &pubkey[pubkey.len() - 4..]),
+        _ => nickname,
+    }
+}
+
+// Ident part of the hostmask, handy for /ignore masks
+fn user_name(pubkey: &str) -> &str {
+    &pubkey[..pubkey.len().min(8)]
+}
+
+fn npub(pubkey: &str) -> String {
+    PublicKey::from_hex(pubkey)
+        .ok()
+        .and_then(|public_key| public_key.to_bech32().ok())
+        .unwrap_or_else(|| pubkey.to_string())
+}
+
+// Multi-line messages become several PRIVMSGs, long lines are split on char boundaries
+fn text_lines(content: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    for line in content.lines().map(|line| line.trim_end_matches('\r')).filter(|line| !line.is_empty()) {
+        let mut current = String::new();
+        for c in line.chars() {
+            if current.len() + c.len_utf8() > MAX_TEXT_BYTES {
+                lines.push(std::mem::take(&mut current));
+            }
+            current.push(c);
+        }
+        lines.push(current);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::nostr::{HttpResolver, Identity};
+    use std::sync::Arc;
+
+    // An app with no relays or history, so nothing leaves the machine
+    async fn offline_app() -> App {
+        let mut config = Config::default();
+        config.relays.clear();
+        config.history.enabled = false;
+        let config_path = std::env::temp_dir().join(format!("bitchatx-irc-{}.toml", std::process::id()));
+        let resolver = Arc::new(HttpResolver::new(None).unwrap());
+        App::new(Identity::ephemeral(), config, config_path, None, resolver).await.unwrap()
+    }
+
+    fn gateway(password: Option<&str>) -> Gateway {
+        Gateway {
+            password: password.map(str::to_string),
+            clients: HashMap::new(),
+            channels: BTreeSet::new(),
+            members: HashMap::new(),
+        }
+    }
+
+    // A client as spawn_client makes it, without the socket
+    fn connect(gateway: &mut Gateway, id: u64) -> mpsc::UnboundedReceiver<String> {
+        let (sender, lines) = mpsc::unbounded_channel();
+        let authenticated = gateway.password.is_none();
+        gateway.clients.insert(id, IrcClient { sender, nick: None, has_user: false, authenticated, registered: false });
+        lines
+    }
+
+    #[tokio::test]
+    async fn nick_needs_the_password() {
+        let mut app = offline_app().await;
+        let nickname = app.identity.nickname.clone();
+        let mut gateway = gateway(Some("hunter2"));
+
+        let _intruder = connect(&mut gateway, 1);
+        gateway.handle_line(&mut app, 1, "NICK mallory").await;
+        assert_eq!(app.identity.nickname, nickname);
+        gateway.handle_line(&mut app, 1, "USER m 0 * :Mallory").await;
+        assert_eq!(app.identity.nickname, nickname);
+        assert!(!gateway.clients.contains_key(&1));
+
+        let mut lines = connect(&mut gateway, 2);
+        gateway.handle_line(&mut app, 2, "PASS hunter2").await;
+        gateway.handle_line(&mut app, 2, "NICK alice").await;
+        assert_eq!(app.identity.nickname, nickname);
+        gateway.handle_line(&mut app, 2, "USER a 0 * :Alice").await;
+        assert_eq!(app.identity.nickname, "alice");
+        assert!(lines.try_recv().unwrap().contains(" 001 alice "));
+    }
+
+    #[tokio::test]
+    async fn reads_lines_without_endings() {
+        let mut input: &[u8] = b"NICK alice\r\nUSER a 0 * :A\nPING x";
+        assert_eq!(read_line(&mut input).await.unwrap().as_deref(), Some("NICK alice"));
+        assert_eq!(read_line(&mut input).await.unwrap().as_deref(), Some("USER a 0 * :A"));
+        assert_eq!(read_line(&mut input).await.unwrap().as_deref(), Some("PING x"));
+        assert_eq!(read_line(&mut input).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn rejects_overlong_lines() {
+        let mut line = vec![b'a'; MAX_LINE - 2];
+        line.extend_from_slice(b"\r\n");
+        let mut input: &[u8] = &line;
+        assert!(read_line(&mut input).await.unwrap().is_some());
+
+        let endless = vec![b'a'; MAX_LINE * 4];
+        let mut input: &[u8] = &endless;
+        assert!(read_line(&mut input).await.is_err());
+    }
+
+    #[test]
+    fn compares_passwords() {
+        assert!(same_secret("hunter2", "hunter2"));
+        assert!(!same_secret("hunter2", "hunter3"));
+        assert!(!same_secret("hunter2", "hunter"));
+        assert!(!same_secret("hunter2", ""));
+    }
+}
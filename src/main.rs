@@ -1,5 +1,5 @@
-use anyhow::Result;
-use clap::{Arg, Command};
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgMatches, Command};
 use crossterm::{
     event::{self, DisableMouseCapture, EnableMouseCapture},
     execute,
@@ -13,12 +13,22 @@ use std::{
     time::{Duration, Instant},
 };
 
+mod aliases;
 mod app;
 mod channels;
+mod cli;
+mod config;
+mod control;
+mod headless;
+mod irc;
 mod nostr;
+mod scripts;
 mod ui;
 
 use app::App;
+use config::Config;
+use control::ControlServer;
+use crate::nostr::{HttpResolver, Identity, Keystore, RemoteSigner};
 
 const BITCHATX_LOGO: &str = r#"
  █████      ███   █████             █████                 █████    █████ █████
@@ -77,6 +87,27 @@ async fn main() -> Result<()> {
                 .value_name("NSEC_KEY")
                 .help("Login with your Nostr private key (nsec format) - WARNING: Visible in process list!")
         )
+        .arg(
+            Arg::new("keystore")
+                .long("keystore")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("nsec")
+                .help("Login with the encrypted key from `bitchatx key` (prompts for the passphrase)")
+        )
+        .arg(
+            Arg::new("bunker")
+                .long("bunker")
+                .value_name("BUNKER_URI")
+                .conflicts_with_all(["nsec", "keystore"])
+                .help("Sign with a NIP-46 remote signer (bunker://...) instead of a local key")
+        )
+        .arg(
+            Arg::new("nostrconnect")
+                .long("nostrconnect")
+                .value_name("RELAY_URL")
+                .conflicts_with_all(["nsec", "keystore", "bunker"])
+                .help("Pair with a NIP-46 signer app by showing a nostrconnect:// URI on this relay")
+        )
         .arg(
             Arg::new("channel")
                 .short('c')
@@ -84,19 +115,143 @@ async fn main() -> Result<()> {
                 .value_name("GEOHASH")
                 .help("Auto-join a geohash channel on startup")
         )
+        .arg(
+            Arg::new("geohash-identities")
+                .long("geohash-identities")
+                .action(clap::ArgAction::SetTrue)
+                .help("Sign each geohash channel with its own derived key, like bitchat mobile")
+        )
+        .arg(
+            Arg::new("pow")
+                .long("pow")
+                .value_name("BITS")
+                .value_parser(clap::value_parser!(u8).range(0..=32))
+                .help("Mine NIP-13 proof of work of this many bits into channel messages")
+        )
+        .arg(
+            Arg::new("min-pow")
+                .long("min-pow")
+                .value_name("BITS")
+                .value_parser(clap::value_parser!(u8).range(0..=32))
+                .help("Drop channel messages with less proof of work than this")
+        )
+        .arg(
+            Arg::new("nip05-server")
+                .long("nip05-server")
+                .value_name("URL")
+                .help("Resolve every NIP-05 identifier against this server instead of its domain (for testing)")
+        )
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .value_name("PATH")
+                .help("Read settings from this file instead of ~/.config/bitchatx/config.toml")
+        )
+        .arg(
+            Arg::new("control-socket")
+                .long("control-socket")
+                .value_name("PATH")
+                .help("Serve the JSON-RPC control API on this socket (without it: $XDG_RUNTIME_DIR/bitchatx.sock if control.enabled is set)")
+        )
+        .arg(
+            Arg::new("headless")
+                .long("headless")
+                .action(clap::ArgAction::SetTrue)
+                .help("Run without the terminal UI: messages as JSON lines on stdout, JSON commands on stdin")
+        )
+        .arg(
+            Arg::new("irc-listen")
+                .long("irc-listen")
+                .value_name("ADDR")
+                .conflicts_with("headless")
+                .help("Run without the terminal UI as an IRC server for irssi/weechat on this address (e.g. 127.0.0.1:6667)")
+        )
+        .arg(
+            Arg::new("irc-password")
+                .long("irc-password")
+                .value_name("PASSWORD")
+                .requires("irc-listen")
+                .help("Password IRC clients must send (PASS); required to listen on anything but localhost. Also read from BITCHATX_IRC_PASSWORD")
+        )
         .arg(
             Arg::new("no-logo")
                 .long("no-logo")
                 .action(clap::ArgAction::SetTrue)
                 .help("Skip startup logo animation")
         )
+        .subcommand(cli::key::command())
+        .subcommand(cli::send::command())
+        .subcommand(cli::listen::tail_command())
+        .subcommand(cli::listen::listen_command())
         .get_matches();
 
+    if let Some(("key", key_matches)) = matches.subcommand() {
+        return cli::key::run(key_matches).await;
+    }
+
+    let config_path = matches
+        .get_one::<String>("config")
+        .map(std::path::PathBuf::from)
+        .unwrap_or_else(Config::default_path);
+    let mut config = Config::load(&config_path)?;
+
+    // Resolve identity before the TUI takes over the terminal (may prompt for a passphrase)
+    let identity = load_identity(&matches).await?;
+
+    let irc_listen = matches.get_one::<String>("irc-listen");
+    // Command line PoW settings override config.toml for this session
+    let mining = matches.get_one::<u8>("pow").copied().unwrap_or(config.pow.difficulty);
+    let minimum = matches.get_one::<u8>("min-pow").copied().unwrap_or(config.spam.min_pow_difficulty);
+    
+    if let Some(("send", send_matches)) = matches.subcommand() {
+        return cli::send::run(send_matches, identity, &config, mining).await;
+    }
+    
+    // tail and listen run like --headless, printing instead of taking commands
+    let tail_channel = match matches.subcommand() {
+        Some(("tail", tail_matches)) => {
+            // Only the followed channel, not the usual ones
+            config.auto_join.clear();
+            Some(cli::listen::tail_channel(tail_matches)?)
+        }
+        _ => None,
+    };
+    let headless = matches.get_flag("headless") || irc_listen.is_some() || matches.subcommand().is_some();
+
     // Show startup logo unless disabled
-    if !matches.get_flag("no-logo") {
+    if config.ui.show_logo && !matches.get_flag("no-logo") && !headless {
         show_startup_logo();
     }
 
+    // Create app
+    let auto_channel = tail_channel.as_deref().or(matches.get_one::<String>("channel").map(|s| s.as_str()));
+    
+    let nip05_resolver = HttpResolver::new(matches.get_one::<String>("nip05-server").cloned())?;
+    
+    let mut app = App::new(identity, config, config_path, auto_channel, std::sync::Arc::new(nip05_resolver)).await?;
+    app.set_pow_policy(mining, minimum);
+    
+    match matches.subcommand() {
+        Some(("tail", tail_matches)) => return cli::listen::tail(&mut app, tail_matches).await,
+        Some(("listen", listen_matches)) => return cli::listen::listen(&mut app, listen_matches).await,
+        _ => {}
+    }
+
+    let control_socket = matches.get_one::<String>("control-socket").map(std::path::PathBuf::from);
+    if app.config.control.enabled || control_socket.is_some() {
+        app.start_control_socket(&control_socket.unwrap_or_else(ControlServer::default_path));
+    }
+
+    if let Some(address) = irc_listen {
+        let password = matches.get_one::<String>("irc-password").cloned()
+            .or_else(|| std::env::var("BITCHATX_IRC_PASSWORD").ok())
+            .filter(|password| !password.is_empty());
+        return irc::run(&mut app, address, password).await;
+    }
+    if headless {
+        return headless::run(&mut app).await;
+    }
+
     // Setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -108,11 +263,7 @@ async fn main() -> Result<()> {
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    // Create and run app
-    let nsec = matches.get_one::<String>("nsec").map(|s| s.as_str());
-    let auto_channel = matches.get_one::<String>("channel").map(|s| s.as_str());
-    
-    let mut app = App::new(nsec, auto_channel).await?;
+    // Run app
     let res = run_app(&mut terminal, &mut app).await;
 
     // Restore terminal
@@ -134,11 +285,46 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
+async fn load_identity(matches: &ArgMatches) -> Result<Identity> {
+    let mut identity = if let Some(nsec) = matches.get_one::<String>("nsec") {
+        Identity::from_nsec(nsec).await?
+    } else if matches.get_flag("keystore") {
+        let keystore = Keystore::open_default();
+        if !keystore.exists() {
+            return Err(anyhow!(
+                "No keystore at {} - create one with `bitchatx key generate` or `bitchatx key import`",
+                keystore.path().display()
+            ));
+        }
+        let passphrase = cli::prompt_secret(&format!("Passphrase for {}: ", keystore.path().display()))?;
+        Identity::from_keystore(&keystore, &passphrase).await?
+    } else if let Some(uri) = matches.get_one::<String>("bunker") {
+        eprintln!("Connecting to remote signer (approve the request in your signer if asked)...");
+        Identity::from_remote_signer(RemoteSigner::from_bunker_uri(uri).await?).await?
+    } else if let Some(relay) = matches.get_one::<String>("nostrconnect") {
+        let remote = RemoteSigner::pair(relay, |uri| {
+            eprintln!("Paste this into your NIP-46 signer app to connect:\n\n  {}\n", uri);
+            eprintln!("Waiting for the signer...");
+        }).await?;
+        Identity::from_remote_signer(remote).await?
+    } else {
+        Identity::ephemeral()
+    };
+    
+    if matches.get_flag("geohash-identities") {
+        identity.enable_geohash_identities()?;
+    }
+    
+    Ok(identity)
+}
+
 async fn run_app(terminal: &mut Terminal<CrosstermBackend<std::io::Stdout>>, app: &mut App) -> Result<()> {
     let mut last_tick = Instant::now();
-    let tick_rate = Duration::from_millis(100); // Increased frequency from 250ms to 100ms for more responsive message processing
 
     loop {
+        // Read every pass so /set ui.tick_rate_ms takes effect immediately
+        let tick_rate = Duration::from_millis(app.config.ui.tick_rate_ms);
+
         terminal.draw(|f| {
             ui::draw(f, &mut *app)
         })?;
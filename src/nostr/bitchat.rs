@@ -0,0 +1,300 @@
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+
+// Bitchat mobile clients embed their binary packet format inside NIP-17 rumors
+// as `bitchat1:<base64url>` (see NostrEmbeddedBitChat in bitchat-android/iOS)
+const CONTENT_PREFIX: &str = "bitchat1:";
+
+const PACKET_VERSION: u8 = 1;
+const PACKET_TTL: u8 = 7;
+const HEADER_SIZE: usize = 14; // version, type, ttl, timestamp(8), flags, payload length(2)
+const SENDER_ID_SIZE: usize = 8;
+const RECIPIENT_ID_SIZE: usize = 8;
+const SIGNATURE_SIZE: usize = 64;
+
+const FLAG_HAS_RECIPIENT: u8 = 0x01;
+const FLAG_HAS_SIGNATURE: u8 = 0x02;
+const FLAG_IS_COMPRESSED: u8 = 0x04;
+
+// Packet and noise payload types used for private messages
+const MESSAGE_TYPE_NOISE_ENCRYPTED: u8 = 0x11;
+const NOISE_PAYLOAD_PRIVATE_MESSAGE: u8 = 0x01;
+const NOISE_PAYLOAD_READ_RECEIPT: u8 = 0x02;
+const NOISE_PAYLOAD_DELIVERED: u8 = 0x03;
+
+// TLV fields of a private message payload
+const TLV_MESSAGE_ID: u8 = 0x00;
+const TLV_CONTENT: u8 = 0x01;
+
+// Bitchat pads packets to fixed block sizes to hide message length
+const PADDING_BLOCK_SIZES: &[usize] = &[256, 512, 1024, 2048];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BitchatPayload {
+    PrivateMessage { message_id: String, content: String },
+    Delivered { message_id: String },
+    ReadReceipt { message_id: String },
+}
+
+/// Check whether rumor content carries an embedded bitchat packet
+pub fn is_embedded(content: &str) -> bool {
+    content.starts_with(CONTENT_PREFIX)
+}
+
+/// Encode a payload as `bitchat1:` rumor content
+pub fn encode_for_nostr(payload: &BitchatPayload, sender_pubkey: &str) -> Result<String> {
+    let noise_payload = match payload {
+        BitchatPayload::PrivateMessage { message_id, content } => {
+            let mut data = vec![NOISE_PAYLOAD_PRIVATE_MESSAGE];
+            push_tlv(&mut data, TLV_MESSAGE_ID, message_id.as_bytes())?;
+            push_tlv(&mut data, TLV_CONTENT, content.as_bytes())?;
+            data
+        }
+        BitchatPayload::Delivered { message_id } => {
+            let mut data = vec![NOISE_PAYLOAD_DELIVERED];
+            data.extend_from_slice(message_id.as_bytes());
+            data
+        }
+        BitchatPayload::ReadReceipt { message_id } => {
+            let mut data = vec![NOISE_PAYLOAD_READ_RECEIPT];
+            data.extend_from_slice(message_id.as_bytes());
+            data
+        }
+    };
+    
+    if noise_payload.len() > u16::MAX as usize {
+        return Err(anyhow!("Bitchat payload too large"));
+    }
+    
+    let timestamp = chrono::Utc::now().timestamp_millis() as u64;
+    
+    let mut packet = Vec::with_capacity(HEADER_SIZE + SENDER_ID_SIZE + noise_payload.len());
+    packet.push(PACKET_VERSION);
+    packet.push(MESSAGE_TYPE_NOISE_ENCRYPTED);
+    packet.push(PACKET_TTL);
+    packet.extend_from_slice(&timestamp.to_be_bytes());
+    // No recipient id, on purpose: the gift wrap already addresses one peer, and
+    // mobile sends its geohash DMs the same way. We accept either form when decoding.
+    packet.push(0); // No recipient, signature or compression over Nostr
+    packet.extend_from_slice(&(noise_payload.len() as u16).to_be_bytes());
+    packet.extend_from_slice(&sender_id(sender_pubkey));
+    packet.extend_from_slice(&noise_payload);
+    
+    pad(&mut packet);
+    
+    Ok(format!("{}{}", CONTENT_PREFIX, URL_SAFE_NO_PAD.encode(packet)))
+}
+
+/// Decode `bitchat1:` rumor content into a payload
+pub fn decode_from_nostr(content: &str) -> Result<BitchatPayload> {
+    let encoded = content
+        .strip_prefix(CONTENT_PREFIX)
+        .ok_or_else(|| anyhow!("Not a bitchat payload"))?;
+    
+    // Tolerate senders that include base64 padding
+    let packet = URL_SAFE_NO_PAD.decode(encoded.trim_end_matches('='))?;
+    
+    if packet.len() < HEADER_SIZE + SENDER_ID_SIZE {
+        return Err(anyhow!("Bitchat packet too short"));
+    }
+    if packet[0] != PACKET_VERSION {
+        return Err(anyhow!("Unsupported bitchat packet version {}", packet[0]));
+    }
+    if packet[1] != MESSAGE_TYPE_NOISE_ENCRYPTED {
+        return Err(anyhow!("Unsupported bitchat packet type {:#04x}", packet[1]));
+    }
+    
+    let flags = packet[11];
+    if flags & FLAG_IS_COMPRESSED != 0 {
+        return Err(anyhow!("Compressed bitchat packets are not supported"));
+    }
+    
+    let payload_length = u16::from_be_bytes([packet[12], packet[13]]) as usize;
+    let mut offset = HEADER_SIZE + SENDER_ID_SIZE;
+    if flags & FLAG_HAS_RECIPIENT != 0 {
+        offset += RECIPIENT_ID_SIZE;
+    }
+    
+    // Any trailing bytes past the payload (signature, padding) are ignored
+    let signature_size = if flags & FLAG_HAS_SIGNATURE != 0 { SIGNATURE_SIZE } else { 0 };
+    if packet.len() < offset + payload_length + signature_size || payload_length == 0 {
+        return Err(anyhow!("Truncated bitchat packet"));
+    }
+    
+    let noise_payload = &packet[offset..offset + payload_length];
+    let data = &noise_payload[1..];
+    
+    match noise_payload[0] {
+        NOISE_PAYLOAD_PRIVATE_MESSAGE => {
+            let mut message_id = None;
+            let mut content = None;
+            let mut pos = 0;
+            while pos + 2 <= data.len() {
+                let field = data[pos];
+                let len = data[pos + 1] as usize;
+                pos += 2;
+                if pos + len > data.len() {
+                    return Err(anyhow!("Malformed bitchat private message"));
+                }
+                let value = String::from_utf8_lossy(&data[pos..pos + len]).to_string();
+                match field {
+                    TLV_MESSAGE_ID => message_id = Some(value),
+                    TLV_CONTENT => content = Some(value),
+                    _ => {} // Skip unknown fields for forward compatibility
+                }
+                pos += len;
+            }
+            
+            match (message_id, content) {
+                (Some(message_id), Some(content)) => Ok(BitchatPayload::PrivateMessage { message_id, content }),
+                _ => Err(anyhow!("Incomplete bitchat private message")),
+            }
+        }
+        NOISE_PAYLOAD_DELIVERED => Ok(BitchatPayload::Delivered {
+            message_id: String::from_utf8_lossy(data).to_string(),
+        }),
+        NOISE_PAYLOAD_READ_RECEIPT => Ok(BitchatPayload::ReadReceipt {
+            message_id: String::from_utf8_lossy(data).to_string(),
+        }),
+        other => Err(anyhow!("Unsupported bitchat payload type {:#04x}", other)),
+    }
+}
+
+/// Generate a message id in the format bitchat clients use
+pub fn new_message_id() -> String {
+    uuid::Uuid::new_v4().to_string().to_uppercase()
+}
+
+fn push_tlv(data: &mut Vec<u8>, field: u8, value: &[u8]) -> Result<()> {
+    if value.len() > u8::MAX as usize {
+        return Err(anyhow!("Bitchat private messages are limited to 255 bytes"));
+    }
+    data.push(field);
+    data.push(value.len() as u8);
+    data.extend_from_slice(value);
+    Ok(())
+}
+
+/// Bitchat peer ids are 8 bytes; over Nostr we derive ours from the pubkey
+fn sender_id(pubkey_hex: &str) -> [u8; SENDER_ID_SIZE] {
+    let mut id = [0u8; SENDER_ID_SIZE];
+    for (i, byte) in id.iter_mut().enumerate() {
+        if let Some(hex) = pubkey_hex.get(i * 2..i * 2 + 2) {
+            *byte = u8::from_str_radix(hex, 16).unwrap_or(0);
+        }
+    }
+    id
+}
+
+/// PKCS#7-style padding up to the next bitchat block size
+fn pad(packet: &mut Vec<u8>) {
+    let Some(&block_size) = PADDING_BLOCK_SIZES.iter().find(|&&size| size >= packet.len()) else {
+        return;
+    };
+    
+    let padding = block_size - packet.len();
+    if padding == 0 || padding > u8::MAX as usize {
+        return;
+    }
+    
+    packet.resize(block_size, padding as u8);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PUBKEY: &str = "2b0ca3b5f3e1c1a4d7b3f4b9e8a7c6d5e4f3a2b1c0d9e8f7a6b5c4d3e2f1a0b9";
+
+    fn private_message(content: &str) -> BitchatPayload {
+        BitchatPayload::PrivateMessage {
+            message_id: new_message_id(),
+            content: content.to_string(),
+        }
+    }
+
+    #[test]
+    fn round_trips_every_payload() {
+        let message_id = new_message_id();
+        for payload in [
+            private_message("hello from the terminal ☕"),
+            BitchatPayload::Delivered { message_id: message_id.clone() },
+            BitchatPayload::ReadReceipt { message_id },
+        ] {
+            let content = encode_for_nostr(&payload, PUBKEY).unwrap();
+            assert!(is_embedded(&content));
+            assert_eq!(decode_from_nostr(&content).unwrap(), payload);
+        }
+    }
+
+    #[test]
+    fn decodes_a_packet_from_mobile() {
+        // A private message as bitchat mobile sends it, with a recipient id set
+        let mut packet = vec![
+            0x01, // version
+            0x11, // noise encrypted
+            0x07, // ttl
+            0x00, 0x00, 0x01, 0x92, 0x3e, 0x4a, 0x5b, 0x00, // timestamp (ms)
+            0x01, // flags: has recipient
+            0x00, 0x2e, // payload length: 46
+            0x2b, 0x0c, 0xa3, 0xb5, 0xf3, 0xe1, 0xc1, 0xa4, // sender id
+            0x9a, 0x8b, 0x7c, 0x6d, 0x5e, 0x4f, 0x30, 0x21, // recipient id
+            0x01, // private message
+            0x00, 0x24, // message id, 36 bytes
+        ];
+        packet.extend_from_slice(b"5F0E2C4A-8B1D-4E3F-9A6C-7D2B1E0F3A4C");
+        packet.extend_from_slice(&[0x01, 0x05]); // content, 5 bytes
+        packet.extend_from_slice(b"hello");
+        assert_eq!(packet.len(), 76);
+        packet.resize(256, 0xb4); // PKCS#7 padding: 180 bytes of 180
+
+        let content = format!("{}{}", CONTENT_PREFIX, URL_SAFE_NO_PAD.encode(&packet));
+        assert_eq!(
+            decode_from_nostr(&content).unwrap(),
+            BitchatPayload::PrivateMessage {
+                message_id: "5F0E2C4A-8B1D-4E3F-9A6C-7D2B1E0F3A4C".to_string(),
+                content: "hello".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn pads_to_a_block_size() {
+        let content = encode_for_nostr(&private_message("hi"), PUBKEY).unwrap();
+        let packet = URL_SAFE_NO_PAD.decode(content.strip_prefix(CONTENT_PREFIX).unwrap()).unwrap();
+        assert_eq!(packet.len(), 256);
+        assert_eq!(&packet[HEADER_SIZE..HEADER_SIZE + SENDER_ID_SIZE], &sender_id(PUBKEY));
+    }
+
+    #[test]
+    fn limits_content_to_255_bytes() {
+        let longest = private_message(&"a".repeat(255));
+        let content = encode_for_nostr(&longest, PUBKEY).unwrap();
+        assert_eq!(decode_from_nostr(&content).unwrap(), longest);
+
+        assert!(encode_for_nostr(&private_message(&"a".repeat(256)), PUBKEY).is_err());
+        // The limit is in bytes, not characters
+        assert!(encode_for_nostr(&private_message(&"é".repeat(128)), PUBKEY).is_err());
+    }
+
+    #[test]
+    fn accepts_base64_padding() {
+        let payload = BitchatPayload::Delivered { message_id: new_message_id() };
+        let content = encode_for_nostr(&payload, PUBKEY).unwrap();
+        let packet = URL_SAFE_NO_PAD.decode(content.strip_prefix(CONTENT_PREFIX).unwrap()).unwrap();
+        let padded = format!("{}{}", CONTENT_PREFIX, base64::engine::general_purpose::URL_SAFE.encode(packet));
+        assert_eq!(decode_from_nostr(&padded).unwrap(), payload);
+    }
+
+    #[test]
+    fn rejects_malformed_content() {
+        assert!(decode_from_nostr("hello").is_err());
+        assert!(decode_from_nostr("bitchat1:!!!").is_err());
+        assert!(decode_from_nostr(&format!("{}{}", CONTENT_PREFIX, URL_SAFE_NO_PAD.encode([1u8; 10]))).is_err());
+
+        let content = encode_for_nostr(&private_message("hi"), PUBKEY).unwrap();
+        let mut packet = URL_SAFE_NO_PAD.decode(content.strip_prefix(CONTENT_PREFIX).unwrap()).unwrap();
+        packet.truncate(HEADER_SIZE + SENDER_ID_SIZE + 4);
+        assert!(decode_from_nostr(&format!("{}{}", CONTENT_PREFIX, URL_SAFE_NO_PAD.encode(packet))).is_err());
+    }
+}
@@ -1,61 +1,160 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use nostr_sdk::prelude::*;
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
 use tokio::time::timeout;
 
-use super::{Identity, GeoRelayDirectory};
+use super::bitchat::{self, BitchatPayload};
+use super::event_audit::{EventAudit, RelayEventStats, Rejection};
+use super::relay_health::RelaySupervisor;
+use super::relay_list::{self, RelayEntry, RelaySource};
+use super::pow::{self, PowJob};
+use super::{Identity, GeoRelayDirectory, Nip05Resolver, Nip05Verifier};
 use crate::channels::Message;
 
-// Default Nostr relays for BitchatX (synchronized with bitchat-android)
+// Default Nostr relays for BitchatX (synchronized with bitchat-android), unless config.toml lists others
 // These are the same 4 core relays used in bitchat-android's NostrRelayManager.kt
 // for consistent connectivity across platforms
-const DEFAULT_RELAYS: &[&str] = &[
+pub const DEFAULT_RELAYS: &[&str] = &[
     "wss://relay.damus.io",      // Damus relay - popular and reliable
     "wss://relay.primal.net",    // Primal relay - good performance
     "wss://offchain.pub",        // Offchain relay - stable connection
     "wss://nostr21.com",         // Nostr21 relay - additional redundancy
 ];
 
+// How long to wait for a relay's NIP-11 information document
+const RELAY_INFO_TIMEOUT: Duration = Duration::from_secs(10);
+
+// How long a relay has to accept a channel message before it counts as not sent there
+const SEND_TIMEOUT: Duration = Duration::from_secs(5);
+
+// How long `bitchatx send` waits for each relay to connect and to answer OK
+const PUBLISH_TIMEOUT: Duration = Duration::from_secs(10);
+
+// How long to wait for relays to return our kind 0 profile
+const PROFILE_FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+
+// NIP-59 randomizes gift wrap timestamps up to two days into the past,
+// so the DM subscription has to look back at least that far
+const GIFT_WRAP_LOOKBACK_SECS: u64 = 2 * 24 * 60 * 60;
+
+/// How a private message's rumor content is written
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DmFormat {
+    /// Plain NIP-17 text
+    Plain,
+    /// A `bitchat1:` packet, as bitchat Android/iOS send
+    Bitchat,
+}
+
+/// A channel message on its way out, from NostrClient::send_message
+pub struct OutgoingMessage {
+    /// Proof of work being mined for it, if enabled
+    pub pow: Option<PowJob>,
+    /// The event id once a relay accepted it, or why it wasn't sent
+    pub sent: oneshot::Receiver<Result<EventId>>,
+}
+
+/// State shared with the background notification listener
+#[derive(Clone)]
+struct ListenerContext {
+    client: Client,
+    identity: Identity,
+    message_tx: mpsc::UnboundedSender<Message>,
+    status_tx: mpsc::UnboundedSender<String>,
+    dm_formats: Arc<Mutex<HashMap<String, DmFormat>>>,
+    geohash_pubkeys: Arc<Mutex<HashMap<String, String>>>,
+    dm_geohashes: Arc<Mutex<HashMap<String, String>>>,
+    audit: EventAudit,
+    relay_supervisor: RelaySupervisor,
+}
+
 pub struct NostrClient {
     client: Client,
     identity: Identity,
     subscriptions: HashMap<String, SubscriptionId>,
+    dm_subscription: Option<SubscriptionId>,
+    listener: Option<JoinHandle<()>>,
     message_tx: mpsc::UnboundedSender<Message>,
     status_tx: mpsc::UnboundedSender<String>,
     geo_relay_directory: GeoRelayDirectory,
-    connected_relays: HashSet<String>,
+    connected_relays: HashMap<String, RelaySource>,
+    // Relays dropped with /relay remove that georelay lookups mustn't bring back
+    removed_relays: Vec<String>,
+    // Connection state and reconnection of every relay in the pool
+    relay_supervisor: RelaySupervisor,
+    // Format each peer last DMed us in, replies use the same
+    dm_formats: Arc<Mutex<HashMap<String, DmFormat>>>,
+    // Send bitchat packets to peers we haven't heard from yet, rather than plain NIP-17
+    bitchat_first_contact: bool,
+    // Derived pubkey -> geohash, for routing gift wraps sent to per-geohash identities
+    geohash_pubkeys: Arc<Mutex<HashMap<String, String>>>,
+    // Peer pubkey -> geohash whose identity the private chat uses
+    dm_geohashes: Arc<Mutex<HashMap<String, String>>>,
+    // NIP-13 difficulty mined into outgoing channel messages, 0 = off
+    pow_difficulty: u8,
+    // Forged and malformed events per relay
+    audit: EventAudit,
 }
 
 impl NostrClient {
     pub async fn new(
         identity: &Identity,
+        relays: &[String],
+        removed_relays: &[String],
         message_tx: mpsc::UnboundedSender<Message>,
         status_tx: mpsc::UnboundedSender<String>,
     ) -> Result<Self> {
-        let client = Client::new(&identity.keys);
+        let client = match identity.nostr_signer() {
+            Some(signer) => Client::new(signer),
+            // Remote signing happens in Identity, outside the client
+            None => Client::default(),
+        };
         
         // Initialize georelay directory
         let geo_relay_directory = GeoRelayDirectory::new()?;
         geo_relay_directory.initialize().await?;
         
-        // Add default relays for initial connection
+        // Connect to the configured relays first
         // These will be supplemented with geohash-specific relays when joining channels
-        let mut connected_relays = HashSet::new();
-        for &relay_url in DEFAULT_RELAYS {
-            client.add_relay(relay_url).await?;
-            connected_relays.insert(relay_url.to_string());
+        let relay_supervisor = RelaySupervisor::new(client.clone(), status_tx.clone());
+        let mut connected_relays = HashMap::new();
+        for relay_url in relays {
+            match relay_supervisor.add(relay_url).await {
+                Ok(_) => {
+                    let source = match DEFAULT_RELAYS.contains(&relay_url.as_str()) {
+                        true => RelaySource::Default,
+                        false => RelaySource::Manual,
+                    };
+                    connected_relays.insert(relay_url.clone(), source);
+                }
+                Err(e) => {
+                    let _ = status_tx.send(format!("Skipping relay {}: {}", relay_url, e));
+                }
+            }
         }
         
         Ok(Self {
             client,
             identity: identity.clone(),
             subscriptions: HashMap::new(),
+            dm_subscription: None,
+            listener: None,
             message_tx,
             status_tx,
             geo_relay_directory,
             connected_relays,
+            removed_relays: removed_relays.to_vec(),
+            relay_supervisor,
+            dm_formats: Arc::new(Mutex::new(HashMap::new())),
+            bitchat_first_contact: false,
+            geohash_pubkeys: Arc::new(Mutex::new(HashMap::new())),
+            dm_geohashes: Arc::new(Mutex::new(HashMap::new())),
+            pow_difficulty: 0,
+            audit: EventAudit::shared(),
         })
     }
     
@@ -69,52 +168,109 @@ impl NostrClient {
                 
                 // Start listening for notifications
                 self.start_notification_listener().await?;
+                self.subscribe_to_private_messages().await;
                 Ok(())
             }
             Err(_) => {
                 let _ = self.status_tx.send("Connection timeout - using available relays".to_string());
                 // Continue with partial connectivity
                 self.start_notification_listener().await?;
+                self.subscribe_to_private_messages().await;
                 Ok(())
             }
         }
     }
     
-    async fn start_notification_listener(&self) -> Result<()> {
+    async fn start_notification_listener(&mut self) -> Result<()> {
         let mut notifications = self.client.notifications();
-        let message_tx = self.message_tx.clone();
-        let status_tx = self.status_tx.clone();
-        let our_pubkey = self.identity.pubkey.clone();
+        let ctx = ListenerContext {
+            client: self.client.clone(),
+            identity: self.identity.clone(),
+            message_tx: self.message_tx.clone(),
+            status_tx: self.status_tx.clone(),
+            dm_formats: self.dm_formats.clone(),
+            geohash_pubkeys: self.geohash_pubkeys.clone(),
+            dm_geohashes: self.dm_geohashes.clone(),
+            audit: self.audit.clone(),
+            relay_supervisor: self.relay_supervisor.clone(),
+        };
         
-        tokio::spawn(async move {
+        let listener = tokio::spawn(async move {
             while let Ok(notification) = notifications.recv().await {
                 // Process notifications immediately without any buffering
                 match notification {
-                    RelayPoolNotification::Event { event, .. } => {
-                        if let Err(e) = Self::handle_event(*event, &message_tx, &status_tx, &our_pubkey).await {
-                            let _ = status_tx.send(format!("Error processing event: {}", e));
+                    RelayPoolNotification::Event { relay_url, event, .. } if event.kind() == Kind::GiftWrap => {
+                        // Unwrapping may round-trip to a remote signer, so don't hold up channel traffic
+                        let ctx = ctx.clone();
+                        tokio::spawn(async move {
+                            if let Err(e) = Self::handle_gift_wrap(&event, &relay_url, &ctx).await {
+                                let _ = ctx.status_tx.send(format!("Error processing private message: {}", e));
+                            }
+                        });
+                    }
+                    // Channel events are taken from every relay's copy rather than just the
+                    // first, so the channel manager can record which relays delivered them
+                    RelayPoolNotification::Message { relay_url, message: RelayMessage::Event { event, .. } }
+                        if event.kind() == Kind::Ephemeral(20000) =>
+                    {
+                        if let Err(e) = Self::handle_event(*event, &relay_url, &ctx).await {
+                            let _ = ctx.status_tx.send(format!("Error processing event: {}", e));
                         }
                     }
+                    RelayPoolNotification::Event { .. } => {
+                        // Already handled through the relay's Message notification
+                    }
                     RelayPoolNotification::Message { .. } => {
                         // Don't show raw relay messages to users
                     }
-                    RelayPoolNotification::RelayStatus { .. } => {
-                        // Don't show relay connection status messages to users
+                    RelayPoolNotification::RelayStatus { relay_url, status } => {
+                        // Tracked for reconnection and the title bar, not shown per relay
+                        ctx.relay_supervisor.on_status(&relay_url, status);
                     }
                     _ => {}
                 }
             }
         });
+        self.listener = Some(listener);
         
         Ok(())
     }
     
-    async fn handle_event(
-        event: Event,
-        message_tx: &mpsc::UnboundedSender<Message>,
-        _status_tx: &mpsc::UnboundedSender<String>,
-        our_pubkey: &str,
-    ) -> Result<()> {
+    /// Switch to another identity without reconnecting
+    ///
+    /// Subsequent events are signed with the new keys, and channel and private
+    /// message subscriptions are rebuilt for its pubkeys.
+    pub async fn set_identity(&mut self, identity: &Identity) -> Result<()> {
+        self.identity = identity.clone();
+        self.client.set_signer(identity.nostr_signer()).await;
+        
+        // Private chat routing belonged to the previous identity's keys
+        if let Ok(mut geohash_pubkeys) = self.geohash_pubkeys.lock() {
+            geohash_pubkeys.clear();
+        }
+        if let Ok(mut dm_geohashes) = self.dm_geohashes.lock() {
+            dm_geohashes.clear();
+        }
+        
+        // The listener holds its own copy of the identity
+        if let Some(listener) = self.listener.take() {
+            listener.abort();
+        }
+        self.start_notification_listener().await?;
+        
+        let channels: Vec<String> = self.subscriptions.keys().cloned().collect();
+        for geohash in channels {
+            if let Some(subscription_id) = self.subscriptions.remove(&geohash) {
+                self.client.unsubscribe(subscription_id).await;
+            }
+            self.subscribe_to_channel(&geohash).await?;
+        }
+        self.subscribe_to_private_messages().await;
+        
+        Ok(())
+    }
+    
+    async fn handle_event(event: Event, relay_url: &Url, ctx: &ListenerContext) -> Result<()> {
         // Only process kind 20000 (ephemeral events)
         if event.kind() != Kind::Ephemeral(20000) {
             return Ok(());
@@ -148,13 +304,22 @@ impl NostrClient {
             .unwrap_or_else(|| format!("anon{}", &event.pubkey.to_hex()[..8]));
         
         if let Some(channel) = geohash {
-            let is_own = event.pubkey.to_hex() == our_pubkey;
+            let author = event.pubkey.to_hex();
+            let is_own = author == ctx.identity.pubkey || author == ctx.identity.pubkey_for_geohash(&channel);
             
             // Skip our own messages if we already have local echo
             if is_own {
                 return Ok(());
             }
             
+            // A p tag naming us; the app adds nickname and highlight word mentions
+            let is_mention = event.tags().iter().any(|tag| match tag.as_vec() {
+                vec if vec.len() >= 2 && vec[0] == "p" => {
+                    vec[1] == ctx.identity.pubkey || vec[1] == ctx.identity.pubkey_for_geohash(&channel)
+                }
+                _ => false,
+            });
+            
             let message = Message {
                 channel,
                 nickname,
@@ -165,14 +330,197 @@ impl NostrClient {
                 is_own,
                 is_private: false,
                 recipient_pubkey: None,
+                pow_difficulty: pow::difficulty_of(&event),
+                event_id: Some(event.id.to_hex()),
+                relay_url: Some(relay_url.to_string()),
+                is_mention,
+                send_id: None,
             };
             
-            let _ = message_tx.send(message);
+            let _ = ctx.message_tx.send(message);
+        } else {
+            // Channel subscriptions filter on the g tag, so the relay ignored our filter
+            ctx.audit.record(relay_url.as_str(), Rejection::Malformed);
         }
         
         Ok(())
     }
     
+    /// Decrypt a NIP-17 gift wrap and route the private message to its `dm:` channel
+    async fn handle_gift_wrap(event: &Event, relay_url: &Url, ctx: &ListenerContext) -> Result<()> {
+        let identity = &ctx.identity;
+        
+        // Gift wraps addressed to a per-geohash identity must be opened with that key
+        let geohash = event
+            .public_keys()
+            .next()
+            .and_then(|recipient| {
+                ctx.geohash_pubkeys.lock().ok()?.get(&recipient.to_hex()).cloned()
+            });
+        let our_pubkey = match &geohash {
+            Some(geohash) => identity.pubkey_for_geohash(geohash),
+            None => identity.pubkey.clone(),
+        };
+        
+        // The pool only verified the wrap, which is signed with a throwaway key; the
+        // seal inside carries the sender's signature
+        let seal = identity.open_gift_wrap(geohash.as_deref(), event).await?;
+        if !ctx.audit.verify(relay_url, &seal) {
+            return Ok(());
+        }
+        let rumor = identity.open_seal(geohash.as_deref(), &seal).await?;
+        
+        // Only chat messages are supported
+        if rumor.kind != Kind::SealedDirect {
+            return Ok(());
+        }
+        // The rumor author must match the seal signer, otherwise anyone could forge a
+        // DM in someone else's name
+        if rumor.pubkey != seal.pubkey {
+            ctx.audit.record(relay_url.as_str(), Rejection::Forged);
+            return Ok(());
+        }
+        
+        let sender = seal.pubkey.to_hex();
+        
+        // Our own wraps are already shown via local echo
+        if sender == identity.pubkey || sender == our_pubkey {
+            return Ok(());
+        }
+        
+        // Replies in this private chat must come from the same identity
+        if let Some(geohash) = &geohash {
+            if let Ok(mut dm_geohashes) = ctx.dm_geohashes.lock() {
+                dm_geohashes.insert(sender.clone(), geohash.clone());
+            }
+        }
+        
+        let nickname = rumor
+            .tags
+            .iter()
+            .find_map(|tag| {
+                match tag.as_vec() {
+                    vec if vec.len() >= 2 && vec[0] == "n" => {
+                        Some(vec[1].to_string())
+                    }
+                    _ => None
+                }
+            })
+            .unwrap_or_else(|| format!("anon{}", &sender[..8]));
+        
+        // Bitchat mobile clients embed their binary packet format instead of plain text
+        let format = if bitchat::is_embedded(&rumor.content) { DmFormat::Bitchat } else { DmFormat::Plain };
+        if let Ok(mut formats) = ctx.dm_formats.lock() {
+            formats.insert(sender.clone(), format);
+        }
+        
+        let content = if format == DmFormat::Bitchat {
+            match bitchat::decode_from_nostr(&rumor.content)? {
+                BitchatPayload::PrivateMessage { message_id, content } => {
+                    // Acknowledge delivery the way bitchat clients expect
+                    let ack = BitchatPayload::Delivered { message_id };
+                    Self::spawn_send_bitchat_payload(ctx.client.clone(), identity, geohash.as_deref(), seal.pubkey, &ack, &ctx.status_tx)?;
+                    content
+                }
+                BitchatPayload::Delivered { .. } => {
+                    let _ = ctx.status_tx.send(format!("✓ Private message delivered to {}...", &sender[..8]));
+                    return Ok(());
+                }
+                BitchatPayload::ReadReceipt { .. } => {
+                    let _ = ctx.status_tx.send(format!("✓ Private message read by {}...", &sender[..8]));
+                    return Ok(());
+                }
+            }
+        } else {
+            rumor.content
+        };
+        
+        let message = Message {
+            channel: format!("dm:{}", sender),
+            nickname,
+            content,
+            timestamp: chrono::DateTime::from_timestamp(rumor.created_at.as_u64() as i64, 0)
+                .unwrap_or_else(chrono::Utc::now),
+            pubkey: Some(sender),
+            is_own: false,
+            is_private: true,
+            recipient_pubkey: Some(our_pubkey),
+            pow_difficulty: 0,
+            event_id: Some(event.id.to_hex()),
+            relay_url: Some(relay_url.to_string()),
+            is_mention: false,
+            send_id: None,
+        };
+        
+        let _ = ctx.message_tx.send(message);
+        
+        Ok(())
+    }
+    
+    /// Gift wrap a bitchat payload and publish it in the background
+    fn spawn_send_bitchat_payload(
+        client: Client,
+        identity: &Identity,
+        geohash: Option<&str>,
+        receiver: PublicKey,
+        payload: &BitchatPayload,
+        status_tx: &mpsc::UnboundedSender<String>,
+    ) -> Result<()> {
+        let sender_pubkey = match geohash {
+            Some(geohash) => identity.pubkey_for_geohash(geohash),
+            None => identity.pubkey.clone(),
+        };
+        let content = bitchat::encode_for_nostr(payload, &sender_pubkey)?;
+        let rumor = EventBuilder::new(Kind::SealedDirect, content, [Tag::public_key(receiver)]);
+        
+        Self::spawn_send_gift_wrap(client, identity.clone(), geohash.map(str::to_string), receiver, rumor, status_tx.clone());
+        Ok(())
+    }
+    
+    /// Gift wrap a rumor and publish it in the background (sealing may wait on a remote signer)
+    fn spawn_send_gift_wrap(
+        client: Client,
+        identity: Identity,
+        geohash: Option<String>,
+        receiver: PublicKey,
+        rumor: EventBuilder,
+        status_tx: mpsc::UnboundedSender<String>,
+    ) {
+        tokio::spawn(async move {
+            let gift_wrap = match identity.gift_wrap(geohash.as_deref(), &receiver, rumor).await {
+                Ok(gift_wrap) => gift_wrap,
+                Err(e) => {
+                    let _ = status_tx.send(format!("Failed to seal private message: {}", e));
+                    return;
+                }
+            };
+            
+            if timeout(Duration::from_secs(5), client.send_event(gift_wrap)).await.is_err() {
+                let _ = status_tx.send("Private message send timeout".to_string());
+            }
+        });
+    }
+    
+    /// Subscribe to NIP-17 gift wraps addressed to our pubkey and any per-geohash identities
+    async fn subscribe_to_private_messages(&mut self) {
+        if let Some(subscription_id) = self.dm_subscription.take() {
+            self.client.unsubscribe(subscription_id).await;
+        }
+        
+        let mut pubkeys = vec![self.identity.public_key()];
+        if let Ok(geohash_pubkeys) = self.geohash_pubkeys.lock() {
+            pubkeys.extend(geohash_pubkeys.keys().filter_map(|pk| PublicKey::from_hex(pk).ok()));
+        }
+        
+        let filter = Filter::new()
+            .kind(Kind::GiftWrap)
+            .pubkeys(pubkeys)
+            .since(Timestamp::now() - GIFT_WRAP_LOOKBACK_SECS);
+        
+        let subscription_id = self.client.subscribe(vec![filter], None).await;
+        self.dm_subscription = Some(subscription_id);
+    }
+    
     pub async fn subscribe_to_channel(&mut self, geohash: &str) -> Result<()> {
         // Create subscription filter first (for immediate subscription to default relays)
         let filter = Filter::new()
@@ -187,6 +535,18 @@ impl NostrClient {
         let subscription_id = self.client.subscribe(vec![filter], None).await;
         self.subscriptions.insert(geohash.to_string(), subscription_id);
         
+        // Listen for private messages sent to this channel's derived identity
+        if self.identity.uses_geohash_identities() {
+            let derived_pubkey = self.identity.pubkey_for_geohash(geohash);
+            let is_new = self.geohash_pubkeys
+                .lock()
+                .map(|mut pubkeys| pubkeys.insert(derived_pubkey, geohash.to_string()).is_none())
+                .unwrap_or(false);
+            if is_new {
+                self.subscribe_to_private_messages().await;
+            }
+        }
+        
         Ok(())
     }
     
@@ -198,40 +558,277 @@ impl NostrClient {
         Ok(())
     }
     
-    pub async fn send_message(&self, channel: &str, content: &str, nickname: &str) -> Result<()> {
-        let tags = vec![
-            Tag::parse(vec!["g", channel]).unwrap(),
-            Tag::parse(vec!["n", nickname]).unwrap(),
-            Tag::parse(vec!["t", "bitchatx"]).unwrap(),
-            Tag::parse(vec!["client", "bitchatx"]).unwrap(),
-        ];
-        
-        let event_builder = EventBuilder::new(
-            Kind::Ephemeral(20000),
-            content,
-            tags,
-        );
+    /// Publish a channel message, mining NIP-13 proof of work first if enabled
+    ///
+    /// Returns at once; the mining job lets the caller show progress and cancel it,
+    /// and the outcome arrives on `sent`.
+    pub async fn send_message(&self, channel: &str, content: &str, nickname: &str) -> Result<OutgoingMessage> {
+        let tags = Self::channel_message_tags(channel, nickname);
         
-        let event = self.identity.sign_event(event_builder)?;
+        let job = (self.pow_difficulty > 0).then(|| PowJob::new(channel, self.pow_difficulty));
         
-        // Send to all connected relays in background (fire-and-forget)
+        // Mine, sign and send to all connected relays in background;
+        // mining and a remote signer can both take a while
         let client = self.client.clone();
-        let status_tx = self.status_tx.clone();
+        let identity = self.identity.clone();
         let channel = channel.to_string();
+        let content = content.to_string();
+        let mining = job.clone();
+        let (sent_tx, sent) = oneshot::channel();
         tokio::spawn(async move {
-            match timeout(Duration::from_secs(5), client.send_event(event)).await {
-                Ok(_event_id) => {
-                    // Don't spam with "Message sent" notifications
-                }
-                Err(_) => {
-                    let _ = status_tx.send(format!("Message send timeout to #{}", channel));
+            let result = async {
+                let event_builder = match &mining {
+                    Some(job) => {
+                        let builder = Self::mine_event(job, &identity, &channel, &content, tags).await;
+                        builder.ok_or_else(|| {
+                            job.finish();
+                            anyhow!("proof of work cancelled")
+                        })?
+                    }
+                    None => EventBuilder::new(Kind::Ephemeral(20000), content, tags),
+                };
+                
+                // Signed with the channel's derived key when per-geohash identities are enabled
+                let signed = identity.sign_event_for_geohash(&channel, event_builder).await;
+                if let Some(job) = &mining {
+                    job.finish();
                 }
+                let event = signed.map_err(|e| anyhow!("cannot sign: {}", e))?;
+                
+                Self::send_to_first_relay(&client, event).await
             }
+            .await;
+            let _ = sent_tx.send(result);
         });
         
+        Ok(OutgoingMessage { pow: job, sent })
+    }
+    
+    // Send to every relay, done as soon as one accepts; Client::send_event would
+    // wait for the slowest relay
+    async fn send_to_first_relay(client: &Client, event: Event) -> Result<EventId> {
+        let event_id = event.id;
+        let (result_tx, mut results) = mpsc::unbounded_channel();
+        for relay in client.relays().await.into_values() {
+            let event = event.clone();
+            let result_tx = result_tx.clone();
+            tokio::spawn(async move {
+                let result = relay.send_event(event, RelaySendOptions::new().timeout(Some(SEND_TIMEOUT))).await;
+                let _ = result_tx.send(result);
+            });
+        }
+        drop(result_tx);
+        
+        let mut error = anyhow!("no relay connected");
+        while let Some(result) = results.recv().await {
+            match result {
+                Ok(_) => return Ok(event_id),
+                Err(e) => error = anyhow!("{}", e),
+            }
+        }
+        Err(error)
+    }
+    
+    /// Publish a channel message and wait for each relay's OK (for `bitchatx send`)
+    ///
+    /// Connects to the channel's georelays too. Returns how many relays accepted
+    /// the event out of how many were tried.
+    pub async fn publish_message(&mut self, channel: &str, content: &str, nickname: &str) -> Result<(usize, usize)> {
+        self.ensure_georelays_connected(channel).await?;
+        
+        let tags = Self::channel_message_tags(channel, nickname);
+        let event_builder = if self.pow_difficulty > 0 {
+            let job = PowJob::new(channel, self.pow_difficulty);
+            let builder = Self::mine_event(&job, &self.identity, channel, content, tags).await;
+            job.finish();
+            builder.ok_or_else(|| anyhow!("Proof of work failed, message not sent"))?
+        } else {
+            EventBuilder::new(Kind::Ephemeral(20000), content, tags)
+        };
+        let event = self.identity.sign_event_for_geohash(channel, event_builder).await?;
+        
+        // Every relay separately, so one slow relay doesn't hide the others' answers
+        let relays = self.client.relays().await;
+        let total = relays.len();
+        let sends: Vec<_> = relays
+            .into_values()
+            .map(|relay| {
+                let event = event.clone();
+                tokio::spawn(async move {
+                    relay.connect(Some(PUBLISH_TIMEOUT)).await;
+                    relay.send_event(event, RelaySendOptions::new().timeout(Some(PUBLISH_TIMEOUT))).await.is_ok()
+                })
+            })
+            .collect();
+        
+        let mut accepted = 0;
+        for send in sends {
+            if send.await.unwrap_or(false) {
+                accepted += 1;
+            }
+        }
+        Ok((accepted, total))
+    }
+    
+    fn channel_message_tags(channel: &str, nickname: &str) -> Vec<Tag> {
+        vec![
+            Tag::Geohash(channel.to_string()),
+            Self::nickname_tag(nickname),
+            Tag::Hashtag("bitchatx".to_string()),
+            Tag::Generic(TagKind::Custom("client".to_string()), vec!["bitchatx".to_string()]),
+        ]
+    }
+    
+    // bitchat's `n` tag with the sender's nickname
+    fn nickname_tag(nickname: &str) -> Tag {
+        Tag::Generic(TagKind::Custom("n".to_string()), vec![nickname.to_string()])
+    }
+    
+    /// Mine a nonce for a channel message on a blocking thread
+    ///
+    /// The id commits to pubkey and timestamp, so both are fixed up front and the
+    /// signer must sign the builder with the same ones. None when cancelled.
+    async fn mine_event(job: &PowJob, identity: &Identity, channel: &str, content: &str, mut tags: Vec<Tag>) -> Option<EventBuilder> {
+        let pubkey = PublicKey::from_hex(identity.pubkey_for_geohash(channel)).ok()?;
+        let created_at = Timestamp::now();
+        let kind = Kind::Ephemeral(20000);
+        
+        let nonce = {
+            let job = job.clone();
+            let tags = tags.clone();
+            let content = content.to_string();
+            tokio::task::spawn_blocking(move || job.mine(&pubkey, created_at, &kind, &tags, &content))
+                .await
+                .ok()??
+        };
+        
+        tags.push(nonce);
+        Some(EventBuilder::new(kind, content, tags).custom_created_at(created_at))
+    }
+    
+    /// Relays that have delivered forged or malformed events
+    pub fn relay_event_stats(&self) -> Vec<(String, RelayEventStats)> {
+        self.audit.snapshot()
+    }
+    
+    /// NIP-13 difficulty mined into outgoing channel messages, 0 = off
+    pub fn set_pow_difficulty(&mut self, difficulty: u8) {
+        self.pow_difficulty = difficulty;
+    }
+    
+    pub fn pow_difficulty(&self) -> u8 {
+        self.pow_difficulty
+    }
+    
+    /// Whether peers that haven't DMed us yet get bitchat packets instead of plain NIP-17
+    pub fn set_bitchat_first_contact(&mut self, enabled: bool) {
+        self.bitchat_first_contact = enabled;
+    }
+    
+    /// Send a NIP-17 private message, gift wrapped so only the recipient can read it
+    ///
+    /// Peers get the format they last DMed us in: plain text, which every NIP-17
+    /// client reads, or bitchat's embedded packets for bitchat Android/iOS. Peers we
+    /// haven't heard from get plain text unless bitchat first contact is enabled.
+    /// `channel_hint` is the geohash where the recipient was seen, whose identity we
+    /// use for the conversation when per-geohash identities are enabled.
+    pub async fn send_private_message(
+        &self,
+        recipient_pubkey: &str,
+        content: &str,
+        nickname: &str,
+        channel_hint: Option<&str>,
+    ) -> Result<()> {
+        let receiver = PublicKey::from_hex(recipient_pubkey)?;
+        
+        let geohash = self.dm_geohash_for(recipient_pubkey, channel_hint);
+        let geohash = geohash.as_deref();
+        
+        let known_format = self.dm_formats
+            .lock()
+            .ok()
+            .and_then(|formats| formats.get(recipient_pubkey).copied());
+        let format = known_format.unwrap_or(match self.bitchat_first_contact {
+            true => DmFormat::Bitchat,
+            false => DmFormat::Plain,
+        });
+        
+        if format == DmFormat::Bitchat {
+            let payload = BitchatPayload::PrivateMessage {
+                message_id: bitchat::new_message_id(),
+                content: content.to_string(),
+            };
+            return Self::spawn_send_bitchat_payload(self.client.clone(), &self.identity, geohash, receiver, &payload, &self.status_tx);
+        }
+        
+        let tags = vec![
+            Tag::public_key(receiver),
+            Self::nickname_tag(nickname),
+        ];
+        let rumor = EventBuilder::new(Kind::SealedDirect, content, tags);
+        
+        // Send to all connected relays in background (fire-and-forget)
+        Self::spawn_send_gift_wrap(self.client.clone(), self.identity.clone(), geohash.map(str::to_string), receiver, rumor, self.status_tx.clone());
+        
         Ok(())
     }
     
+    /// Geohash identity used for a private chat, remembering the first one chosen
+    fn dm_geohash_for(&self, recipient_pubkey: &str, channel_hint: Option<&str>) -> Option<String> {
+        if !self.identity.uses_geohash_identities() {
+            return None;
+        }
+        
+        let mut dm_geohashes = self.dm_geohashes.lock().ok()?;
+        if let Some(geohash) = dm_geohashes.get(recipient_pubkey) {
+            return Some(geohash.clone());
+        }
+        
+        let geohash = channel_hint?.to_string();
+        dm_geohashes.insert(recipient_pubkey.to_string(), geohash.clone());
+        Some(geohash)
+    }
+    
+    /// NIP-05 verifier fetching profiles through our relay connections
+    pub fn nip05_verifier(&self, resolver: Arc<dyn Nip05Resolver>) -> Nip05Verifier {
+        Nip05Verifier::new(self.client.clone(), resolver)
+    }
+    
+    /// Latest kind 0 profile of our main identity, kept as raw JSON so fields
+    /// we don't know about survive an edit
+    pub async fn fetch_own_profile(&self) -> Result<Option<serde_json::Map<String, serde_json::Value>>> {
+        let filter = Filter::new()
+            .author(self.identity.public_key())
+            .kind(Kind::Metadata)
+            .limit(1);
+        
+        let events = self.client.get_events_of(vec![filter], Some(PROFILE_FETCH_TIMEOUT)).await?;
+        
+        // Relays may hold different versions; the newest one wins
+        match events.iter().max_by_key(|event| event.created_at()) {
+            Some(event) => match serde_json::from_str(event.content())? {
+                serde_json::Value::Object(profile) => Ok(Some(profile)),
+                _ => Err(anyhow!("Existing profile is not a JSON object")),
+            },
+            // Also what a timeout looks like, so callers must not take it as "no profile"
+            None => Ok(None),
+        }
+    }
+    
+    /// Publish a kind 0 profile for our main identity (never a per-geohash key)
+    pub async fn publish_profile(&self, profile: &serde_json::Map<String, serde_json::Value>) -> Result<()> {
+        let content = serde_json::to_string(profile)?;
+        let event = self.identity.sign_event(EventBuilder::new(Kind::Metadata, content, [])).await?;
+        
+        match timeout(Duration::from_secs(5), self.client.send_event(event)).await {
+            Ok(result) => {
+                result?;
+                Ok(())
+            }
+            Err(_) => Err(anyhow!("Profile publish timeout")),
+        }
+    }
+    
     /// Ensure that georelays are connected for a specific geohash
     async fn ensure_georelays_connected(&mut self, geohash: &str) -> Result<()> {
         // Get closest relays for this geohash
@@ -239,11 +836,11 @@ impl NostrClient {
         
         // Add geohash-specific relays to client
         for relay_url in &georelay_urls {
-            // Only add if not already connected
-            if !self.connected_relays.contains(relay_url) {
-                match self.client.add_relay(relay_url.clone()).await {
+            // Only add if not already connected, and never one the user removed
+            if !self.connected_relays.contains_key(relay_url) && !self.removed_relays.contains(relay_url) {
+                match self.relay_supervisor.add(relay_url).await {
                     Ok(_) => {
-                        self.connected_relays.insert(relay_url.clone());
+                        self.connected_relays.insert(relay_url.clone(), RelaySource::Geo);
                         let total_relays = self.connected_relays.len();
                         let _ = self.status_tx.send(format!("Connected to georelay: {} (total: {})", relay_url, total_relays));
                     }
@@ -267,13 +864,80 @@ impl NostrClient {
         self.connected_relays.len()
     }
     
-    /// Get the current relay count including georelays
+    /// Get relay connection statistics: (default, geo, manual)
+    pub fn get_relay_stats(&self) -> (usize, usize, usize) {
+        let count = |source: RelaySource| self.connected_relays.values().filter(|s| **s == source).count();
+        (count(RelaySource::Default), count(RelaySource::Geo), count(RelaySource::Manual))
+    }
     
-    /// Get relay connection statistics
-    pub fn get_relay_stats(&self) -> (usize, usize) {
-        let total_connected = self.connected_relays.len();
-        let default_count = DEFAULT_RELAYS.len();
-        let georelay_count = total_connected.saturating_sub(default_count);
-        (default_count, georelay_count)
+    /// (connected, still connecting, total) relays, for the connection state display
+    pub fn relay_health(&self) -> (usize, usize, usize) {
+        self.relay_supervisor.summary()
+    }
+    
+    /// Every relay in the pool with its live status, sorted by source then URL
+    pub async fn relay_list(&self) -> Vec<RelayEntry> {
+        let mut entries = Vec::new();
+        for (url, source) in &self.connected_relays {
+            let status = self.relay_supervisor
+                .state(url)
+                .map(|state| state.to_string())
+                .unwrap_or_else(|| "Unknown".to_string());
+            let latency = match self.client.relay(url.as_str()).await {
+                Ok(relay) => relay.stats().latency().await,
+                Err(_) => None,
+            };
+            entries.push(RelayEntry { url: url.clone(), source: *source, status, latency });
+        }
+        entries.sort_by(|a, b| (a.source as u8, &a.url).cmp(&(b.source as u8, &b.url)));
+        entries
+    }
+    
+    /// Add a relay by hand
+    ///
+    /// It picks up every active subscription as it connects. The caller keeps
+    /// config.toml's relay list in step.
+    pub async fn add_manual_relay(&mut self, url: &str) -> Result<String> {
+        let url = relay_list::normalize_relay_url(url)?;
+        if self.connected_relays.get(&url) == Some(&RelaySource::Manual) {
+            return Err(anyhow!("{} is already in the relay list", url));
+        }
+        
+        if !self.connected_relays.contains_key(&url) {
+            self.relay_supervisor.add(&url).await?;
+            self.client.connect_relay(url.as_str()).await?;
+        }
+        self.connected_relays.insert(url.clone(), RelaySource::Manual);
+        self.removed_relays.retain(|removed| removed != &url);
+        Ok(url)
+    }
+    
+    /// Drop a relay from the pool; default and geo relays aren't re-added as georelays
+    pub async fn remove_relay(&mut self, url: &str) -> Result<String> {
+        let url = relay_list::normalize_relay_url(url)?;
+        let Some(source) = self.connected_relays.remove(&url) else {
+            return Err(anyhow!("{} is not in the relay list", url));
+        };
+        
+        self.relay_supervisor.remove(&url).await?;
+        
+        if source != RelaySource::Manual && !self.removed_relays.contains(&url) {
+            self.removed_relays.push(url.clone());
+        }
+        Ok(url)
+    }
+    
+    /// Relays /relay remove dropped, for config.toml's `removed_relays`
+    pub fn removed_relays(&self) -> &[String] {
+        &self.removed_relays
+    }
+    
+    /// Fetch a relay's NIP-11 information document
+    pub async fn relay_info(&self, url: &str) -> Result<RelayInformationDocument> {
+        let url = Url::parse(&relay_list::normalize_relay_url(url)?)?;
+        match timeout(RELAY_INFO_TIMEOUT, RelayInformationDocument::get(url, None)).await {
+            Ok(document) => Ok(document?),
+            Err(_) => Err(anyhow!("Relay information request timed out")),
+        }
     }
 }
\ No newline at end of file
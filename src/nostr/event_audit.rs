@@ -0,0 +1,248 @@
+use nostr_sdk::prelude::*;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex, OnceLock};
+use tracing::field::{Field, Visit};
+use tracing::{span, Level, Metadata};
+
+/// Invalid events a relay has delivered
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RelayEventStats {
+    /// Id or signature doesn't match the content, i.e. someone else's name on it
+    pub forged: u64,
+    /// Unparseable, or missing what our subscription filtered on
+    pub malformed: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Rejection {
+    Forged,
+    Malformed,
+}
+
+/// Per-relay tally of forged and malformed events
+///
+/// The relay pool checks every event's id and signature before we see it and
+/// only logs the ones it drops, so the tally shared by all clients reads that
+/// log (see `shared`). Our own checks add what the pool can't see: forged seals
+/// and DMs claiming someone else's name inside gift wraps, and channel events
+/// missing what we subscribed to.
+#[derive(Debug, Clone, Default)]
+pub struct EventAudit {
+    stats: Arc<Mutex<HashMap<String, RelayEventStats>>>,
+}
+
+impl EventAudit {
+    /// The process-wide tally, which also counts the forged events the relay pool drops
+    ///
+    /// The first call installs a tracing subscriber that picks those out of the
+    /// pool's error log; nothing else in the program uses tracing.
+    pub fn shared() -> Self {
+        static SHARED: OnceLock<EventAudit> = OnceLock::new();
+        SHARED
+            .get_or_init(|| {
+                let audit = EventAudit::default();
+                let _ = tracing::subscriber::set_global_default(PoolLog { audit: audit.clone() });
+                audit
+            })
+            .clone()
+    }
+
+    /// Check the event id and signature, counting it against the relay if they don't hold
+    pub fn verify(&self, relay_url: &Url, event: &Event) -> bool {
+        match event.verify() {
+            Ok(()) => true,
+            Err(_) => {
+                self.record(relay_url.as_str(), Rejection::Forged);
+                false
+            }
+        }
+    }
+
+    pub fn record(&self, relay_url: &str, rejection: Rejection) {
+        if let Ok(mut stats) = self.stats.lock() {
+            let entry = stats.entry(relay_url.to_string()).or_default();
+            match rejection {
+                Rejection::Forged => entry.forged += 1,
+                Rejection::Malformed => entry.malformed += 1,
+            }
+        }
+    }
+
+    /// Relays that delivered at least one invalid event, sorted by URL
+    pub fn snapshot(&self) -> Vec<(String, RelayEventStats)> {
+        let mut stats: Vec<(String, RelayEventStats)> = self.stats
+            .lock()
+            .map(|stats| stats.iter().map(|(url, stats)| (url.clone(), *stats)).collect())
+            .unwrap_or_default();
+        stats.sort_by(|a, b| a.0.cmp(&b.0));
+        stats
+    }
+}
+
+// Feeds the relay pool's "can't handle this event" errors into an EventAudit
+struct PoolLog {
+    audit: EventAudit,
+}
+
+impl tracing::Subscriber for PoolLog {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        *metadata.level() == Level::ERROR && metadata.target().starts_with("nostr_relay_pool")
+    }
+
+    fn new_span(&self, _: &span::Attributes<'_>) -> span::Id {
+        span::Id::from_u64(1)
+    }
+
+    fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+
+    fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+    fn event(&self, event: &tracing::Event<'_>) {
+        let mut message = MessageField(String::new());
+        event.record(&mut message);
+        if let Some(relay_url) = forged_event_relay(&message.0) {
+            self.audit.record(relay_url, Rejection::Forged);
+        }
+    }
+
+    fn enter(&self, _: &span::Id) {}
+
+    fn exit(&self, _: &span::Id) {}
+}
+
+struct MessageField(String);
+
+impl Visit for MessageField {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.0 = format!("{:?}", value);
+        }
+    }
+}
+
+// The relay behind "Impossible to handle relay message from <url>: <error>" when
+// the error is a failed id or signature check
+fn forged_event_relay(message: &str) -> Option<&str> {
+    let (relay_url, error) = message
+        .strip_prefix("Impossible to handle relay message from ")?
+        .split_once(": ")?;
+    matches!(error, "Invalid signature" | "Invalid event id").then_some(relay_url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn relay() -> Url {
+        Url::parse("wss://relay.example.com").unwrap()
+    }
+
+    fn signed_event() -> Event {
+        EventBuilder::new(Kind::Seal, "sealed", []).to_event(&Keys::generate()).unwrap()
+    }
+
+    #[test]
+    fn accepts_valid_event() {
+        let audit = EventAudit::default();
+        assert!(audit.verify(&relay(), &signed_event()));
+        assert!(audit.snapshot().is_empty());
+    }
+
+    #[test]
+    fn counts_tampered_event_as_forged() {
+        let audit = EventAudit::default();
+        let json = signed_event().as_json().replace("\"sealed\"", "\"tampered\"");
+        let tampered = Event::from_json(json).unwrap();
+
+        assert!(!audit.verify(&relay(), &tampered));
+        let stats = audit.snapshot();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].0, relay().as_str());
+        assert_eq!(stats[0].1.forged, 1);
+        assert_eq!(stats[0].1.malformed, 0);
+    }
+
+    #[test]
+    fn picks_forged_events_out_of_the_pool_log() {
+        let relay = "wss://relay.example.com/";
+        assert_eq!(forged_event_relay(&format!("Impossible to handle relay message from {}: Invalid signature", relay)), Some(relay));
+        assert_eq!(forged_event_relay(&format!("Impossible to handle relay message from {}: Invalid event id", relay)), Some(relay));
+        assert_eq!(forged_event_relay(&format!("Impossible to handle relay message from {}: Event expired", relay)), None);
+        assert_eq!(forged_event_relay("Impossible to connect to relay"), None);
+    }
+
+    // Answers the first REQ with a channel message whose content was changed after signing
+    async fn start_forging_relay() -> Url {
+        use futures_util::{SinkExt, StreamExt};
+        use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!("ws://{}", listener.local_addr().unwrap())).unwrap();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let Ok(socket) = tokio_tungstenite::accept_async(stream).await else {
+                    continue;
+                };
+                let (mut sink, mut messages) = socket.split();
+                let subscription_id = loop {
+                    match messages.next().await {
+                        Some(Ok(WsMessage::Text(text))) => {
+                            if let Ok(ClientMessage::Req { subscription_id, .. }) = ClientMessage::from_json(text) {
+                                break subscription_id;
+                            }
+                        }
+                        Some(Ok(_)) => {}
+                        _ => return,
+                    }
+                };
+                let event = EventBuilder::new(Kind::Ephemeral(20000), "hello", [Tag::Geohash("dr5reg".to_string())])
+                    .to_event(&Keys::generate())
+                    .unwrap()
+                    .as_json()
+                    .replace("\"hello\"", "\"send sats here\"");
+                let forged = format!("[\"EVENT\",\"{}\",{}]", subscription_id, event);
+                let _ = sink.send(WsMessage::Text(forged)).await;
+                // Keep the connection open
+                while messages.next().await.is_some() {}
+            }
+        });
+        url
+    }
+
+    #[tokio::test]
+    async fn counts_channel_events_the_relay_pool_drops() {
+        let audit = EventAudit::shared();
+        let relay = start_forging_relay().await;
+        let client = Client::default();
+        client.add_relay(relay.as_str()).await.unwrap();
+        client.connect().await;
+        client.subscribe(vec![Filter::new().kind(Kind::Ephemeral(20000))], None).await;
+
+        let forged = || {
+            audit.snapshot()
+                .into_iter()
+                .find(|(url, _)| url == relay.as_str())
+                .map(|(_, stats)| stats.forged)
+        };
+        for _ in 0..50 {
+            if forged().is_some() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        assert_eq!(forged(), Some(1));
+    }
+
+    #[test]
+    fn counts_per_relay() {
+        let audit = EventAudit::default();
+        audit.record("wss://b.example.com", Rejection::Malformed);
+        audit.record("wss://a.example.com", Rejection::Forged);
+        audit.record("wss://b.example.com", Rejection::Malformed);
+
+        let stats = audit.snapshot();
+        assert_eq!(stats[0].0, "wss://a.example.com");
+        assert_eq!(stats[1].1.malformed, 2);
+    }
+}
@@ -1,13 +1,110 @@
 use anyhow::{anyhow, Result};
+use nostr::hashes::hmac::{Hmac, HmacEngine};
+use nostr::hashes::{sha256, Hash, HashEngine};
 use nostr_sdk::prelude::*;
-use rand::{thread_rng, Rng};
+use rand::{thread_rng, Rng, RngCore};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::keystore::{self, Keystore};
+use super::remote_signer::RemoteSigner;
 
 #[derive(Debug, Clone)]
 pub struct Identity {
-    pub keys: Keys,
+    signer: Signer,
     pub pubkey: String,
     pub nickname: String,
     pub is_ephemeral: bool,
+    // When set, every geohash channel is signed with its own key derived from this seed
+    pub geohash_seed: Option<DeviceSeed>,
+}
+
+/// Where the identity's secret key lives
+#[derive(Debug, Clone)]
+enum Signer {
+    Local(Keys),
+    // NIP-46 bunker; the secret key never leaves the signer
+    Remote(Arc<RemoteSigner>),
+}
+
+impl Signer {
+    fn public_key(&self) -> PublicKey {
+        match self {
+            Signer::Local(keys) => keys.public_key(),
+            Signer::Remote(remote) => remote.public_key(),
+        }
+    }
+}
+
+/// Per-device secret used to derive unlinkable per-geohash keys (as bitchat mobile does)
+#[derive(Clone)]
+pub struct DeviceSeed([u8; 32]);
+
+impl fmt::Debug for DeviceSeed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Never print the seed itself
+        f.write_str("DeviceSeed(..)")
+    }
+}
+
+impl DeviceSeed {
+    /// Load the device seed from the config dir, creating it on first use
+    pub fn load_or_create() -> Result<Self> {
+        let path = Self::path();
+        
+        // Only a missing seed is replaced; an unreadable one must not silently
+        // swap every geohash identity for a new set
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => return Self::parse(contents.trim(), &path),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(anyhow!("Failed to read device seed at {}: {}", path.display(), e)),
+        }
+        
+        let mut seed = [0u8; 32];
+        thread_rng().fill_bytes(&mut seed);
+        
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let encoded: String = seed.iter().map(|b| format!("{:02x}", b)).collect();
+        keystore::write_private_file(&path, encoded.as_bytes())?;
+        
+        Ok(Self(seed))
+    }
+    
+    fn parse(contents: &str, path: &Path) -> Result<Self> {
+        let mut seed = [0u8; 32];
+        if contents.len() != 64 {
+            return Err(anyhow!("Corrupt device seed at {}", path.display()));
+        }
+        for (i, byte) in seed.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&contents[i * 2..i * 2 + 2], 16)
+                .map_err(|_| anyhow!("Corrupt device seed at {}", path.display()))?;
+        }
+        Ok(Self(seed))
+    }
+    
+    fn path() -> PathBuf {
+        keystore::config_dir().join("device_seed")
+    }
+    
+    /// Derive the signing key for a geohash: HMAC-SHA256(seed, geohash || iteration)
+    fn derive_keys(&self, geohash: &str) -> Option<Keys> {
+        // Retry with the next iteration in the (astronomically unlikely) case the
+        // HMAC output is not a valid secp256k1 secret key
+        for iteration in 0u32..10 {
+            let mut engine = HmacEngine::<sha256::Hash>::new(&self.0);
+            engine.input(geohash.as_bytes());
+            engine.input(&iteration.to_be_bytes());
+            let code = Hmac::<sha256::Hash>::from_engine(engine);
+            
+            if let Ok(secret_key) = SecretKey::from_slice(code.as_byte_array()) {
+                return Some(Keys::new(secret_key));
+            }
+        }
+        None
+    }
 }
 
 impl Identity {
@@ -18,10 +115,11 @@ impl Identity {
         let nickname = generate_random_nickname();
         
         Self {
-            keys,
+            signer: Signer::Local(keys),
             pubkey,
             nickname,
             is_ephemeral: true,
+            geohash_seed: None,
         }
     }
     
@@ -29,9 +127,27 @@ impl Identity {
     pub async fn from_nsec(nsec: &str) -> Result<Self> {
         let secret_key = SecretKey::from_bech32(nsec)
             .map_err(|_| anyhow!("Invalid nsec format"))?;
-        let keys = Keys::new(secret_key);
-        let pubkey_hex = keys.public_key().to_hex();
-        let pubkey = keys.public_key();
+        Self::from_secret_key(secret_key).await
+    }
+    
+    /// Create identity from the encrypted NIP-49 keystore and fetch profile
+    pub async fn from_keystore(keystore: &Keystore, passphrase: &str) -> Result<Self> {
+        let secret_key = keystore.unlock(passphrase)?;
+        Self::from_secret_key(secret_key).await
+    }
+    
+    /// Create identity backed by a NIP-46 remote signer and fetch profile
+    pub async fn from_remote_signer(remote: RemoteSigner) -> Result<Self> {
+        Self::with_signer(Signer::Remote(Arc::new(remote))).await
+    }
+    
+    async fn from_secret_key(secret_key: SecretKey) -> Result<Self> {
+        Self::with_signer(Signer::Local(Keys::new(secret_key))).await
+    }
+    
+    async fn with_signer(signer: Signer) -> Result<Self> {
+        let pubkey = signer.public_key();
+        let pubkey_hex = pubkey.to_hex();
         
         // Try to fetch profile metadata from Nostr relays
         let nickname = match Self::fetch_profile_name(&pubkey).await {
@@ -43,13 +159,56 @@ impl Identity {
         };
         
         Ok(Self {
-            keys,
+            signer,
             pubkey: pubkey_hex,
             nickname,
             is_ephemeral: false,
+            geohash_seed: None,
         })
     }
     
+    /// Switch to signing each geohash channel with its own derived key
+    pub fn enable_geohash_identities(&mut self) -> Result<()> {
+        self.geohash_seed = Some(DeviceSeed::load_or_create()?);
+        Ok(())
+    }
+    
+    pub fn uses_geohash_identities(&self) -> bool {
+        self.geohash_seed.is_some()
+    }
+    
+    /// Whether signing is delegated to a NIP-46 remote signer
+    pub fn uses_remote_signer(&self) -> bool {
+        matches!(self.signer, Signer::Remote(_))
+    }
+    
+    /// Our main public key
+    pub fn public_key(&self) -> PublicKey {
+        self.signer.public_key()
+    }
+    
+    /// Signer for the nostr-sdk client; remote signing goes through `RemoteSigner` directly
+    pub fn nostr_signer(&self) -> Option<NostrSigner> {
+        match &self.signer {
+            Signer::Local(keys) => Some(keys.clone().into()),
+            Signer::Remote(_) => None,
+        }
+    }
+    
+    /// Signer used in a geohash channel (derived locally when per-geohash identities are enabled)
+    fn signer_for_geohash(&self, geohash: &str) -> Signer {
+        self.geohash_seed
+            .as_ref()
+            .and_then(|seed| seed.derive_keys(geohash))
+            .map(Signer::Local)
+            .unwrap_or_else(|| self.signer.clone())
+    }
+    
+    /// Hex pubkey we appear as in a geohash channel
+    pub fn pubkey_for_geohash(&self, geohash: &str) -> String {
+        self.signer_for_geohash(geohash).public_key().to_hex()
+    }
+    
     /// Fetch profile name from Nostr relays
     async fn fetch_profile_name(pubkey: &PublicKey) -> Result<String> {
         // Create a temporary client to fetch profile metadata
@@ -99,8 +258,69 @@ impl Identity {
     
     
     /// Sign an event
-    pub fn sign_event(&self, event_builder: EventBuilder) -> Result<Event> {
-        Ok(event_builder.to_event(&self.keys)?)
+    pub async fn sign_event(&self, event_builder: EventBuilder) -> Result<Event> {
+        Self::sign_with(&self.signer, event_builder).await
+    }
+    
+    /// Sign an event for a geohash channel with that channel's key
+    pub async fn sign_event_for_geohash(&self, geohash: &str, event_builder: EventBuilder) -> Result<Event> {
+        Self::sign_with(&self.signer_for_geohash(geohash), event_builder).await
+    }
+    
+    async fn sign_with(signer: &Signer, event_builder: EventBuilder) -> Result<Event> {
+        match signer {
+            Signer::Local(keys) => Ok(event_builder.to_event(keys)?),
+            Signer::Remote(remote) => {
+                remote.sign_event(event_builder.to_unsigned_event(remote.public_key())).await
+            }
+        }
+    }
+    
+    /// Seal and gift wrap a rumor for a single recipient (NIP-59)
+    ///
+    /// Private chats that started in a geohash channel are sealed with that channel's key.
+    pub async fn gift_wrap(&self, geohash: Option<&str>, receiver: &PublicKey, rumor: EventBuilder) -> Result<Event> {
+        let signer = self.signer_for_dm(geohash);
+        let rumor = rumor.to_unsigned_event(signer.public_key());
+        
+        match &signer {
+            Signer::Local(keys) => Ok(EventBuilder::gift_wrap(keys, receiver, rumor, None)?),
+            Signer::Remote(remote) => {
+                // The signer encrypts and signs the seal; only the outer wrap uses a throwaway key
+                let content = remote.nip44_encrypt(receiver, &rumor.as_json()).await?;
+                let seal = Self::sign_with(&signer, EventBuilder::new(Kind::Seal, content, [])).await?;
+                Ok(EventBuilder::gift_wrap_from_seal(receiver, &seal, None)?)
+            }
+        }
+    }
+    
+    /// Decrypt the seal inside a gift wrap addressed to us
+    ///
+    /// The seal isn't verified here: relays only check the outer wrap, so the
+    /// caller checks the seal's signature before trusting its author.
+    pub async fn open_gift_wrap(&self, geohash: Option<&str>, gift_wrap: &Event) -> Result<Event> {
+        let seal = self.nip44_decrypt(geohash, &gift_wrap.pubkey, &gift_wrap.content).await?;
+        Ok(Event::from_json(seal)?)
+    }
+    
+    /// Decrypt the rumor inside a verified seal
+    pub async fn open_seal(&self, geohash: Option<&str>, seal: &Event) -> Result<UnsignedEvent> {
+        let rumor = self.nip44_decrypt(geohash, &seal.pubkey, &seal.content).await?;
+        Ok(UnsignedEvent::from_json(rumor)?)
+    }
+    
+    async fn nip44_decrypt(&self, geohash: Option<&str>, sender: &PublicKey, payload: &str) -> Result<String> {
+        match self.signer_for_dm(geohash) {
+            Signer::Local(keys) => Ok(nip44::decrypt(keys.secret_key()?, sender, payload)?),
+            Signer::Remote(remote) => remote.nip44_decrypt(sender, payload).await,
+        }
+    }
+    
+    fn signer_for_dm(&self, geohash: Option<&str>) -> Signer {
+        match geohash {
+            Some(geohash) => self.signer_for_geohash(geohash),
+            None => self.signer.clone(),
+        }
     }
 }
 
@@ -0,0 +1,103 @@
+use anyhow::{anyhow, Result};
+use nostr::nips::nip49::{EncryptedSecretKey, KeySecurity};
+use nostr_sdk::prelude::*;
+use std::path::{Path, PathBuf};
+
+// scrypt work factor recommended by NIP-49 (2^16 rounds)
+const SCRYPT_LOG_N: u8 = 16;
+
+/// Encrypted on-disk secret key in NIP-49 `ncryptsec` format
+#[derive(Debug, Clone)]
+pub struct Keystore {
+    path: PathBuf,
+}
+
+impl Keystore {
+    /// Keystore at the default location (~/.config/bitchatx/keystore.ncryptsec)
+    pub fn open_default() -> Self {
+        Self {
+            path: config_dir().join("keystore.ncryptsec"),
+        }
+    }
+    
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    
+    pub fn exists(&self) -> bool {
+        self.path.exists()
+    }
+    
+    /// Load the stored `ncryptsec` without decrypting it
+    pub fn load(&self) -> Result<EncryptedSecretKey> {
+        let contents = std::fs::read_to_string(&self.path)
+            .map_err(|e| anyhow!("Cannot read keystore {}: {}", self.path.display(), e))?;
+        EncryptedSecretKey::from_bech32(contents.trim())
+            .map_err(|_| anyhow!("Keystore {} is not a valid ncryptsec", self.path.display()))
+    }
+    
+    /// Decrypt the stored secret key with the passphrase
+    pub fn unlock(&self, passphrase: &str) -> Result<SecretKey> {
+        self.load()?
+            .to_secret_key(passphrase)
+            .map_err(|_| anyhow!("Wrong passphrase or corrupt keystore"))
+    }
+    
+    /// Refuse to replace an existing keystore unless asked to
+    pub fn ensure_writable(&self, overwrite: bool) -> Result<()> {
+        if self.exists() && !overwrite {
+            return Err(anyhow!(
+                "Keystore {} already exists (use --force to replace it)",
+                self.path.display()
+            ));
+        }
+        Ok(())
+    }
+    
+    /// Encrypt and store a secret key
+    pub fn save(&self, secret_key: &SecretKey, passphrase: &str, key_security: KeySecurity, overwrite: bool) -> Result<EncryptedSecretKey> {
+        self.ensure_writable(overwrite)?;
+        
+        let encrypted = EncryptedSecretKey::new(secret_key, passphrase, SCRYPT_LOG_N, key_security)?;
+        
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        write_private_file(&self.path, encrypted.to_bech32()?.as_bytes())?;
+        
+        Ok(encrypted)
+    }
+}
+
+/// BitchatX configuration directory (~/.config/bitchatx)
+pub fn config_dir() -> PathBuf {
+    dirs::config_dir()
+        .or_else(|| dirs::home_dir().map(|h| h.join(".config")))
+        .unwrap_or_else(|| PathBuf::from(".config"))
+        .join("bitchatx")
+}
+
+/// BitchatX data directory (~/.local/share/bitchatx)
+pub fn data_dir() -> PathBuf {
+    dirs::data_dir()
+        .or_else(|| dirs::home_dir().map(|h| h.join(".local").join("share")))
+        .unwrap_or_else(|| PathBuf::from(".local/share"))
+        .join("bitchatx")
+}
+
+/// Write a file readable only by the current user
+pub fn write_private_file(path: &Path, contents: &[u8]) -> Result<()> {
+    use std::io::Write;
+    
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    
+    let mut file = options.open(path)?;
+    file.write_all(contents)?;
+    Ok(())
+}
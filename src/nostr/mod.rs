@@ -1,18 +1,20 @@
-use serde::{Deserialize, Serialize};
-
-
 pub use identity::Identity;
-pub use client::NostrClient;
+pub use keystore::{config_dir, data_dir, write_private_file, Keystore};
+pub use nip05::{HttpResolver, Nip05Resolver, Nip05Status, Nip05Verifier};
+pub use pow::PowJob;
+pub use relay_list::normalize_relay_url;
+pub use remote_signer::RemoteSigner;
+pub use client::{NostrClient, DEFAULT_RELAYS};
 pub use georelay_directory::GeoRelayDirectory;
 
+mod bitchat;
+mod event_audit;
 mod identity;
+mod keystore;
+mod nip05;
+mod pow;
+mod relay_health;
+mod relay_list;
+mod remote_signer;
 mod client;
 mod georelay_directory;
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct EphemeralMessage {
-    pub channel: String,
-    pub content: String,
-    pub nickname: String,
-    pub timestamp: chrono::DateTime<chrono::Utc>,
-}
\ No newline at end of file
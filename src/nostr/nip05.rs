@@ -0,0 +1,362 @@
+use anyhow::{anyhow, Result};
+use nostr_sdk::prelude::*;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+// Re-check verified identifiers hourly, failures sooner in case they were fixed
+const VERIFIED_TTL: Duration = Duration::from_secs(60 * 60);
+const UNVERIFIED_TTL: Duration = Duration::from_secs(10 * 60);
+// A lookup still pending after this was lost (e.g. the relay never answered), try again
+const PENDING_TTL: Duration = Duration::from_secs(30);
+
+// Collect participants for a moment so their profiles are fetched in one request
+const BATCH_DELAY: Duration = Duration::from_secs(1);
+const MAX_BATCH: usize = 100;
+
+const PROFILE_FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Nip05Status {
+    Pending,
+    /// The identifier's domain vouches for this pubkey
+    Verified(String),
+    /// An identifier is claimed but its domain doesn't confirm it
+    Unverified(String),
+    /// No NIP-05 identifier in the profile (or no profile at all)
+    None,
+}
+
+pub type LookupFuture<'a> = Pin<Box<dyn Future<Output = Result<Option<PublicKey>>> + Send + 'a>>;
+
+/// Resolves `name@domain` to the pubkey the domain lists for it
+pub trait Nip05Resolver: Send + Sync {
+    fn lookup<'a>(&'a self, name: &'a str, domain: &'a str) -> LookupFuture<'a>;
+}
+
+/// Resolver using `https://<domain>/.well-known/nostr.json?name=<name>`
+pub struct HttpResolver {
+    http: reqwest::Client,
+    // Send every lookup to this server instead, e.g. a local stand-in for testing
+    base_url: Option<String>,
+}
+
+impl HttpResolver {
+    pub fn new(base_url: Option<String>) -> Result<Self> {
+        // NIP-05 requires fetchers to ignore redirects
+        let http = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .timeout(LOOKUP_TIMEOUT)
+            .build()?;
+
+        Ok(Self {
+            http,
+            base_url: base_url.map(|url| url.trim_end_matches('/').to_string()),
+        })
+    }
+}
+
+impl Nip05Resolver for HttpResolver {
+    fn lookup<'a>(&'a self, name: &'a str, domain: &'a str) -> LookupFuture<'a> {
+        Box::pin(async move {
+            let base = match &self.base_url {
+                Some(base) => base.clone(),
+                None => format!("https://{}", domain),
+            };
+            let url = format!("{}/.well-known/nostr.json", base);
+
+            let response = self.http.get(&url).query(&[("name", name)]).send().await?;
+            if !response.status().is_success() {
+                return Err(anyhow!("{} returned {}", url, response.status()));
+            }
+
+            let document: serde_json::Value = response.json().await?;
+            Ok(document
+                .get("names")
+                .and_then(|names| names.get(name))
+                .and_then(|pubkey| pubkey.as_str())
+                .and_then(|pubkey| PublicKey::from_hex(pubkey).ok()))
+        })
+    }
+}
+
+/// Fetches participants' profiles on demand and verifies their NIP-05 identifiers
+#[derive(Clone)]
+pub struct Nip05Verifier {
+    client: Client,
+    resolver: Arc<dyn Nip05Resolver>,
+    cache: Arc<Mutex<HashMap<String, (Nip05Status, Instant)>>>,
+    queue: mpsc::UnboundedSender<String>,
+}
+
+impl Nip05Verifier {
+    pub fn new(client: Client, resolver: Arc<dyn Nip05Resolver>) -> Self {
+        let (queue, queue_rx) = mpsc::unbounded_channel();
+        let verifier = Self {
+            client,
+            resolver,
+            cache: Arc::new(Mutex::new(HashMap::new())),
+            queue,
+        };
+
+        tokio::spawn(verifier.clone().run_batches(queue_rx));
+        verifier
+    }
+
+    /// Last known status of a pubkey, if it has been checked
+    pub fn status(&self, pubkey: &str) -> Option<Nip05Status> {
+        self.cache.lock().ok()?.get(pubkey).map(|(status, _)| status.clone())
+    }
+
+    /// Whether the pubkey has a verified identifier (cached, never blocks)
+    pub fn is_verified(&self, pubkey: &str) -> bool {
+        matches!(self.status(pubkey), Some(Nip05Status::Verified(_)))
+    }
+
+    /// Queue a pubkey for verification unless it has been checked recently
+    pub fn request(&self, pubkey: &str) {
+        if self.is_fresh(pubkey) {
+            return;
+        }
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.insert(pubkey.to_string(), (Nip05Status::Pending, Instant::now()));
+        }
+        let _ = self.queue.send(pubkey.to_string());
+    }
+
+    /// Fetch the profile and verify right away (for /whois)
+    pub async fn verify(&self, pubkey: &str) -> Nip05Status {
+        if self.is_fresh(pubkey) {
+            if let Some(status) = self.status(pubkey).filter(|s| *s != Nip05Status::Pending) {
+                return status;
+            }
+        }
+
+        let identifiers = self.fetch_identifiers(&[pubkey.to_string()]).await;
+        let status = match identifiers.get(pubkey) {
+            Some(identifier) => self.check(pubkey, identifier).await,
+            None => Nip05Status::None,
+        };
+        self.store(pubkey, status.clone());
+        status
+    }
+
+    fn is_fresh(&self, pubkey: &str) -> bool {
+        let Ok(cache) = self.cache.lock() else {
+            return false;
+        };
+        match cache.get(pubkey) {
+            Some((Nip05Status::Pending, queued)) => queued.elapsed() < PENDING_TTL,
+            Some((Nip05Status::Verified(_), checked)) => checked.elapsed() < VERIFIED_TTL,
+            Some((_, checked)) => checked.elapsed() < UNVERIFIED_TTL,
+            None => false,
+        }
+    }
+
+    fn store(&self, pubkey: &str, status: Nip05Status) {
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.insert(pubkey.to_string(), (status, Instant::now()));
+        }
+    }
+
+    async fn run_batches(self, mut queue_rx: mpsc::UnboundedReceiver<String>) {
+        while let Some(first) = queue_rx.recv().await {
+            tokio::time::sleep(BATCH_DELAY).await;
+
+            let mut batch = vec![first];
+            while batch.len() < MAX_BATCH {
+                match queue_rx.try_recv() {
+                    Ok(pubkey) => batch.push(pubkey),
+                    Err(_) => break,
+                }
+            }
+
+            let identifiers = self.fetch_identifiers(&batch).await;
+            for pubkey in batch {
+                match identifiers.get(&pubkey) {
+                    Some(identifier) => {
+                        // Lookups hit arbitrary domains, don't let a slow one hold up the rest
+                        let verifier = self.clone();
+                        let identifier = identifier.clone();
+                        tokio::spawn(async move {
+                            let status = verifier.check(&pubkey, &identifier).await;
+                            verifier.store(&pubkey, status);
+                        });
+                    }
+                    None => self.store(&pubkey, Nip05Status::None),
+                }
+            }
+        }
+    }
+
+    /// NIP-05 identifiers from the newest kind 0 of each pubkey
+    async fn fetch_identifiers(&self, pubkeys: &[String]) -> HashMap<String, String> {
+        let authors: Vec<PublicKey> = pubkeys.iter().filter_map(|pk| PublicKey::from_hex(pk).ok()).collect();
+        let filter = Filter::new().authors(authors).kind(Kind::Metadata);
+
+        let events = self.client
+            .get_events_of(vec![filter], Some(PROFILE_FETCH_TIMEOUT))
+            .await
+            .unwrap_or_default();
+
+        let mut newest: HashMap<String, &Event> = HashMap::new();
+        for event in &events {
+            let author = event.pubkey.to_hex();
+            if newest.get(&author).is_none_or(|known| known.created_at() < event.created_at()) {
+                newest.insert(author, event);
+            }
+        }
+
+        newest
+            .into_iter()
+            .filter_map(|(author, event)| {
+                let profile: serde_json::Value = serde_json::from_str(event.content()).ok()?;
+                let identifier = profile.get("nip05")?.as_str()?.trim().to_string();
+                (!identifier.is_empty()).then_some((author, identifier))
+            })
+            .collect()
+    }
+
+    async fn check(&self, pubkey: &str, identifier: &str) -> Nip05Status {
+        // A bare domain stands for the root identifier `_@domain`
+        let (name, domain) = match identifier.split_once('@') {
+            Some((name, domain)) => (name.to_lowercase(), domain.to_lowercase()),
+            None => ("_".to_string(), identifier.to_lowercase()),
+        };
+        // The domain is chosen by whoever wrote the profile, never let it point us at
+        // a port or a machine on our own network
+        if !is_public_hostname(&domain) {
+            return Nip05Status::Unverified(identifier.to_string());
+        }
+
+        match self.resolver.lookup(&name, &domain).await {
+            Ok(Some(resolved)) if resolved.to_hex() == pubkey => Nip05Status::Verified(identifier.to_string()),
+            _ => Nip05Status::Unverified(identifier.to_string()),
+        }
+    }
+}
+
+// A DNS name like example.com: no port, no IP address, no local-only names
+fn is_public_hostname(domain: &str) -> bool {
+    let labels: Vec<&str> = domain.split('.').collect();
+    let valid_labels = labels.iter().all(|label| {
+        !label.is_empty()
+            && label.len() <= 63
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    });
+    let Some(tld) = labels.last() else {
+        return false;
+    };
+
+    valid_labels
+        && labels.len() >= 2
+        && domain.len() <= 253
+        // An all-numeric last label is an IPv4 address, in any of its spellings
+        && !tld.chars().all(|c| c.is_ascii_digit())
+        && !matches!(*tld, "localhost" | "local" | "internal" | "lan" | "home" | "arpa")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // Stand-in for a domain's nostr.json: knows a single name
+    struct StandIn {
+        name: String,
+        pubkey: PublicKey,
+        lookups: AtomicUsize,
+    }
+
+    impl Nip05Resolver for StandIn {
+        fn lookup<'a>(&'a self, name: &'a str, _domain: &'a str) -> LookupFuture<'a> {
+            self.lookups.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async move { Ok((name == self.name).then_some(self.pubkey)) })
+        }
+    }
+
+    fn verifier(name: &str, pubkey: PublicKey) -> (Nip05Verifier, Arc<StandIn>) {
+        let resolver = Arc::new(StandIn {
+            name: name.to_string(),
+            pubkey,
+            lookups: AtomicUsize::new(0),
+        });
+        let client = Client::new(Keys::generate());
+        (Nip05Verifier::new(client, resolver.clone()), resolver)
+    }
+
+    #[tokio::test]
+    async fn verifies_identifier_the_domain_confirms() {
+        let keys = Keys::generate();
+        let (verifier, _) = verifier("alice", keys.public_key());
+        let pubkey = keys.public_key().to_hex();
+
+        assert_eq!(
+            verifier.check(&pubkey, "Alice@example.com").await,
+            Nip05Status::Verified("Alice@example.com".to_string())
+        );
+        assert_eq!(
+            verifier.check(&pubkey, "bob@example.com").await,
+            Nip05Status::Unverified("bob@example.com".to_string())
+        );
+        let other = Keys::generate().public_key().to_hex();
+        assert_eq!(
+            verifier.check(&other, "alice@example.com").await,
+            Nip05Status::Unverified("alice@example.com".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn bare_domain_is_the_root_identifier() {
+        let keys = Keys::generate();
+        let (verifier, _) = verifier("_", keys.public_key());
+
+        assert_eq!(
+            verifier.check(&keys.public_key().to_hex(), "example.com").await,
+            Nip05Status::Verified("example.com".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn never_looks_up_local_domains() {
+        let keys = Keys::generate();
+        let (verifier, resolver) = verifier("alice", keys.public_key());
+        let pubkey = keys.public_key().to_hex();
+
+        for identifier in [
+            "alice@localhost",
+            "alice@127.0.0.1",
+            "alice@10.0.0.1",
+            "alice@[::1]",
+            "alice@example.com:8080",
+            "alice@router.lan",
+            "alice@printer.local",
+            "alice@example.com/path",
+        ] {
+            assert_eq!(
+                verifier.check(&pubkey, identifier).await,
+                Nip05Status::Unverified(identifier.to_string())
+            );
+        }
+        assert_eq!(resolver.lookups.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn stale_pending_lookups_are_retried() {
+        let keys = Keys::generate();
+        let (verifier, _) = verifier("alice", keys.public_key());
+
+        verifier.request("fresh");
+        assert!(verifier.is_fresh("fresh"));
+
+        let queued = Instant::now() - PENDING_TTL - Duration::from_secs(1);
+        verifier.cache.lock().unwrap().insert("lost".to_string(), (Nip05Status::Pending, queued));
+        assert!(!verifier.is_fresh("lost"));
+    }
+}
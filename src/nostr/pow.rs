@@ -0,0 +1,100 @@
+use nostr::nips::nip13;
+use nostr_sdk::prelude::*;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// Check for cancellation and report progress this often while mining
+const PROGRESS_INTERVAL: u128 = 1024;
+
+/// Handle to a background NIP-13 mining job, for progress display and cancellation
+#[derive(Debug, Clone)]
+pub struct PowJob {
+    pub channel: String,
+    pub difficulty: u8,
+    attempts: Arc<AtomicU64>,
+    cancelled: Arc<AtomicBool>,
+    finished: Arc<AtomicBool>,
+    started: Instant,
+}
+
+impl PowJob {
+    pub(super) fn new(channel: &str, difficulty: u8) -> Self {
+        Self {
+            channel: channel.to_string(),
+            difficulty,
+            attempts: Arc::new(AtomicU64::new(0)),
+            cancelled: Arc::new(AtomicBool::new(false)),
+            finished: Arc::new(AtomicBool::new(false)),
+            started: Instant::now(),
+        }
+    }
+
+    /// Hashes tried so far
+    pub fn attempts(&self) -> u64 {
+        self.attempts.load(Ordering::Relaxed)
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Stop mining; the message is dropped instead of being sent without PoW
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::Relaxed)
+    }
+
+    /// Find a nonce tag giving the event id at least `difficulty` leading zero bits
+    ///
+    /// CPU bound, run it on a blocking thread. Returns None when cancelled.
+    pub(super) fn mine(&self, pubkey: &PublicKey, created_at: Timestamp, kind: &Kind, tags: &[Tag], content: &str) -> Option<Tag> {
+        let mut candidate = tags.to_vec();
+        let mut nonce: u128 = 0;
+
+        loop {
+            if nonce.is_multiple_of(PROGRESS_INTERVAL) {
+                if self.cancelled.load(Ordering::Relaxed) {
+                    return None;
+                }
+                self.attempts.store(nonce as u64, Ordering::Relaxed);
+            }
+
+            let tag = Tag::POW { nonce, difficulty: self.difficulty };
+            candidate.push(tag.clone());
+            let id = EventId::new(pubkey, created_at, kind, &candidate, content);
+            candidate.pop();
+
+            if nip13::get_leading_zero_bits(id.inner()) >= self.difficulty {
+                return Some(tag);
+            }
+            nonce += 1;
+        }
+    }
+
+    /// Mark the job done once its message has been sent (or given up on)
+    pub(super) fn finish(&self) {
+        self.finished.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Proof-of-work of a received event in leading zero bits (NIP-13)
+///
+/// When the event commits to a target lower than the work found, the target
+/// counts: the extra zero bits were luck, not effort.
+pub fn difficulty_of(event: &Event) -> u8 {
+    let bits = nip13::get_leading_zero_bits(event.id.inner());
+
+    let committed = event.tags().iter().find_map(|tag| match tag {
+        Tag::POW { difficulty, .. } => Some(*difficulty),
+        _ => None,
+    });
+
+    match committed {
+        Some(target) => bits.min(target),
+        None => bits,
+    }
+}
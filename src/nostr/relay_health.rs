@@ -0,0 +1,190 @@
+use anyhow::Result;
+use nostr_sdk::prelude::*;
+use rand::Rng;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+// Reconnect delays double from the first to the last, each randomized down to half
+// so relays that dropped together don't all come back at the same moment
+const FIRST_RETRY: Duration = Duration::from_secs(2);
+const MAX_RETRY: Duration = Duration::from_secs(5 * 60);
+
+/// Connection state of one relay
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RelayState {
+    Connecting,
+    Connected,
+    /// Disconnected, next attempt due at the given time
+    Backoff(Instant),
+}
+
+impl std::fmt::Display for RelayState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Connecting => write!(f, "Connecting"),
+            Self::Connected => write!(f, "Connected"),
+            Self::Backoff(retry_at) => {
+                write!(f, "retry in {}s", retry_at.saturating_duration_since(Instant::now()).as_secs())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct RelayConnection {
+    state: RelayState,
+    // Consecutive failed attempts, drives the backoff
+    failures: u32,
+}
+
+/// Keeps relays connected, reconnecting with exponential backoff and jitter
+///
+/// The pool's own reconnect loop is turned off for relays added here. A dropped
+/// relay is replaced by a fresh one, which takes over all pool subscriptions as it
+/// connects, so joined channels and private messages resume without resubscribing.
+#[derive(Clone)]
+pub struct RelaySupervisor {
+    client: Client,
+    connections: Arc<Mutex<HashMap<String, RelayConnection>>>,
+    status_tx: mpsc::UnboundedSender<String>,
+}
+
+impl RelaySupervisor {
+    pub fn new(client: Client, status_tx: mpsc::UnboundedSender<String>) -> Self {
+        Self {
+            client,
+            connections: Arc::new(Mutex::new(HashMap::new())),
+            status_tx,
+        }
+    }
+
+    /// Add a relay to the pool under supervision (connect it separately)
+    pub async fn add(&self, url: &str) -> Result<bool> {
+        let added = self.client.add_relay_with_opts(url, Self::relay_options()).await?;
+        if let Ok(mut connections) = self.connections.lock() {
+            connections.insert(Self::key(url), RelayConnection { state: RelayState::Connecting, failures: 0 });
+        }
+        Ok(added)
+    }
+
+    /// Drop a relay from the pool and stop reconnecting it
+    pub async fn remove(&self, url: &str) -> Result<()> {
+        if let Ok(mut connections) = self.connections.lock() {
+            connections.remove(&Self::key(url));
+        }
+        self.client.remove_relay(url).await?;
+        Ok(())
+    }
+
+    pub fn state(&self, url: &str) -> Option<RelayState> {
+        self.connections.lock().ok()?.get(&Self::key(url)).map(|connection| connection.state)
+    }
+
+    /// (connected, connecting, total) relays
+    pub fn summary(&self) -> (usize, usize, usize) {
+        let Ok(connections) = self.connections.lock() else {
+            return (0, 0, 0);
+        };
+        let count = |state: RelayState| connections.values().filter(|c| c.state == state).count();
+        (count(RelayState::Connected), count(RelayState::Connecting), connections.len())
+    }
+
+    /// Track a relay status notification from the pool
+    pub fn on_status(&self, relay_url: &Url, status: RelayStatus) {
+        let key = Self::key(relay_url.as_str());
+
+        let retry = {
+            let Ok(mut connections) = self.connections.lock() else {
+                return;
+            };
+            // Removed relays report Terminated on their way out
+            let Some(connection) = connections.get_mut(&key) else {
+                return;
+            };
+
+            match status {
+                RelayStatus::Connected => {
+                    if connection.failures > 0 {
+                        let _ = self.status_tx.send(format!("Reconnected to {}", key));
+                    }
+                    connection.state = RelayState::Connected;
+                    connection.failures = 0;
+                    None
+                }
+                RelayStatus::Connecting | RelayStatus::Pending | RelayStatus::Initialized => {
+                    connection.state = RelayState::Connecting;
+                    None
+                }
+                // Dropped or failed to connect; a retry may already be waiting
+                RelayStatus::Disconnected if !matches!(connection.state, RelayState::Backoff(_)) => {
+                    if connection.state == RelayState::Connected {
+                        let _ = self.status_tx.send(format!("Lost connection to {}", key));
+                    }
+                    let delay = Self::backoff(connection.failures);
+                    connection.failures += 1;
+                    connection.state = RelayState::Backoff(Instant::now() + delay);
+                    Some(delay)
+                }
+                // Stopped and terminated relays were shut down on purpose
+                _ => None,
+            }
+        };
+
+        if let Some(delay) = retry {
+            let supervisor = self.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(delay).await;
+                supervisor.reconnect(&key).await;
+            });
+        }
+    }
+
+    async fn reconnect(&self, key: &str) {
+        // The relay may have been removed while we waited
+        let still_wanted = self.connections
+            .lock()
+            .map(|mut connections| match connections.get_mut(key) {
+                Some(connection) => {
+                    connection.state = RelayState::Connecting;
+                    true
+                }
+                None => false,
+            })
+            .unwrap_or(false);
+        if !still_wanted {
+            return;
+        }
+
+        // A disconnected relay can't be restarted in place, so swap in a new one;
+        // connecting it through the pool hands it every active subscription
+        let _ = self.client.remove_relay(key).await;
+        let result = match self.client.add_relay_with_opts(key, Self::relay_options()).await {
+            Ok(_) => self.client.connect_relay(key).await,
+            Err(e) => Err(e),
+        };
+
+        if result.is_err() {
+            // Never got as far as a status notification, so schedule the next try here
+            if let Ok(url) = Url::parse(key) {
+                self.on_status(&url, RelayStatus::Disconnected);
+            }
+        }
+    }
+
+    /// Delay before retry number `failures + 1`: exponential, capped, with jitter
+    fn backoff(failures: u32) -> Duration {
+        let delay = FIRST_RETRY.saturating_mul(2u32.saturating_pow(failures)).min(MAX_RETRY);
+        delay.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+    }
+
+    fn relay_options() -> RelayOptions {
+        RelayOptions::new().reconnect(false)
+    }
+
+    // The pool reports URLs with a trailing slash, ours are stored without
+    fn key(url: &str) -> String {
+        url.trim_end_matches('/').to_string()
+    }
+}
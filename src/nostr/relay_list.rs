@@ -0,0 +1,42 @@
+use anyhow::{anyhow, Result};
+use nostr_sdk::prelude::*;
+use std::time::Duration;
+
+/// Why a relay is in the pool
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelaySource {
+    /// One of the built-in relays shared with bitchat mobile
+    Default,
+    /// Picked by the georelay directory for a joined channel
+    Geo,
+    /// Added with /relay add or listed in config.toml
+    Manual,
+}
+
+impl std::fmt::Display for RelaySource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Default => write!(f, "default"),
+            Self::Geo => write!(f, "geo"),
+            Self::Manual => write!(f, "manual"),
+        }
+    }
+}
+
+/// A relay row for /relay list
+#[derive(Debug, Clone)]
+pub struct RelayEntry {
+    pub url: String,
+    pub source: RelaySource,
+    pub status: String,
+    pub latency: Option<Duration>,
+}
+
+/// Canonical form of a relay URL as typed by the user: ws(s) only, no trailing slash
+pub fn normalize_relay_url(input: &str) -> Result<String> {
+    let url = Url::parse(input.trim()).map_err(|e| anyhow!("Invalid relay URL {}: {}", input, e))?;
+    if !matches!(url.scheme(), "wss" | "ws") || url.host_str().is_none() {
+        return Err(anyhow!("Relay URLs must look like wss://relay.example.com"));
+    }
+    Ok(url.as_str().trim_end_matches('/').to_string())
+}
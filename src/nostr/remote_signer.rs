@@ -0,0 +1,539 @@
+use anyhow::{anyhow, Result};
+use nostr_sdk::prelude::*;
+use rand::{thread_rng, RngCore};
+use serde_json::{json, Value};
+use std::time::Duration;
+use tokio::time::timeout;
+
+use super::keystore;
+
+// Pairing and the first request may wait on the user approving us in their signer app
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(120);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+const RELAY_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// NIP-46 remote signer ("bunker") holding the user's secret key
+///
+/// Requests and responses are kind 24133 events between our app key and the
+/// signer, NIP-44 encrypted (NIP-04 responses from older signers are accepted).
+#[derive(Debug, Clone)]
+pub struct RemoteSigner {
+    client: Client,
+    app_keys: Keys,
+    signer_pubkey: PublicKey,
+    user_pubkey: PublicKey,
+}
+
+impl RemoteSigner {
+    /// Connect using a `bunker://<signer-pubkey>?relay=...&secret=...` URI from the signer
+    pub async fn from_bunker_uri(uri: &str) -> Result<Self> {
+        Self::connect(uri, load_or_create_app_keys()?).await
+    }
+
+    async fn connect(uri: &str, app_keys: Keys) -> Result<Self> {
+        let url = Url::parse(uri.trim()).map_err(|_| anyhow!("Invalid bunker URI"))?;
+        if url.scheme() != "bunker" {
+            return Err(anyhow!("Expected a bunker:// URI"));
+        }
+
+        let signer_pubkey = url
+            .host_str()
+            .and_then(|host| PublicKey::from_hex(host).ok())
+            .ok_or_else(|| anyhow!("Bunker URI is missing the signer pubkey"))?;
+        let relays: Vec<String> = url
+            .query_pairs()
+            .filter(|(key, _)| key == "relay")
+            .map(|(_, value)| value.to_string())
+            .collect();
+        let secret = url
+            .query_pairs()
+            .find(|(key, _)| key == "secret")
+            .map(|(_, value)| value.to_string());
+
+        if relays.is_empty() {
+            return Err(anyhow!("Bunker URI has no relay"));
+        }
+
+        let client = connect_relays(&app_keys, &relays).await?;
+
+        let mut signer = Self {
+            client,
+            app_keys,
+            signer_pubkey,
+            user_pubkey: signer_pubkey,
+        };
+
+        let mut params = vec![signer_pubkey.to_hex()];
+        params.extend(secret.clone());
+        let result = signer.request("connect", params, CONNECT_TIMEOUT).await?;
+
+        // Signers acknowledge with "ack" or by echoing the secret
+        if result != "ack" && Some(&result) != secret.as_ref() {
+            return Err(anyhow!("Unexpected connect response from remote signer"));
+        }
+
+        signer.user_pubkey = signer.fetch_user_pubkey().await?;
+        Ok(signer)
+    }
+
+    /// Pair with a signer app by handing it a `nostrconnect://` URI
+    ///
+    /// `show_uri` is called once we are listening on the relay; the user pastes
+    /// the URI into their signer, which then connects back to us.
+    pub async fn pair(relay: &str, show_uri: impl FnOnce(&str)) -> Result<Self> {
+        let app_keys = load_or_create_app_keys()?;
+        let secret = random_hex(16);
+
+        let mut uri = Url::parse(&format!("nostrconnect://{}", app_keys.public_key().to_hex()))?;
+        uri.query_pairs_mut()
+            .append_pair("relay", relay)
+            .append_pair("secret", &secret)
+            .append_pair("name", "BitchatX");
+
+        let client = connect_relays(&app_keys, &[relay.to_string()]).await?;
+        let mut notifications = client.notifications();
+
+        show_uri(uri.as_str());
+
+        let signer_pubkey = timeout(CONNECT_TIMEOUT, async {
+            while let Ok(notification) = notifications.recv().await {
+                let RelayPoolNotification::Event { event, .. } = notification else {
+                    continue;
+                };
+                let Ok(message) = open_message(&app_keys, &event) else {
+                    continue;
+                };
+
+                // Current signers answer with our secret, older ones send a connect
+                // request carrying it: [signer pubkey, secret, ...]
+                let accepted = message.get("result").and_then(Value::as_str) == Some(secret.as_str())
+                    || (message.get("method").and_then(Value::as_str) == Some("connect")
+                        && message.pointer("/params/1").and_then(Value::as_str) == Some(secret.as_str()));
+                if accepted {
+                    return Some(event.pubkey);
+                }
+            }
+            None
+        })
+        .await
+        .ok()
+        .flatten()
+        .ok_or_else(|| anyhow!("No signer connected within {}s", CONNECT_TIMEOUT.as_secs()))?;
+
+        let mut signer = Self {
+            client,
+            app_keys,
+            signer_pubkey,
+            user_pubkey: signer_pubkey,
+        };
+        signer.user_pubkey = signer.fetch_user_pubkey().await?;
+        Ok(signer)
+    }
+
+    /// The user's public key (not the signer's or our app key)
+    pub fn public_key(&self) -> PublicKey {
+        self.user_pubkey
+    }
+
+    /// Have the remote signer sign an event
+    pub async fn sign_event(&self, unsigned: UnsignedEvent) -> Result<Event> {
+        // The signer fills in pubkey and id itself
+        let mut template = serde_json::to_value(&unsigned)?;
+        if let Some(object) = template.as_object_mut() {
+            object.remove("id");
+            object.remove("pubkey");
+        }
+
+        let result = self.request("sign_event", vec![template.to_string()], REQUEST_TIMEOUT).await?;
+        let event = Event::from_json(result)?;
+        event.verify()?;
+
+        // Anything else changed would publish words we didn't write, or drop a mined PoW nonce
+        if event.pubkey != self.user_pubkey
+            || event.kind != unsigned.kind
+            || event.content != unsigned.content
+            || event.tags != unsigned.tags
+            || event.created_at != unsigned.created_at
+        {
+            return Err(anyhow!("Remote signer returned a different event than requested"));
+        }
+
+        Ok(event)
+    }
+
+    pub async fn nip44_encrypt(&self, public_key: &PublicKey, plaintext: &str) -> Result<String> {
+        self.request("nip44_encrypt", vec![public_key.to_hex(), plaintext.to_string()], REQUEST_TIMEOUT).await
+    }
+
+    pub async fn nip44_decrypt(&self, public_key: &PublicKey, ciphertext: &str) -> Result<String> {
+        self.request("nip44_decrypt", vec![public_key.to_hex(), ciphertext.to_string()], REQUEST_TIMEOUT).await
+    }
+
+    async fn fetch_user_pubkey(&self) -> Result<PublicKey> {
+        let result = self.request("get_public_key", Vec::new(), REQUEST_TIMEOUT).await?;
+        PublicKey::from_hex(&result).map_err(|_| anyhow!("Remote signer returned an invalid pubkey"))
+    }
+
+    /// Send a NIP-46 request and wait for the matching response
+    async fn request(&self, method: &str, params: Vec<String>, wait: Duration) -> Result<String> {
+        let id = random_hex(8);
+        let message = json!({ "id": id, "method": method, "params": params });
+        let event = seal_message(&self.app_keys, &self.signer_pubkey, &message)?;
+
+        // Listen before sending so a fast response can't be missed
+        let mut notifications = self.client.notifications();
+        self.client.send_event(event).await?;
+
+        timeout(wait, async {
+            while let Ok(notification) = notifications.recv().await {
+                let RelayPoolNotification::Event { event, .. } = notification else {
+                    continue;
+                };
+                if event.pubkey != self.signer_pubkey {
+                    continue;
+                }
+                let Ok(response) = open_message(&self.app_keys, &event) else {
+                    continue;
+                };
+                if response.get("id").and_then(Value::as_str) != Some(id.as_str()) {
+                    continue;
+                }
+
+                let result = response.get("result").and_then(Value::as_str).unwrap_or_default();
+
+                // Signers that need the user to approve in a browser send an auth
+                // challenge first and the real response once approved
+                if result == "auth_url" {
+                    continue;
+                }
+
+                if let Some(error) = response.get("error").and_then(Value::as_str).filter(|e| !e.is_empty()) {
+                    return Err(anyhow!("Remote signer refused {}: {}", method, error));
+                }
+
+                return Ok(result.to_string());
+            }
+            Err(anyhow!("Remote signer connection closed"))
+        })
+        .await
+        .map_err(|_| anyhow!("Remote signer did not answer {} within {}s", method, wait.as_secs()))?
+    }
+}
+
+/// Connect to the signer relays and listen for NIP-46 messages addressed to `keys`
+async fn connect_relays(keys: &Keys, relays: &[String]) -> Result<Client> {
+    let opts = Options::new().connection_timeout(Some(RELAY_CONNECT_TIMEOUT));
+    let client = Client::with_opts(keys, opts);
+    for relay in relays {
+        client.add_relay(relay.as_str()).await?;
+    }
+    client.connect().await;
+
+    let filter = Filter::new()
+        .kind(Kind::NostrConnect)
+        .pubkey(keys.public_key())
+        .since(Timestamp::now());
+    client.subscribe(vec![filter], None).await;
+
+    Ok(client)
+}
+
+/// Encrypt a NIP-46 message into a kind 24133 event for `receiver`
+fn seal_message(keys: &Keys, receiver: &PublicKey, message: &Value) -> Result<Event> {
+    let content = nip44::encrypt(keys.secret_key()?, receiver, message.to_string(), nip44::Version::default())?;
+    Ok(EventBuilder::new(Kind::NostrConnect, content, [Tag::public_key(*receiver)]).to_event(keys)?)
+}
+
+/// Decrypt a NIP-46 message, accepting NIP-44 or legacy NIP-04 encryption
+fn open_message(keys: &Keys, event: &Event) -> Result<Value> {
+    if event.kind != Kind::NostrConnect {
+        return Err(anyhow!("Not a NIP-46 message"));
+    }
+
+    let secret_key = keys.secret_key()?;
+    let plaintext = nip44::decrypt(secret_key, &event.pubkey, &event.content)
+        .or_else(|_| nip04::decrypt(secret_key, &event.pubkey, &event.content))
+        .map_err(|_| anyhow!("Cannot decrypt NIP-46 message"))?;
+
+    Ok(serde_json::from_str(&plaintext)?)
+}
+
+/// Our NIP-46 app key, kept so signers remember the approval across sessions
+fn load_or_create_app_keys() -> Result<Keys> {
+    let path = keystore::config_dir().join("nip46_app_key");
+
+    if let Ok(contents) = std::fs::read_to_string(&path) {
+        let secret_key = SecretKey::from_hex(contents.trim())
+            .map_err(|_| anyhow!("Corrupt NIP-46 app key at {}", path.display()))?;
+        return Ok(Keys::new(secret_key));
+    }
+
+    let keys = Keys::generate();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    keystore::write_private_file(&path, keys.secret_key()?.display_secret().to_string().as_bytes())?;
+
+    Ok(keys)
+}
+
+fn random_hex(len: usize) -> String {
+    let mut bytes = vec![0u8; len];
+    thread_rng().fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::{SinkExt, StreamExt};
+    use std::collections::HashSet;
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::sync::broadcast;
+    use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+    /// Relay on a free local port that keeps nothing: events go to the matching
+    /// subscriptions of every connection, which is all NIP-46 needs
+    async fn start_relay() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let (events, _) = broadcast::channel(64);
+
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve_relay(stream, events.clone()));
+            }
+        });
+        url
+    }
+
+    async fn serve_relay(stream: TcpStream, events: broadcast::Sender<Event>) {
+        let Ok(socket) = tokio_tungstenite::accept_async(stream).await else {
+            return;
+        };
+        let (mut sink, mut messages) = socket.split();
+        let mut published = events.subscribe();
+        let mut subscriptions: Vec<(SubscriptionId, Vec<Filter>)> = Vec::new();
+
+        loop {
+            let reply = tokio::select! {
+                message = messages.next() => {
+                    let text = match message {
+                        Some(Ok(WsMessage::Text(text))) => text,
+                        Some(Ok(_)) => continue,
+                        _ => return,
+                    };
+                    match ClientMessage::from_json(text) {
+                        Ok(ClientMessage::Event(event)) => {
+                            let _ = events.send(*event.clone());
+                            json!(["OK", event.id, true, ""])
+                        }
+                        Ok(ClientMessage::Req { subscription_id, filters }) => {
+                            let eose = json!(["EOSE", subscription_id]);
+                            subscriptions.push((subscription_id, filters));
+                            eose
+                        }
+                        Ok(ClientMessage::Close(subscription_id)) => {
+                            subscriptions.retain(|(id, _)| *id != subscription_id);
+                            continue;
+                        }
+                        _ => continue,
+                    }
+                }
+                Ok(event) = published.recv() => {
+                    let Some((id, _)) = subscriptions
+                        .iter()
+                        .find(|(_, filters)| filters.iter().any(|filter| filter.match_event(&event)))
+                    else {
+                        continue;
+                    };
+                    json!(["EVENT", id, event])
+                }
+            };
+            if sink.send(WsMessage::Text(reply.to_string())).await.is_err() {
+                return;
+            }
+        }
+    }
+
+    /// Minimal NIP-46 signer serving a local key over a relay, standing in for a
+    /// real bunker; it approves every request from clients that know the secret
+    struct Bunker {
+        client: Client,
+        keys: Keys,
+        relay: String,
+        secret: String,
+        // Signs events without their tags, as a misbehaving signer might
+        drop_tags: bool,
+    }
+
+    impl Bunker {
+        async fn start(keys: Keys, relay: &str) -> Result<Self> {
+            let client = connect_relays(&keys, &[relay.to_string()]).await?;
+            Ok(Self {
+                client,
+                keys,
+                relay: relay.to_string(),
+                secret: random_hex(16),
+                drop_tags: false,
+            })
+        }
+
+        /// `bunker://` URI for clients to connect with
+        fn uri(&self) -> String {
+            let mut uri = Url::parse(&format!("bunker://{}", self.keys.public_key().to_hex()))
+                .expect("hex pubkey is a valid URI host");
+            uri.query_pairs_mut()
+                .append_pair("relay", &self.relay)
+                .append_pair("secret", &self.secret);
+            uri.to_string()
+        }
+
+        /// Answer requests until the relay connection closes
+        async fn run(&self) -> Result<()> {
+            let mut notifications = self.client.notifications();
+            let mut authorized: HashSet<PublicKey> = HashSet::new();
+
+            while let Ok(notification) = notifications.recv().await {
+                let RelayPoolNotification::Event { event, .. } = notification else {
+                    continue;
+                };
+                let Ok(request) = open_message(&self.keys, &event) else {
+                    continue;
+                };
+                let (Some(id), Some(method)) = (
+                    request.get("id").and_then(Value::as_str),
+                    request.get("method").and_then(Value::as_str),
+                ) else {
+                    continue;
+                };
+                let params: Vec<String> = request
+                    .get("params")
+                    .and_then(Value::as_array)
+                    .map(|params| params.iter().filter_map(|p| p.as_str().map(str::to_string)).collect())
+                    .unwrap_or_default();
+
+                let client_pubkey = event.pubkey;
+                let outcome = match method {
+                    "connect" if params.get(1) == Some(&self.secret) => {
+                        authorized.insert(client_pubkey);
+                        Ok("ack".to_string())
+                    }
+                    "connect" => Err(anyhow!("invalid secret")),
+                    "ping" => Ok("pong".to_string()),
+                    _ if !authorized.contains(&client_pubkey) => Err(anyhow!("unauthorized")),
+                    _ => self.handle(method, &params),
+                };
+
+                let response = match &outcome {
+                    Ok(result) => json!({ "id": id, "result": result }),
+                    Err(e) => json!({ "id": id, "result": "", "error": e.to_string() }),
+                };
+                let reply = seal_message(&self.keys, &client_pubkey, &response)?;
+                self.client.send_event(reply).await?;
+            }
+
+            Ok(())
+        }
+
+        fn handle(&self, method: &str, params: &[String]) -> Result<String> {
+            let secret_key = self.keys.secret_key()?;
+            let param = |i: usize| params.get(i).ok_or_else(|| anyhow!("missing parameter"));
+            let peer = |i: usize| -> Result<PublicKey> { Ok(PublicKey::from_hex(param(i)?)?) };
+
+            match method {
+                "get_public_key" => Ok(self.keys.public_key().to_hex()),
+                "sign_event" => {
+                    let template: Value = serde_json::from_str(param(0)?)?;
+                    let kind = template.get("kind").and_then(Value::as_u64).ok_or_else(|| anyhow!("missing kind"))?;
+                    let content = template.get("content").and_then(Value::as_str).unwrap_or_default();
+                    let created_at = template.get("created_at").and_then(Value::as_u64).map(Timestamp::from);
+                    let tags: Vec<Vec<String>> = serde_json::from_value(template.get("tags").cloned().unwrap_or(json!([])))?;
+                    let mut tags = tags.into_iter().map(Tag::parse).collect::<Result<Vec<_>, _>>()?;
+                    if self.drop_tags {
+                        tags.clear();
+                    }
+
+                    let mut builder = EventBuilder::new(Kind::from(kind), content, tags);
+                    if let Some(created_at) = created_at {
+                        builder = builder.custom_created_at(created_at);
+                    }
+                    Ok(builder.to_event(&self.keys)?.as_json())
+                }
+                "nip44_encrypt" => Ok(nip44::encrypt(secret_key, &peer(0)?, param(1)?, nip44::Version::default())?),
+                "nip44_decrypt" => Ok(nip44::decrypt(secret_key, &peer(0)?, param(1)?)?),
+                "nip04_encrypt" => Ok(nip04::encrypt(secret_key, &peer(0)?, param(1)?)?),
+                "nip04_decrypt" => Ok(nip04::decrypt(secret_key, &peer(0)?, param(1)?)?),
+                other => Err(anyhow!("unsupported method {}", other)),
+            }
+        }
+    }
+
+    async fn start_bunker(relay: &str, keys: &Keys) -> String {
+        start_bunker_with(relay, keys, false).await
+    }
+
+    async fn start_bunker_with(relay: &str, keys: &Keys, drop_tags: bool) -> String {
+        let mut bunker = Bunker::start(keys.clone(), relay).await.unwrap();
+        bunker.drop_tags = drop_tags;
+        let uri = bunker.uri();
+        tokio::spawn(async move { bunker.run().await });
+        uri
+    }
+
+    #[tokio::test]
+    async fn signs_and_encrypts_through_the_bunker() {
+        let relay = start_relay().await;
+        let user_keys = Keys::generate();
+        let uri = start_bunker(&relay, &user_keys).await;
+
+        let signer = RemoteSigner::connect(&uri, Keys::generate()).await.unwrap();
+        assert_eq!(signer.public_key(), user_keys.public_key());
+
+        let unsigned = EventBuilder::new(Kind::from(20000), "hello", [Tag::Geohash("dr5reg".to_string())])
+            .to_unsigned_event(signer.public_key());
+        let event = signer.sign_event(unsigned).await.unwrap();
+        assert!(event.verify().is_ok());
+        assert_eq!(event.pubkey, user_keys.public_key());
+        assert_eq!(event.content, "hello");
+
+        let peer = Keys::generate();
+        let ciphertext = signer.nip44_encrypt(&peer.public_key(), "to the peer").await.unwrap();
+        assert_eq!(
+            nip44::decrypt(peer.secret_key().unwrap(), &user_keys.public_key(), ciphertext).unwrap(),
+            "to the peer"
+        );
+        let ciphertext = nip44::encrypt(
+            peer.secret_key().unwrap(),
+            &user_keys.public_key(),
+            "from the peer",
+            nip44::Version::default(),
+        )
+        .unwrap();
+        assert_eq!(signer.nip44_decrypt(&peer.public_key(), &ciphertext).await.unwrap(), "from the peer");
+    }
+
+    #[tokio::test]
+    async fn bunker_refuses_a_wrong_secret() {
+        let relay = start_relay().await;
+        let uri = start_bunker(&relay, &Keys::generate()).await;
+        let mut url = Url::parse(&uri).unwrap();
+        let relay_param = url.query_pairs().find(|(key, _)| key == "relay").unwrap().1.to_string();
+        url.query_pairs_mut().clear().append_pair("relay", &relay_param).append_pair("secret", "guessed");
+
+        let error = RemoteSigner::connect(url.as_str(), Keys::generate()).await.unwrap_err();
+        assert!(error.to_string().contains("invalid secret"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn rejects_an_event_signed_with_other_tags() {
+        let relay = start_relay().await;
+        let uri = start_bunker_with(&relay, &Keys::generate(), true).await;
+        let signer = RemoteSigner::connect(&uri, Keys::generate()).await.unwrap();
+
+        let unsigned = EventBuilder::new(Kind::from(20000), "hello", [Tag::Geohash("dr5reg".to_string())])
+            .to_unsigned_event(signer.public_key());
+        let error = signer.sign_event(unsigned).await.unwrap_err();
+        assert!(error.to_string().contains("different event"), "{}", error);
+    }
+}
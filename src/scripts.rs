@@ -0,0 +1,342 @@
+use anyhow::{anyhow, Result};
+use rhai::{Array, CallFnOptions, Dynamic, Engine, Map, Scope, AST};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::channels::Message;
+use crate::nostr::config_dir;
+
+// Keeps a runaway loop in a script from freezing the client
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+/// Something a script asked for, carried out by the app once the script returns
+pub enum ScriptAction {
+    Send { channel: String, text: String },
+    SendPrivate { to: String, text: String },
+    Join(String),
+    Leave(String),
+    Status(String),
+}
+
+/// When a message hook runs, relative to the spam filter
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HookStage {
+    /// Sees every message first; returning `false` drops it
+    BeforeSpam,
+    /// Sees the messages that made it into a channel
+    AfterSpam,
+}
+
+/// What scripts can read about the session while they run
+#[derive(Default)]
+pub struct ScriptContext {
+    pub nickname: String,
+    pub current_channel: String,
+    /// Joined channels with their participants
+    pub channels: Vec<(String, Vec<ScriptParticipant>)>,
+}
+
+pub struct ScriptParticipant {
+    pub nickname: String,
+    pub pubkey: String,
+    pub messages: usize,
+}
+
+struct Script {
+    name: String,
+    path: PathBuf,
+    ast: AST,
+    scope: Scope<'static>,
+}
+
+// A script function to call
+#[derive(Clone)]
+struct Callback {
+    script: String,
+    function: String,
+}
+
+// State the engine's API functions share with the host
+#[derive(Default)]
+struct Shared {
+    // Script whose top level is running, the owner of new registrations
+    loading: Option<String>,
+    commands: HashMap<String, Callback>,
+    hooks: Vec<(HookStage, Callback)>,
+    actions: Vec<ScriptAction>,
+    context: ScriptContext,
+}
+
+/// Rhai scripts from ~/.config/bitchatx/scripts, managed with /script
+///
+/// A script's top level runs when it is loaded and registers what it handles:
+///
+/// ```rhai
+/// register_command("hello", "hello");        // /hello calls fn hello(args)
+/// register_hook("before_spam", "filter");    // fn filter(msg), false drops the message
+/// register_hook("after_spam", "on_message"); // fn on_message(msg)
+/// ```
+///
+/// Scripts act through `send(channel, text)`, `send_private(to, text)`,
+/// `join(channel)`, `leave(channel)` and `print(text)`, and can look at the
+/// session with `my_nick()`, `current_channel()`, `channels()` and
+/// `participants(channel)`. Scripts in `scripts/autorun/` are loaded on startup.
+pub struct ScriptHost {
+    engine: Engine,
+    dir: PathBuf,
+    scripts: Vec<Script>,
+    shared: Rc<RefCell<Shared>>,
+}
+
+impl ScriptHost {
+    pub fn new() -> Self {
+        let shared = Rc::new(RefCell::new(Shared::default()));
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+
+        let state = shared.clone();
+        engine.on_print(move |text| state.borrow_mut().actions.push(ScriptAction::Status(text.to_string())));
+        let state = shared.clone();
+        engine.on_debug(move |text, _, _| state.borrow_mut().actions.push(ScriptAction::Status(text.to_string())));
+
+        let state = shared.clone();
+        engine.register_fn("register_command", move |name: &str, function: &str| {
+            let mut state = state.borrow_mut();
+            if let Some(script) = state.loading.clone() {
+                let name = name.trim_start_matches('/').to_lowercase();
+                state.commands.insert(name, Callback { script, function: function.to_string() });
+            }
+        });
+        let state = shared.clone();
+        engine.register_fn("register_hook", move |stage: &str, function: &str| -> std::result::Result<(), Box<rhai::EvalAltResult>> {
+            let stage = match stage {
+                "before_spam" => HookStage::BeforeSpam,
+                "after_spam" => HookStage::AfterSpam,
+                _ => return Err(format!("Unknown hook {} (before_spam or after_spam)", stage).into()),
+            };
+            let mut state = state.borrow_mut();
+            if let Some(script) = state.loading.clone() {
+                state.hooks.push((stage, Callback { script, function: function.to_string() }));
+            }
+            Ok(())
+        });
+
+        let state = shared.clone();
+        engine.register_fn("send", move |channel: &str, text: &str| {
+            let channel = channel.trim_start_matches('#').to_string();
+            state.borrow_mut().actions.push(ScriptAction::Send { channel, text: text.to_string() });
+        });
+        let state = shared.clone();
+        engine.register_fn("send_private", move |to: &str, text: &str| {
+            state.borrow_mut().actions.push(ScriptAction::SendPrivate { to: to.to_string(), text: text.to_string() });
+        });
+        let state = shared.clone();
+        engine.register_fn("join", move |channel: &str| {
+            state.borrow_mut().actions.push(ScriptAction::Join(channel.trim_start_matches('#').to_string()));
+        });
+        let state = shared.clone();
+        engine.register_fn("leave", move |channel: &str| {
+            state.borrow_mut().actions.push(ScriptAction::Leave(channel.trim_start_matches('#').to_string()));
+        });
+
+        let state = shared.clone();
+        engine.register_fn("my_nick", move || state.borrow().context.nickname.clone());
+        let state = shared.clone();
+        engine.register_fn("current_channel", move || state.borrow().context.current_channel.clone());
+        let state = shared.clone();
+        engine.register_fn("channels", move || -> Array {
+            state.borrow().context.channels.iter().map(|(name, _)| Dynamic::from(name.clone())).collect()
+        });
+        let state = shared.clone();
+        engine.register_fn("participants", move |channel: &str| -> Array {
+            let channel = channel.trim_start_matches('#');
+            let state = state.borrow();
+            let Some((_, participants)) = state.context.channels.iter().find(|(name, _)| name == channel) else {
+                return Array::new();
+            };
+            participants
+                .iter()
+                .map(|participant| {
+                    let mut map = Map::new();
+                    map.insert("nickname".into(), participant.nickname.clone().into());
+                    map.insert("pubkey".into(), participant.pubkey.clone().into());
+                    map.insert("messages".into(), (participant.messages as i64).into());
+                    Dynamic::from_map(map)
+                })
+                .collect()
+        });
+
+        Self {
+            engine,
+            dir: config_dir().join("scripts"),
+            scripts: Vec::new(),
+            shared,
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Scripts in scripts/autorun, in name order
+    pub fn autorun_paths(&self) -> Vec<PathBuf> {
+        let Ok(entries) = std::fs::read_dir(self.dir.join("autorun")) else {
+            return Vec::new();
+        };
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|extension| extension == "rhai"))
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    /// Load a script by name (scripts/<name>.rhai) or path, replacing one of the same name
+    ///
+    /// Returns the script's name.
+    pub fn load(&mut self, name_or_path: &str) -> Result<String> {
+        let path = if name_or_path.contains('/') || name_or_path.ends_with(".rhai") {
+            PathBuf::from(name_or_path)
+        } else {
+            self.dir.join(format!("{}.rhai", name_or_path))
+        };
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .ok_or_else(|| anyhow!("Not a script: {}", path.display()))?;
+
+        let ast = self
+            .engine
+            .compile_file(path.clone())
+            .map_err(|e| anyhow!("Cannot load {}: {}", path.display(), e))?;
+
+        self.unload(&name);
+        let mut scope = Scope::new();
+        self.shared.borrow_mut().loading = Some(name.clone());
+        let result = self.engine.run_ast_with_scope(&mut scope, &ast);
+        self.shared.borrow_mut().loading = None;
+
+        if let Err(e) = result {
+            self.forget_registrations(&name);
+            return Err(anyhow!("Error in {}: {}", path.display(), e));
+        }
+
+        self.scripts.push(Script { name: name.clone(), path, ast, scope });
+        Ok(name)
+    }
+
+    /// Drop a script with its commands and hooks; false if it wasn't loaded
+    pub fn unload(&mut self, name: &str) -> bool {
+        let loaded = self.scripts.len();
+        self.scripts.retain(|script| script.name != name);
+        self.forget_registrations(name);
+        self.scripts.len() != loaded
+    }
+
+    /// Loaded scripts with their path and the commands they added
+    pub fn list(&self) -> Vec<(String, PathBuf, Vec<String>)> {
+        let shared = self.shared.borrow();
+        self.scripts
+            .iter()
+            .map(|script| {
+                let mut commands: Vec<String> = shared
+                    .commands
+                    .iter()
+                    .filter(|(_, callback)| callback.script == script.name)
+                    .map(|(name, _)| format!("/{}", name))
+                    .collect();
+                commands.sort();
+                (script.name.clone(), script.path.clone(), commands)
+            })
+            .collect()
+    }
+
+    pub fn has_command(&self, name: &str) -> bool {
+        self.shared.borrow().commands.contains_key(&name.to_lowercase())
+    }
+
+    pub fn has_hooks(&self) -> bool {
+        !self.shared.borrow().hooks.is_empty()
+    }
+
+    /// What the next script call can see of the session
+    pub fn set_context(&mut self, context: ScriptContext) {
+        self.shared.borrow_mut().context = context;
+    }
+
+    /// Run a script command with its arguments
+    pub fn run_command(&mut self, name: &str, args: &[String]) {
+        let Some(callback) = self.shared.borrow().commands.get(&name.to_lowercase()).cloned() else {
+            return;
+        };
+        let args: Array = args.iter().map(|arg| Dynamic::from(arg.clone())).collect();
+        let _ = self.call(&callback, args.into());
+    }
+
+    /// Run the hooks of a stage; false if a before_spam hook dropped the message
+    pub fn run_hooks(&mut self, stage: HookStage, message: &Message) -> bool {
+        let callbacks: Vec<Callback> = self
+            .shared
+            .borrow()
+            .hooks
+            .iter()
+            .filter(|(hook_stage, _)| *hook_stage == stage)
+            .map(|(_, callback)| callback.clone())
+            .collect();
+        if callbacks.is_empty() {
+            return true;
+        }
+
+        let message = Dynamic::from_map(Self::message_map(message));
+        for callback in callbacks {
+            let keep = self.call(&callback, message.clone());
+            if stage == HookStage::BeforeSpam && keep.as_bool().ok() == Some(false) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Actions queued by the scripts since the last call
+    pub fn take_actions(&mut self) -> Vec<ScriptAction> {
+        std::mem::take(&mut self.shared.borrow_mut().actions)
+    }
+
+    // Errors are reported in the status window rather than returned, one bad script
+    // shouldn't stop the others or the message
+    fn call(&mut self, callback: &Callback, arg: Dynamic) -> Dynamic {
+        let Some(script) = self.scripts.iter_mut().find(|script| script.name == callback.script) else {
+            return Dynamic::UNIT;
+        };
+        let options = CallFnOptions::new().eval_ast(false).rewind_scope(true);
+        match self.engine.call_fn_with_options::<Dynamic>(options, &mut script.scope, &script.ast, &callback.function, (arg,)) {
+            Ok(result) => result,
+            Err(e) => {
+                let error = format!("Script {}: {}: {}", script.name, callback.function, e);
+                self.shared.borrow_mut().actions.push(ScriptAction::Status(error));
+                Dynamic::UNIT
+            }
+        }
+    }
+
+    fn forget_registrations(&mut self, name: &str) {
+        let mut shared = self.shared.borrow_mut();
+        shared.commands.retain(|_, callback| callback.script != name);
+        shared.hooks.retain(|(_, callback)| callback.script != name);
+    }
+
+    fn message_map(message: &Message) -> Map {
+        let mut map = Map::new();
+        map.insert("channel".into(), message.channel.clone().into());
+        map.insert("nickname".into(), message.nickname.clone().into());
+        map.insert("content".into(), message.content.clone().into());
+        map.insert("pubkey".into(), message.pubkey.clone().unwrap_or_default().into());
+        map.insert("is_own".into(), message.is_own.into());
+        map.insert("is_private".into(), message.is_private.into());
+        map.insert("is_mention".into(), message.is_mention.into());
+        map.insert("timestamp".into(), message.timestamp.timestamp().into());
+        map
+    }
+}
@@ -8,6 +8,9 @@ use ratatui::{
 
 use crate::app::{App, AppState, InputMode};
 
+// Shown for an nsec being typed, the same however long it is
+const SECRET_MASK: &str = "********";
+
 pub fn draw(f: &mut Frame<'_>, app: &mut App) {
     let size = f.size();
     
@@ -53,16 +56,17 @@ fn draw_title_bar(f: &mut Frame, app: &App, area: Rect) {
     
     let current_channel = app.current_channel.as_deref().unwrap_or("no channel");
     let version = env!("CARGO_PKG_VERSION");
+    let (connected_relays, _, total_relays) = app.nostr_client.relay_health();
     let title = format!(
         " BitchatX v{} | {} | #{} | {} ",
         version,
         app.identity.nickname,
         current_channel,
         match &app.state {
-            AppState::Connected => "connected",
-            AppState::Connecting => "connecting...",
-            AppState::Disconnected => "disconnected",
-            AppState::Error(e) => e,
+            AppState::Connected => format!("connected ({}/{} relays)", connected_relays, total_relays),
+            AppState::Connecting => "connecting...".to_string(),
+            AppState::Disconnected => "disconnected - reconnecting".to_string(),
+            AppState::Error(e) => e.clone(),
         }
     );
     
@@ -79,11 +83,19 @@ fn draw_title_bar(f: &mut Frame, app: &App, area: Rect) {
 }
 
 fn draw_chat_area(f: &mut Frame, app: &mut App, area: Rect) {
+    if app.search_results.is_some() {
+        draw_search_results(f, app, area);
+        return;
+    }
+    
     let block = Block::default()
         .borders(Borders::ALL)
         .title(if let Some(channel) = &app.current_channel {
             if channel == "system" {
                 " System Messages ".to_string()
+            } else if let Some(pubkey) = channel.strip_prefix("dm:") {
+                let nickname = app.private_chats.get(pubkey).map(|n| n.as_str()).unwrap_or("unknown");
+                format!(" Private: @{} ", nickname)
             } else {
                 format!(" Channel: #{} ", channel)
             }
@@ -122,18 +134,21 @@ fn draw_chat_area(f: &mut Frame, app: &mut App, area: Rect) {
             app.update_scroll_offset(effective_scroll_offset);
         }
         
-        for (timestamp, nickname, content, is_own, pubkey) in visible_messages {
-            let nick_color = if is_own { 
-                Color::Green 
+        for (timestamp, nickname, content, is_own, pubkey, is_mention, is_pending) in visible_messages {
+            let nick_style = if is_mention {
+                Style::default().fg(Color::Black).bg(Color::Yellow).add_modifier(Modifier::BOLD)
+            } else if is_own { 
+                Style::default().fg(Color::Green)
             } else { 
-                Color::Magenta 
+                Style::default().fg(Color::Magenta)
             };
             
             let display_nickname = app.format_display_nickname(&nickname, &pubkey);
             
             let mut message_spans = vec![
                 Span::styled(format!("[{}] ", timestamp), Style::default().fg(Color::Gray)),
-                Span::styled(format!("<{}> ", display_nickname), Style::default().fg(nick_color)),
+                Span::styled(format!("<{}>", display_nickname), nick_style),
+                Span::raw(" "),
             ];
             
             // Parse markdown formatting and track nostr URIs
@@ -162,6 +177,10 @@ fn draw_chat_area(f: &mut Frame, app: &mut App, area: Rect) {
             }
             
             let line = Line::from(message_spans);
+            // Mentions stand out from the rest of the channel
+            let line = if is_mention { line.style(Style::default().fg(Color::Yellow)) } else { line };
+            // Our own messages are greyed out until a relay accepts them
+            let line = if is_pending { line.style(Style::default().fg(Color::DarkGray)) } else { line };
             lines.push(line);
         }
     }
@@ -188,6 +207,56 @@ fn draw_chat_area(f: &mut Frame, app: &mut App, area: Rect) {
     f.render_widget(messages_widget, inner);
 }
 
+fn draw_search_results(f: &mut Frame, app: &App, area: Rect) {
+    let Some(results) = &app.search_results else {
+        return;
+    };
+    
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(
+            " {} ({} hits) - Up/Down select, Enter jump, Esc close ",
+            results.title,
+            results.hits.len()
+        ))
+        .style(Style::default().fg(Color::Cyan));
+    
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    
+    // Keep the selected hit in view
+    let height = (inner.height as usize).max(1);
+    let start = (results.selected + 1).saturating_sub(height);
+    
+    let lines: Vec<Line> = results.hits
+        .iter()
+        .enumerate()
+        .skip(start)
+        .take(height)
+        .map(|(index, hit)| {
+            let channel = match hit.channel.strip_prefix("dm:") {
+                Some(pubkey) => format!("@{}", app.private_chats.get(pubkey).map(|n| n.as_str()).unwrap_or("unknown")),
+                None => format!("#{}", hit.channel),
+            };
+            let timestamp = hit.timestamp.with_timezone(&chrono::Local).format("%m-%d %H:%M:%S");
+            
+            let line = Line::from(vec![
+                Span::styled(format!("{:<10} ", channel), Style::default().fg(Color::Yellow)),
+                Span::styled(format!("[{}] ", timestamp), Style::default().fg(Color::Gray)),
+                Span::styled(format!("<{}> ", app.format_display_nickname(&hit.nickname, &hit.pubkey)), Style::default().fg(Color::Magenta)),
+                Span::raw(hit.content.replace('\n', " ")),
+            ]);
+            if index == results.selected {
+                line.style(Style::default().add_modifier(Modifier::REVERSED))
+            } else {
+                line
+            }
+        })
+        .collect();
+    
+    f.render_widget(Paragraph::new(lines), inner);
+}
+
 fn draw_info_panel(f: &mut Frame<'_>, app: &App, area: Rect) {
     let chunks = Layout::default()
         .direction(Direction::Vertical)
@@ -212,7 +281,13 @@ fn draw_info_panel(f: &mut Frame<'_>, app: &App, area: Rect) {
         Line::from(vec![
             Span::raw("Type: "),
             Span::styled(
-                if app.identity.is_ephemeral { "ephemeral" } else { "authenticated" },
+                if app.identity.is_ephemeral {
+                    "ephemeral"
+                } else if app.identity.uses_remote_signer() {
+                    "remote signer"
+                } else {
+                    "authenticated"
+                },
                 Style::default().fg(if app.identity.is_ephemeral { Color::Yellow } else { Color::Green })
             ),
         ]),
@@ -221,6 +296,13 @@ fn draw_info_panel(f: &mut Frame<'_>, app: &App, area: Rect) {
             Span::styled(&app.identity.pubkey[..16], Style::default().fg(Color::Gray)),
             Span::styled("...", Style::default().fg(Color::Gray)),
         ]),
+        Line::from(vec![
+            Span::raw("Keys: "),
+            Span::styled(
+                if app.identity.uses_geohash_identities() { "per-geohash" } else { "shared" },
+                Style::default().fg(Color::Gray)
+            ),
+        ]),
     ];
     
     let identity_paragraph = Paragraph::new(identity_text).block(identity_block);
@@ -232,7 +314,7 @@ fn draw_info_panel(f: &mut Frame<'_>, app: &App, area: Rect) {
         .title(" Connection ")
         .style(Style::default().fg(Color::Blue));
         
-    let relay_count = app.nostr_client.get_relay_count();
+    let (connected_relays, _, total_relays) = app.nostr_client.relay_health();
     let connection_text = vec![
         Line::from(vec![
             Span::raw("Status: "),
@@ -252,7 +334,7 @@ fn draw_info_panel(f: &mut Frame<'_>, app: &App, area: Rect) {
         ]),
         Line::from(vec![
             Span::raw("Relays: "),
-            Span::styled(format!("{}", relay_count), Style::default().fg(Color::Cyan)),
+            Span::styled(format!("{}/{}", connected_relays, total_relays), Style::default().fg(Color::Cyan)),
         ]),
     ];
     
@@ -267,50 +349,47 @@ fn draw_info_panel(f: &mut Frame<'_>, app: &App, area: Rect) {
         
     let mut all_channels = Vec::new();
     
-    // Always show system channel first
-    let system_style = if app.current_channel.as_deref() == Some("system") {
-        Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)
-    } else {
-        Style::default().fg(Color::Cyan)
-    };
-    all_channels.push(ListItem::new("system").style(system_style));
-    
-    // Add all channels with messages (both joined and listening-only)
-    let all_channel_info = app.channel_manager.list_all_channels();
-    for (channel, is_joined) in all_channel_info {
-        if channel != "system" {  // Don't duplicate system channel
-            if channel.starts_with("dm:") {
-                // This is a private message channel
-                let pubkey = &channel[3..]; // Remove "dm:" prefix
-                if let Some(nickname) = app.private_chats.get(pubkey) {
-                    let style = if app.current_channel.as_deref() == Some(&channel) {
-                        Style::default().fg(Color::Magenta).add_modifier(Modifier::BOLD)
-                    } else {
-                        Style::default().fg(Color::Magenta)
-                    };
-                    
-                    let dm_label = format!("@{}", nickname);
-                    all_channels.push(ListItem::new(dm_label).style(style));
-                }
+    // Numbered like BitchX windows, the numbers the activity bar refers to
+    for (index, channel) in app.windows().iter().enumerate() {
+        let is_current = app.current_channel.as_deref() == Some(channel.as_str());
+        let (label, style) = if channel == "system" {
+            ("system".to_string(), Style::default().fg(Color::Cyan))
+        } else if let Some(pubkey) = channel.strip_prefix("dm:") {
+            // This is a private message channel
+            let nickname = app.private_chats.get(pubkey).map(|n| n.as_str()).unwrap_or("unknown");
+            (format!("@{}", nickname), Style::default().fg(Color::Magenta))
+        } else {
+            // Regular geohash channel
+            let is_joined = app.channel_manager.get_channel(channel).is_some_and(|c| c.is_joined);
+            let style = if is_current {
+                Style::default().fg(Color::Green)
+            } else if is_joined {
+                Style::default().fg(Color::White)
             } else {
-                // Regular geohash channel
-                let style = if app.current_channel.as_deref() == Some(&channel) {
-                    Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)
-                } else if is_joined {
-                    Style::default().fg(Color::White)
-                } else {
-                    Style::default().fg(Color::Gray)  // Different color for listening-only channels
-                };
-                
-                let active_users = app.channel_manager.get_active_user_count(&channel);
-                let channel_label = if is_joined {
-                    format!("#{} ({})", channel, active_users)
-                } else {
-                    format!("#{} ({})", channel, active_users)  // Show active user count for all channels
-                };
-                all_channels.push(ListItem::new(channel_label).style(style));
-            }
+                Style::default().fg(Color::Gray)  // Different color for listening-only channels
+            };
+            
+            let active_users = app.channel_manager.get_active_user_count(channel);
+            // Show active user count for all channels
+            (format!("#{} ({})", channel, active_users), style)
+        };
+        
+        let mut spans = vec![
+            Span::styled(format!("{} ", index + 1), Style::default().fg(Color::DarkGray)),
+            Span::styled(label, if is_current { style.add_modifier(Modifier::BOLD) } else { style }),
+        ];
+        
+        // Unread messages, and how many of them were for us
+        if let Some(unread) = app.channel_manager.get_channel(channel).filter(|c| c.unread > 0) {
+            let (counts, color) = if unread.unread_mentions > 0 {
+                (format!(" [{}/{}*]", unread.unread, unread.unread_mentions), Color::Yellow)
+            } else {
+                (format!(" [{}]", unread.unread), Color::White)
+            };
+            spans.push(Span::styled(counts, Style::default().fg(color).add_modifier(Modifier::BOLD)));
         }
+        
+        all_channels.push(ListItem::new(Line::from(spans)));
     }
     
     let channels_list = if all_channels.is_empty() {
@@ -324,16 +403,32 @@ fn draw_info_panel(f: &mut Frame<'_>, app: &App, area: Rect) {
     f.render_widget(channels_list, chunks[2]);
 }
 
+/// BitchX's `[Act: 2,4*]`: the numbers of channels with unread messages, `*` where
+/// some mentioned us
+fn activity_bar(app: &App) -> Option<String> {
+    let active: Vec<String> = app.windows()
+        .iter()
+        .enumerate()
+        .filter_map(|(index, name)| {
+            let channel = app.channel_manager.get_channel(name).filter(|c| c.unread > 0)?;
+            Some(format!("{}{}", index + 1, if channel.unread_mentions > 0 { "*" } else { "" }))
+        })
+        .collect();
+    (!active.is_empty()).then(|| format!("[Act: {}]", active.join(",")))
+}
+
 fn draw_input_area(f: &mut Frame, app: &mut App, area: Rect) {
     let input_style = match app.input_mode {
         InputMode::Normal => Style::default().fg(Color::White),
         InputMode::Editing => Style::default().fg(Color::Green),
     };
     
-    let mode_indicator = match app.input_mode {
+    let mut mode_indicator = match app.input_mode {
         InputMode::Normal => "[NORMAL] Press 'i' to enter input mode".to_string(),
         InputMode::Editing => {
-            if let Some(ref state) = app.tab_completion_state {
+            if app.awaiting_nsec {
+                "[SECRET] Enter nsec - ENTER=load identity, ESC=cancel".to_string()
+            } else if let Some(ref state) = app.tab_completion_state {
                 format!("[INPUT] TAB completion: {} ({}/{})", 
                     state.matches[state.current_match_index],
                     state.current_match_index + 1, 
@@ -344,6 +439,21 @@ fn draw_input_area(f: &mut Frame, app: &mut App, area: Rect) {
         }
     };
     
+    // Proof of work progress for the oldest message still being mined
+    if let Some(job) = app.pow_jobs.iter().find(|job| !job.is_finished()) {
+        let others = app.pow_jobs.len() - 1;
+        mode_indicator.push_str(&format!(" | ⛏ PoW {} bits for #{}: {} hashes, {}s{} - /pow cancel",
+            job.difficulty,
+            job.channel,
+            job.attempts(),
+            job.elapsed().as_secs(),
+            if others > 0 { format!(" (+{} more)", others) } else { String::new() }));
+    }
+    
+    if let Some(activity) = activity_bar(app) {
+        mode_indicator.push_str(&format!(" | {}", activity));
+    }
+    
     let input_block = Block::default()
         .borders(Borders::ALL)
         .title(mode_indicator)
@@ -358,7 +468,12 @@ fn draw_input_area(f: &mut Frame, app: &mut App, area: Rect) {
         app.update_input_scroll_with_width(app.input_width);
     }
     
-    let input_text = if app.input_mode == InputMode::Editing {
+    // Never draw a secret being typed, not even its length
+    let secret_mask = if app.input.is_empty() { "" } else { SECRET_MASK };
+    
+    let input_text = if app.input_mode == InputMode::Editing && app.awaiting_nsec {
+        secret_mask
+    } else if app.input_mode == InputMode::Editing {
         let text = app.input.as_str();
         let scroll_start = app.input_horizontal_scroll;
         
@@ -386,7 +501,11 @@ fn draw_input_area(f: &mut Frame, app: &mut App, area: Rect) {
     // Set cursor position when in editing mode with horizontal scrolling
     if app.input_mode == InputMode::Editing {
         // Calculate visible cursor position accounting for horizontal scroll
-        let cursor_x = (app.cursor_position as i16 - app.input_horizontal_scroll as i16).max(0) as u16;
+        let cursor_x = if app.awaiting_nsec {
+            secret_mask.len() as u16
+        } else {
+            (app.cursor_position as i16 - app.input_horizontal_scroll as i16).max(0) as u16
+        };
         let cursor_y = 0; // First line of inner area (0-indexed)
         
         // Ensure cursor stays within inner area bounds